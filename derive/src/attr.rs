@@ -0,0 +1,178 @@
+//! Parsing `#[visit(...)]` attributes.
+
+use proc_macro2::Span;
+use syn::{spanned::Spanned, Attribute, LitStr, Path};
+
+/// The attributes on a container, variant, or field.
+///
+/// Which attributes are allowed depends on where they appear.
+#[derive(Default)]
+pub(crate) struct Attrs {
+    pub(crate) tag: Option<String>,
+    pub(crate) content: Option<String>,
+    pub(crate) untagged: bool,
+    pub(crate) rename: Option<String>,
+    pub(crate) rename_all: Option<RenameAll>,
+    pub(crate) skip: bool,
+    pub(crate) skip_if: Option<Path>,
+    pub(crate) flatten: bool,
+    pub(crate) with: Option<Path>,
+    pub(crate) span: Option<Span>,
+}
+
+impl Attrs {
+    /// Parse the `#[visit(...)]` attributes, failing on any that aren't `allowed`.
+    pub(crate) fn parse(
+        attrs: &[Attribute],
+        allowed: &[&str],
+        position: &str,
+    ) -> syn::Result<Self> {
+        let mut parsed = Attrs::default();
+
+        for attr in attrs.iter().filter(|attr| attr.path().is_ident("visit")) {
+            parsed.span = Some(attr.span());
+
+            attr.parse_nested_meta(|meta| {
+                let key = match meta.path.get_ident() {
+                    Some(ident) if allowed.iter().any(|allowed| ident == allowed) => {
+                        ident.to_string()
+                    }
+                    Some(ident) if KNOWN.iter().any(|known| ident == known) => {
+                        return Err(
+                            meta.error(format_args!("`{}` can't be used on {}", ident, position))
+                        )
+                    }
+                    _ => return Err(meta.error("unsupported `visit` attribute")),
+                };
+
+                match &*key {
+                    "tag" => parsed.tag = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "content" => parsed.content = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "untagged" => parsed.untagged = true,
+                    "rename" => parsed.rename = Some(meta.value()?.parse::<LitStr>()?.value()),
+                    "rename_all" => {
+                        let lit = meta.value()?.parse::<LitStr>()?;
+
+                        parsed.rename_all =
+                            Some(RenameAll::parse(&lit.value()).ok_or_else(|| {
+                                syn::Error::new(lit.span(), "unsupported case convention")
+                            })?);
+                    }
+                    "skip" => parsed.skip = true,
+                    "skip_if" => parsed.skip_if = Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                    "flatten" => parsed.flatten = true,
+                    "with" => parsed.with = Some(meta.value()?.parse::<LitStr>()?.parse()?),
+                    _ => unreachable!(),
+                }
+
+                Ok(())
+            })?;
+        }
+
+        Ok(parsed)
+    }
+
+    pub(crate) fn span(&self) -> Span {
+        self.span.unwrap_or_else(Span::call_site)
+    }
+}
+
+const KNOWN: &[&str] = &[
+    "tag",
+    "content",
+    "untagged",
+    "rename",
+    "rename_all",
+    "skip",
+    "skip_if",
+    "flatten",
+    "with",
+];
+
+/// A case convention for `rename_all`, with the same names as `serde`.
+#[derive(Clone, Copy)]
+pub(crate) enum RenameAll {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameAll {
+    fn parse(rule: &str) -> Option<Self> {
+        Some(match rule {
+            "lowercase" => RenameAll::Lower,
+            "UPPERCASE" => RenameAll::Upper,
+            "PascalCase" => RenameAll::Pascal,
+            "camelCase" => RenameAll::Camel,
+            "snake_case" => RenameAll::Snake,
+            "SCREAMING_SNAKE_CASE" => RenameAll::ScreamingSnake,
+            "kebab-case" => RenameAll::Kebab,
+            "SCREAMING-KEBAB-CASE" => RenameAll::ScreamingKebab,
+            _ => return None,
+        })
+    }
+
+    /// Rename a field, which is expected to be `snake_case`.
+    pub(crate) fn field(self, field: &str) -> String {
+        match self {
+            RenameAll::Lower | RenameAll::Snake => field.to_owned(),
+            RenameAll::Upper | RenameAll::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameAll::Pascal => field
+                .split('_')
+                .map(|word| {
+                    let mut chars = word.chars();
+                    chars
+                        .next()
+                        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+                        .unwrap_or_default()
+                })
+                .collect(),
+            RenameAll::Camel => {
+                let pascal = RenameAll::Pascal.field(field);
+                let mut chars = pascal.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            }
+            RenameAll::Kebab => field.replace('_', "-"),
+            RenameAll::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+
+    /// Rename a variant, which is expected to be `PascalCase`.
+    pub(crate) fn variant(self, variant: &str) -> String {
+        match self {
+            RenameAll::Lower => variant.to_ascii_lowercase(),
+            RenameAll::Upper => variant.to_ascii_uppercase(),
+            RenameAll::Pascal => variant.to_owned(),
+            RenameAll::Camel => {
+                let mut chars = variant.chars();
+                chars
+                    .next()
+                    .map(|first| first.to_ascii_lowercase().to_string() + chars.as_str())
+                    .unwrap_or_default()
+            }
+            RenameAll::Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            }
+            RenameAll::ScreamingSnake => RenameAll::Snake.variant(variant).to_ascii_uppercase(),
+            RenameAll::Kebab => RenameAll::Snake.variant(variant).replace('_', "-"),
+            RenameAll::ScreamingKebab => {
+                RenameAll::ScreamingSnake.variant(variant).replace('_', "-")
+            }
+        }
+    }
+}
@@ -0,0 +1,628 @@
+//! `#[derive(Visit)]` for `ser`.
+//!
+//! This crate is re-exported by `ser` when its `derive` feature is enabled.
+//! Use it through `ser::Visit` instead of depending on it directly.
+
+extern crate proc_macro;
+
+mod attr;
+
+use proc_macro2::{Ident, Span, TokenStream, TokenTree};
+use quote::{format_ident, quote, ToTokens};
+use syn::{
+    ext::IdentExt, parse_macro_input, parse_quote, punctuated::Punctuated, Data, DataEnum,
+    DataStruct, DeriveInput, Field, Fields, Generics, Index, Path, Token, TraitBoundModifier, Type,
+    TypeParamBound, WherePredicate,
+};
+
+use self::attr::{Attrs, RenameAll};
+
+/// Derive `ser::Visit` for a struct or enum.
+///
+/// Structs with named fields are visited as records, tuple structs as
+/// sequences, newtype structs as their inner value, and unit structs as
+/// unit. The type must also implement `Debug`. Newtype structs can use
+/// `#[visit(tag = "...")]` to announce a semantic type for their inner
+/// value through `Visitor::visit_tag`. The tag isn't seen through `serde_interop`.
+///
+/// Enums are externally tagged by default, like in `serde`. They can use
+/// `#[visit(tag = "...")]` to be internally tagged, `#[visit(tag = "...", content = "...")]`
+/// to be adjacently tagged, or `#[visit(untagged)]` to be untagged.
+///
+/// Containers and variants can use `#[visit(rename = "...")]` and
+/// `#[visit(rename_all = "...")]` with the same case conventions as `serde`.
+/// Fields can use `#[visit(rename = "...")]`, `#[visit(skip)]`, and
+/// `#[visit(skip_if = "path")]`, where `path` is a function that takes
+/// a reference to the field and returns whether to skip it. Named fields
+/// can also use `#[visit(flatten)]` to visit the fields of a nested record
+/// or map inline as fields of the parent record.
+///
+/// Fields can use `#[visit(with = "path")]` to visit them with a function
+/// instead of their `Visit` implementation. The function has the signature
+/// `fn<'v>(&'v T, &mut dyn ser::Visitor<'v>) -> Result<(), ser::Error>`.
+#[proc_macro_derive(Visit, attributes(visit))]
+pub fn derive_visit(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn expand(mut input: DeriveInput) -> syn::Result<TokenStream> {
+    let (body, stream, bounded) = match input.data {
+        Data::Struct(ref data) => {
+            let attrs = Attrs::parse(&input.attrs, &["tag", "rename", "rename_all"], "structs")?;
+            let name = attrs
+                .rename
+                .clone()
+                .unwrap_or_else(|| input.ident.unraw().to_string());
+
+            let (body, bounded) = expand_struct(&name, &attrs, data, Mode::Visit)?;
+
+            // Unsized structs can't be streamed, so they use the default `stream`
+            let stream = match data.fields.iter().last() {
+                Some(field) if is_unsized(&field.ty, &input.generics) => None,
+                _ => Some(expand_struct(&name, &attrs, data, Mode::Stream)?.0),
+            };
+
+            (body, stream, bounded)
+        }
+        Data::Enum(ref data) => {
+            let attrs = Attrs::parse(
+                &input.attrs,
+                &["tag", "content", "untagged", "rename", "rename_all"],
+                "enums",
+            )?;
+            let name = attrs
+                .rename
+                .clone()
+                .unwrap_or_else(|| input.ident.unraw().to_string());
+
+            let (body, bounded) = expand_enum(&name, &attrs, data, Mode::Visit)?;
+            let (stream, _) = expand_enum(&name, &attrs, data, Mode::Stream)?;
+
+            (body, Some(stream), bounded)
+        }
+        Data::Union(_) => {
+            return Err(syn::Error::new(
+                Span::call_site(),
+                "`#[derive(Visit)]` doesn't support unions",
+            ))
+        }
+    };
+
+    // Bound the types of fields that use type parameters, instead of the
+    // parameters themselves, so bounds hold with `serde_interop` too.
+    // `Visit` also needs `Debug`, which usually depends on the parameters
+    let params: Vec<Ident> = input
+        .generics
+        .type_params()
+        .map(|param| param.ident.clone())
+        .collect();
+    if !params.is_empty() {
+        let bounded: Vec<Type> = bounded
+            .into_iter()
+            .filter(|ty| mentions(ty.to_token_stream(), &params))
+            .collect();
+
+        let where_clause = input.generics.make_where_clause();
+        where_clause
+            .predicates
+            .push(parse_quote!(Self: ::core::fmt::Debug));
+        for ty in bounded {
+            where_clause
+                .predicates
+                .push(parse_quote!(#ty: ::ser::Visit));
+        }
+    }
+
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let stream = stream.map(|stream| {
+        quote! {
+            |self, visitor| {
+                #stream
+            }
+        }
+    });
+
+    Ok(quote! {
+        ::ser::__impl_visit! {
+            [#impl_generics] #ident #ty_generics, [#where_clause]
+            |self, visitor| {
+                #body
+            }
+            #stream
+        }
+    })
+}
+
+/// Whether a type is a slice, `str`, trait object, or `?Sized` type parameter.
+fn is_unsized(ty: &Type, generics: &Generics) -> bool {
+    let maybe_sized = |bounds: &Punctuated<TypeParamBound, Token![+]>| {
+        bounds.iter().any(|bound| match bound {
+            TypeParamBound::Trait(bound) => matches!(bound.modifier, TraitBoundModifier::Maybe(_)),
+            _ => false,
+        })
+    };
+
+    match ty {
+        Type::Slice(_) | Type::TraitObject(_) => true,
+        Type::Group(ty) => is_unsized(&ty.elem, generics),
+        Type::Paren(ty) => is_unsized(&ty.elem, generics),
+        Type::Path(ty) if ty.qself.is_none() => {
+            let ident = match ty.path.get_ident() {
+                Some(ident) => ident,
+                None => return false,
+            };
+
+            ident == "str"
+                || generics
+                    .type_params()
+                    .any(|param| param.ident == *ident && maybe_sized(&param.bounds))
+                || generics.where_clause.iter().any(|where_clause| {
+                    where_clause
+                        .predicates
+                        .iter()
+                        .any(|predicate| match predicate {
+                            WherePredicate::Type(predicate) => match predicate.bounded_ty {
+                                Type::Path(ref bounded) => {
+                                    bounded.path.is_ident(ident) && maybe_sized(&predicate.bounds)
+                                }
+                                _ => false,
+                            },
+                            _ => false,
+                        })
+                })
+        }
+        _ => false,
+    }
+}
+
+fn mentions(tokens: TokenStream, params: &[Ident]) -> bool {
+    tokens.into_iter().any(|token| match token {
+        TokenTree::Ident(ident) => params.contains(&ident),
+        TokenTree::Group(group) => mentions(group.stream(), params),
+        TokenTree::Punct(_) | TokenTree::Literal(_) => false,
+    })
+}
+
+/// Whether a body is generated for `Visit::visit` or `Visit::stream`.
+///
+/// Both bodies call the same methods on `visitor`, which is either a
+/// `&mut dyn ser::Visitor` or a generic `ser::Stream`.
+#[derive(Clone, Copy)]
+enum Mode {
+    Visit,
+    Stream,
+}
+
+impl Mode {
+    /// Visit through a `&mut dyn ser::Visitor`.
+    ///
+    /// Streams are wrapped in a `ser::Streamer` for fields that
+    /// can only be visited through a trait object.
+    fn dyn_visitor(self, visit: impl FnOnce(TokenStream) -> TokenStream) -> TokenStream {
+        match self {
+            Mode::Visit => visit(quote!(&mut *visitor)),
+            Mode::Stream => {
+                let visit = visit(quote!(&mut __streamer));
+
+                quote! {
+                    {
+                        let mut __streamer = ::ser::Streamer::new(&mut *visitor);
+                        let __r = #visit;
+                        __streamer.into_result(__r)
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A field to visit, and an expression that borrows its value.
+struct Binding {
+    name: String,
+    value: TokenStream,
+    ty: Type,
+    skip: bool,
+    skip_if: Option<Path>,
+    flatten: bool,
+    with: Option<Path>,
+}
+
+impl Binding {
+    /// Visit the field's value.
+    fn visit(&self, mode: Mode) -> TokenStream {
+        let value = &self.value;
+
+        match (&self.with, mode) {
+            (Some(with), _) => mode.dyn_visitor(|visitor| quote!(#with(#value, #visitor))),
+            (None, Mode::Visit) => quote!(::ser::Visit::visit(#value, visitor)),
+            (None, Mode::Stream) => quote!(::ser::Visit::stream(#value, visitor)),
+        }
+    }
+
+    /// Visit the field's value as fields of the enclosing record.
+    fn flatten(&self, mode: Mode) -> TokenStream {
+        let value = &self.value;
+
+        mode.dyn_visitor(|visitor| {
+            let visitor = quote!(&mut ::ser::__private::Flatten::new(#visitor));
+
+            match self.with {
+                Some(ref with) => quote!(#with(#value, #visitor)),
+                None => quote!(::ser::Visit::visit(#value, #visitor)),
+            }
+        })
+    }
+}
+
+/// The shape of a struct or variant's fields.
+enum Shape {
+    Record(Vec<Binding>),
+    Tuple(Vec<Binding>),
+    Unit,
+}
+
+impl Shape {
+    fn new(
+        fields: &Fields,
+        rename_all: Option<RenameAll>,
+        mut value: impl FnMut(usize, &Field) -> TokenStream,
+    ) -> syn::Result<Self> {
+        let mut bindings = Vec::new();
+
+        for (i, field) in fields.iter().enumerate() {
+            let attrs = Attrs::parse(
+                &field.attrs,
+                &["rename", "skip", "skip_if", "flatten", "with"],
+                "fields",
+            )?;
+
+            if attrs.flatten && field.ident.is_none() {
+                return Err(syn::Error::new(
+                    attrs.span(),
+                    "`flatten` can only be used on named fields",
+                ));
+            }
+
+            let name = match (attrs.rename, &field.ident) {
+                (Some(rename), _) => rename,
+                (None, Some(ident)) => {
+                    let name = ident.unraw().to_string();
+                    rename_all.map_or(name.clone(), |rename_all| rename_all.field(&name))
+                }
+                (None, None) => i.to_string(),
+            };
+
+            bindings.push(Binding {
+                name,
+                value: value(i, field),
+                ty: field.ty.clone(),
+                skip: attrs.skip,
+                skip_if: attrs.skip_if,
+                flatten: attrs.flatten,
+                with: attrs.with,
+            });
+        }
+
+        match fields {
+            Fields::Named(_) => Ok(Shape::Record(bindings)),
+            Fields::Unnamed(_) => match bindings[..] {
+                [ref binding] if binding.skip || binding.skip_if.is_some() => Err(
+                    syn::Error::new_spanned(fields, "the field of a newtype can't be skipped"),
+                ),
+                _ => Ok(Shape::Tuple(bindings)),
+            },
+            Fields::Unit => Ok(Shape::Unit),
+        }
+    }
+
+    fn bindings(&self) -> &[Binding] {
+        match self {
+            Shape::Record(bindings) | Shape::Tuple(bindings) => bindings,
+            Shape::Unit => &[],
+        }
+    }
+
+    /// The types of fields that are visited through their `Visit` implementations.
+    fn visited_types(&self) -> impl Iterator<Item = Type> + '_ {
+        self.bindings()
+            .iter()
+            .filter(|binding| !binding.skip && binding.with.is_none())
+            .map(|binding| binding.ty.clone())
+    }
+
+    /// Visit the fields, using the name for records.
+    fn visit(&self, name: &str, mode: Mode) -> TokenStream {
+        match self {
+            Shape::Record(bindings) => visit_record(name, None, bindings, mode),
+            Shape::Tuple(bindings) if bindings.len() == 1 => bindings[0].visit(mode),
+            Shape::Tuple(bindings) => {
+                let len = len(0, bindings);
+                let elems = bindings
+                    .iter()
+                    .filter(|binding| !binding.skip)
+                    .map(|binding| {
+                        let visit = binding.visit(mode);
+
+                        skip_if(
+                            binding,
+                            quote! {
+                                visitor.seq_elem()?;
+                                #visit?;
+                            },
+                        )
+                    });
+
+                quote! {
+                    visitor.seq_begin(::core::option::Option::Some(#len))?;
+                    #(#elems)*
+                    visitor.seq_end()
+                }
+            }
+            Shape::Unit => quote! {
+                visitor.visit_unit()
+            },
+        }
+    }
+}
+
+/// The number of visited fields, which depends on the values of fields with `skip_if`.
+fn len(leading: usize, bindings: &[Binding]) -> TokenStream {
+    let len = leading + bindings.iter().filter(|binding| !binding.skip).count();
+    let skipped = bindings
+        .iter()
+        .filter(|binding| !binding.skip)
+        .filter_map(|binding| {
+            let value = &binding.value;
+
+            binding
+                .skip_if
+                .as_ref()
+                .map(|skip_if| quote!(- (#skip_if(#value) as usize)))
+        });
+
+    quote!(#len #(#skipped)*)
+}
+
+fn skip_if(binding: &Binding, visit: TokenStream) -> TokenStream {
+    match binding.skip_if {
+        Some(ref skip_if) => {
+            let value = &binding.value;
+
+            quote! {
+                if !#skip_if(#value) {
+                    #visit
+                }
+            }
+        }
+        None => visit,
+    }
+}
+
+/// Visit a record, with a leading field for the tag of an internally tagged enum.
+///
+/// Records with flattened fields don't have a known length.
+fn visit_record(
+    name: &str,
+    leading: Option<(&str, &str)>,
+    bindings: &[Binding],
+    mode: Mode,
+) -> TokenStream {
+    let len = if bindings
+        .iter()
+        .any(|binding| binding.flatten && !binding.skip)
+    {
+        quote!(::core::option::Option::None)
+    } else {
+        let len = len(leading.iter().count(), bindings);
+        quote!(::core::option::Option::Some(#len))
+    };
+    let leading = leading.map(|(field, value)| {
+        quote! {
+            visitor.record_field(#field)?;
+            visitor.visit_borrowed_str(#value)?;
+        }
+    });
+    let fields = bindings
+        .iter()
+        .filter(|binding| !binding.skip)
+        .map(|binding| {
+            let name = &binding.name;
+
+            let visit = if binding.flatten {
+                let visit = binding.flatten(mode);
+
+                quote! {
+                    #visit?;
+                }
+            } else {
+                let visit = binding.visit(mode);
+
+                quote! {
+                    visitor.record_field(#name)?;
+                    #visit?;
+                }
+            };
+
+            skip_if(binding, visit)
+        });
+
+    quote! {
+        visitor.record_begin(#name, #len)?;
+        #leading
+        #(#fields)*
+        visitor.record_end()
+    }
+}
+
+fn expand_struct(
+    name: &str,
+    attrs: &Attrs,
+    data: &DataStruct,
+    mode: Mode,
+) -> syn::Result<(TokenStream, Vec<Type>)> {
+    let shape = Shape::new(&data.fields, attrs.rename_all, |i, field| {
+        match field.ident {
+            Some(ref ident) => quote!(&self.#ident),
+            None => {
+                let index = Index::from(i);
+                quote!(&self.#index)
+            }
+        }
+    })?;
+
+    let body = match (&shape, &attrs.tag) {
+        (Shape::Tuple(bindings), Some(tag)) if bindings.len() == 1 => {
+            let visit = shape.visit(name, mode);
+
+            quote! {
+                visitor.visit_tag(#tag)?;
+                #visit
+            }
+        }
+        (_, Some(_)) => {
+            return Err(syn::Error::new(
+                attrs.span(),
+                "`tag` can only be used on newtype structs",
+            ))
+        }
+        (_, None) => shape.visit(name, mode),
+    };
+
+    Ok((body, shape.visited_types().collect()))
+}
+
+fn expand_enum(
+    name: &str,
+    attrs: &Attrs,
+    data: &DataEnum,
+    mode: Mode,
+) -> syn::Result<(TokenStream, Vec<Type>)> {
+    if data.variants.is_empty() {
+        return Ok((
+            quote! {
+                match *self {}
+            },
+            Vec::new(),
+        ));
+    }
+
+    let mut arms = Vec::new();
+    let mut bounded = Vec::new();
+
+    for (index, variant) in data.variants.iter().enumerate() {
+        let variant_attrs = Attrs::parse(&variant.attrs, &["rename", "rename_all"], "variants")?;
+
+        let ident = &variant.ident;
+        let variant_name = match (variant_attrs.rename, attrs.rename_all) {
+            (Some(rename), _) => rename,
+            (None, Some(rename_all)) => rename_all.variant(&variant.ident.unraw().to_string()),
+            (None, None) => variant.ident.unraw().to_string(),
+        };
+        let index = index as u32;
+
+        let shape = Shape::new(&variant.fields, variant_attrs.rename_all, |i, _| {
+            format_ident!("__binding{}", i).into_token_stream()
+        })?;
+        bounded.extend(shape.visited_types());
+
+        let bindings = shape.bindings().iter().enumerate().map(|(i, binding)| {
+            if binding.skip {
+                quote!(_)
+            } else {
+                format_ident!("__binding{}", i).into_token_stream()
+            }
+        });
+        let pattern = match variant.fields {
+            Fields::Named(ref fields) => {
+                let fields = fields.named.iter().map(|field| &field.ident);
+
+                quote!(Self::#ident { #(#fields: #bindings),* })
+            }
+            Fields::Unnamed(_) => quote!(Self::#ident(#(#bindings),*)),
+            Fields::Unit => quote!(Self::#ident),
+        };
+
+        let body = match (&attrs.tag, &attrs.content, attrs.untagged) {
+            (None, None, false) => match shape {
+                Shape::Unit => quote! {
+                    visitor.visit_variant(#name, #variant_name, #index)
+                },
+                _ => {
+                    let data = shape.visit(&variant_name, mode);
+
+                    quote! {
+                        visitor.variant_begin(#name, #variant_name, #index)?;
+                        { #data }?;
+                        visitor.variant_end()
+                    }
+                }
+            },
+            (Some(tag), None, false) => match shape {
+                Shape::Record(ref bindings) => {
+                    visit_record(name, Some((tag, &variant_name)), bindings, mode)
+                }
+                Shape::Unit => visit_record(name, Some((tag, &variant_name)), &[], mode),
+                Shape::Tuple(ref bindings) if bindings.len() == 1 => {
+                    let visit = bindings[0].flatten(mode);
+
+                    quote! {
+                        visitor.record_begin(#name, ::core::option::Option::None)?;
+                        visitor.record_field(#tag)?;
+                        visitor.visit_borrowed_str(#variant_name)?;
+                        #visit?;
+                        visitor.record_end()
+                    }
+                }
+                Shape::Tuple(_) => {
+                    return Err(syn::Error::new_spanned(
+                        variant,
+                        "internally tagged enums can't contain tuple variants",
+                    ))
+                }
+            },
+            (Some(tag), Some(content), false) => match shape {
+                Shape::Unit => visit_record(name, Some((tag, &variant_name)), &[], mode),
+                _ => {
+                    let data = shape.visit(&variant_name, mode);
+
+                    quote! {
+                        visitor.record_begin(#name, ::core::option::Option::Some(2))?;
+                        visitor.record_field(#tag)?;
+                        visitor.visit_borrowed_str(#variant_name)?;
+                        visitor.record_field(#content)?;
+                        { #data }?;
+                        visitor.record_end()
+                    }
+                }
+            },
+            (None, None, true) => shape.visit(&variant_name, mode),
+            (None, Some(_), false) => {
+                return Err(syn::Error::new(attrs.span(), "`content` needs a `tag` too"))
+            }
+            (_, _, true) => {
+                return Err(syn::Error::new(
+                    attrs.span(),
+                    "`untagged` can't be used with `tag` or `content`",
+                ))
+            }
+        };
+
+        arms.push(quote! {
+            #pattern => { #body }
+        });
+    }
+
+    Ok((
+        quote! {
+            match self {
+                #(#arms)*
+            }
+        },
+        bounded,
+    ))
+}
@@ -0,0 +1,1390 @@
+//! Adapters that wrap a `Visitor` to change its behavior.
+
+use crate::std::{any::Any, fmt, time};
+
+use crate::{BytesHint, Capability, Error, Visitor};
+
+/// A visitor that limits how deeply values can be nested.
+///
+/// Each map, sequence, record, and enum variant increases the depth by one.
+/// When the maximum depth is exceeded, visiting fails with an error instead
+/// of continuing to recurse.
+#[derive(Debug)]
+pub struct DepthLimit<V> {
+    visitor: V,
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<V> DepthLimit<V> {
+    /// Wrap a visitor, limiting values to the given maximum depth.
+    pub fn new(visitor: V, max_depth: usize) -> Self {
+        DepthLimit {
+            visitor,
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    /// Get the current depth.
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    /// Get the inner visitor.
+    pub fn into_inner(self) -> V {
+        self.visitor
+    }
+
+    fn enter(&mut self) -> Result<(), Error> {
+        if self.depth >= self.max_depth {
+            return Err(Error::msg("the maximum depth was exceeded"));
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn exit(&mut self) {
+        self.depth = self.depth.saturating_sub(1);
+    }
+}
+
+impl<'v, V> Visitor<'v> for DepthLimit<V>
+where
+    V: Visitor<'v>,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.visitor.visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.visitor.visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.visitor.visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.visitor.visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.visitor.visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.visitor.visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.visitor.visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.visitor.visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.visitor.visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.visitor.visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.visitor.visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        self.visitor.visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.visitor.visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        self.visitor.visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_str(v)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        self.visitor.visit_borrowed_str(v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.visitor.str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.visitor.str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.visitor.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        self.visitor.visit_borrowed_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        self.visitor.visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.visitor.visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.visitor.visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visitor.visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.enter()?;
+        self.visitor.map_begin(len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.visitor.map_key()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.visitor.map_value()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.exit();
+        self.visitor.map_end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.enter()?;
+        self.visitor.seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.visitor.seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.exit();
+        self.visitor.seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        self.enter()?;
+        self.visitor.record_begin(name, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.visitor.record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.exit();
+        self.visitor.record_end()
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.visitor.visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.enter()?;
+        self.visitor.variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.exit();
+        self.visitor.variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        self.visitor.visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        self.visitor.visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.visitor.visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        self.visitor.visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.visitor.visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        self.visitor.visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.visitor.context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.visitor.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.visitor.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.visitor.visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.visitor.visit_fmt(args)
+    }
+}
+
+/// A visitor that forwards everything it visits to two other visitors.
+///
+/// The first visitor is always visited before the second.
+/// If either visitor fails then visiting stops.
+///
+/// Capabilities are only used if both visitors support them. Values are
+/// visited in their human-readable form if either visitor is human-readable,
+/// because every format can represent that form, like a uuid as a string,
+/// while compact forms, like a uuid as bytes, don't suit text formats.
+#[derive(Debug)]
+pub struct Tee<A, B> {
+    a: A,
+    b: B,
+}
+
+impl<A, B> Tee<A, B> {
+    /// Wrap two visitors.
+    pub fn new(a: A, b: B) -> Self {
+        Tee { a, b }
+    }
+
+    /// Get the inner visitors.
+    pub fn into_inner(self) -> (A, B) {
+        (self.a, self.b)
+    }
+}
+
+impl<'v, A, B> Visitor<'v> for Tee<A, B>
+where
+    A: Visitor<'v>,
+    B: Visitor<'v>,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.a.visit_i8(v)?;
+        self.b.visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.a.visit_i16(v)?;
+        self.b.visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.a.visit_i32(v)?;
+        self.b.visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.a.visit_u8(v)?;
+        self.b.visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.a.visit_u16(v)?;
+        self.b.visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.a.visit_u32(v)?;
+        self.b.visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.a.visit_i64(v)?;
+        self.b.visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.a.visit_u64(v)?;
+        self.b.visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.a.visit_i128(v)?;
+        self.b.visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.a.visit_u128(v)?;
+        self.b.visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.a.visit_f64(v)?;
+        self.b.visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        self.a.visit_f64_nonfinite(v)?;
+        self.b.visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        self.a.visit_number_str(v)?;
+        self.b.visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.a.visit_bool(v)?;
+        self.b.visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        self.a.visit_char(v)?;
+        self.b.visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.a.visit_str(v)?;
+        self.b.visit_str(v)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        self.a.visit_borrowed_str(v)?;
+        self.b.visit_borrowed_str(v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.a.str_begin(len)?;
+        self.b.str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        self.a.visit_str_fragment(v)?;
+        self.b.visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.a.str_end()?;
+        self.b.str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.a.visit_bytes(v)?;
+        self.b.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        self.a.visit_borrowed_bytes(v)?;
+        self.b.visit_borrowed_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        self.a.visit_bytes_hint(v, hint)?;
+        self.b.visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.a.visit_none()?;
+        self.b.visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.a.visit_some()?;
+        self.b.visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.a.visit_unit()?;
+        self.b.visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.a.map_begin(len)?;
+        self.b.map_begin(len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.a.map_key()?;
+        self.b.map_key()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.a.map_value()?;
+        self.b.map_value()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.a.map_end()?;
+        self.b.map_end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.a.seq_begin(len)?;
+        self.b.seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.a.seq_elem()?;
+        self.b.seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.a.seq_end()?;
+        self.b.seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        self.a.record_begin(name, len)?;
+        self.b.record_begin(name, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.a.record_field(name)?;
+        self.b.record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.a.record_end()?;
+        self.b.record_end()
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.a.visit_variant(name, variant, index)?;
+        self.b.visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.a.variant_begin(name, variant, index)?;
+        self.b.variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.a.variant_end()?;
+        self.b.variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        self.a.visit_tag(tag)?;
+        self.b.visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        self.a.visit_duration(v)?;
+        self.b.visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.a.visit_timestamp(secs, nanos)?;
+        self.b.visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        self.a.visit_display(v)?;
+        self.b.visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.a.visit_debug(v)?;
+        self.b.visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        self.a.visit_error(v)?;
+        self.b.visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.a.context().or_else(|| self.b.context())
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.a.supports(capability) && self.b.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.a.is_human_readable() || self.b.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.a.visit_fallback(v)?;
+        self.b.visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.a.visit_fmt(args)?;
+        self.b.visit_fmt(args)
+    }
+}
+
+/// A visitor that skips record fields that don't match a filter.
+///
+/// When a field is skipped, its value is skipped along with it.
+/// Since the number of fields that will be visited isn't known
+/// upfront, records are always begun without a length hint.
+pub struct Filtered<V, F> {
+    visitor: V,
+    filter: F,
+    depth: usize,
+    skip: Option<usize>,
+}
+
+impl<V, F> Filtered<V, F> {
+    /// Wrap a visitor, only visiting record fields whose names match the filter.
+    pub fn new(visitor: V, filter: F) -> Self {
+        Filtered {
+            visitor,
+            filter,
+            depth: 0,
+            skip: None,
+        }
+    }
+
+    /// Get the inner visitor.
+    pub fn into_inner(self) -> V {
+        self.visitor
+    }
+
+    fn value(&mut self) -> bool {
+        match self.skip {
+            Some(depth) => {
+                if self.depth == depth {
+                    self.skip = None;
+                }
+
+                false
+            }
+            None => true,
+        }
+    }
+
+    fn begin(&mut self) -> bool {
+        self.depth += 1;
+        self.skip.is_none()
+    }
+
+    fn end(&mut self) -> bool {
+        self.depth = self.depth.saturating_sub(1);
+        self.value()
+    }
+}
+
+impl<V, F> fmt::Debug for Filtered<V, F>
+where
+    V: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Filtered")
+            .field("visitor", &self.visitor)
+            .field("depth", &self.depth)
+            .field("skip", &self.skip)
+            .finish()
+    }
+}
+
+impl<'v, V, F> Visitor<'v> for Filtered<V, F>
+where
+    V: Visitor<'v>,
+    F: FnMut(&str) -> bool,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_i8(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_i16(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_i32(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_u8(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_u16(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_u32(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_i64(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_u64(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_i128(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_u128(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_f64(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_f64_nonfinite(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_number_str(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_bool(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_char(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_str(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_borrowed_str(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        if self.begin() {
+            self.visitor.str_begin(len)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        if self.skip.is_none() {
+            self.visitor.visit_str_fragment(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.str_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_bytes(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_borrowed_bytes(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_bytes_hint(v, hint)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_none()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        if self.skip.is_none() {
+            self.visitor.visit_some()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_unit()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        if self.begin() {
+            self.visitor.map_begin(len)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        if self.skip.is_none() {
+            self.visitor.map_key()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        if self.skip.is_none() {
+            self.visitor.map_value()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.map_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        if self.begin() {
+            self.visitor.seq_begin(len)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        if self.skip.is_none() {
+            self.visitor.seq_elem()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.seq_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        let _ = len;
+
+        if self.begin() {
+            self.visitor.record_begin(name, None)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        if self.skip.is_some() {
+            return Ok(());
+        }
+
+        if !(self.filter)(name) {
+            self.skip = Some(self.depth);
+            return Ok(());
+        }
+
+        self.visitor.record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.record_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_variant(name, variant, index)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        if self.begin() {
+            self.visitor.variant_begin(name, variant, index)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.variant_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        if self.skip.is_none() {
+            self.visitor.visit_tag(tag)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_duration(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_timestamp(secs, nanos)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_display(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_debug(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_error(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.visitor.context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.visitor.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.visitor.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_fallback(v)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        if self.value() {
+            self.visitor.visit_fmt(args)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// A visitor that carries a context value.
+///
+/// The context is returned from `Visitor::context` so `Visit` implementations
+/// can read it while they're being visited. It replaces any context the
+/// wrapped visitor has.
+#[derive(Debug)]
+pub struct WithContext<V, C> {
+    visitor: V,
+    context: C,
+}
+
+impl<V, C> WithContext<V, C> {
+    /// Wrap a visitor with a context value.
+    pub fn new(visitor: V, context: C) -> Self {
+        WithContext { visitor, context }
+    }
+
+    /// Get the inner visitor and context.
+    pub fn into_inner(self) -> (V, C) {
+        (self.visitor, self.context)
+    }
+}
+
+impl<'v, V, C> Visitor<'v> for WithContext<V, C>
+where
+    V: Visitor<'v>,
+    C: Any,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.visitor.visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.visitor.visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.visitor.visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.visitor.visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.visitor.visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.visitor.visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.visitor.visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.visitor.visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.visitor.visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.visitor.visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.visitor.visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        self.visitor.visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.visitor.visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        self.visitor.visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_str(v)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        self.visitor.visit_borrowed_str(v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.visitor.str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.visitor.str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.visitor.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        self.visitor.visit_borrowed_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        self.visitor.visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.visitor.visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.visitor.visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visitor.visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.visitor.map_begin(len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.visitor.map_key()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.visitor.map_value()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.visitor.map_end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.visitor.seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.visitor.seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.visitor.seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        self.visitor.record_begin(name, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.visitor.record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.visitor.record_end()
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.visitor.visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.visitor.variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.visitor.variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        self.visitor.visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        self.visitor.visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.visitor.visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        self.visitor.visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.visitor.visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        self.visitor.visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        Some(&self.context)
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.visitor.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.visitor.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.visitor.visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.visitor.visit_fmt(args)
+    }
+}
+
+/// Extensions for composing visitors.
+pub trait VisitorExt<'v>: Visitor<'v> + Sized {
+    /// Forward everything this visitor visits to another visitor too.
+    fn tee<V>(self, other: V) -> Tee<Self, V>
+    where
+        V: Visitor<'v>,
+    {
+        Tee::new(self, other)
+    }
+
+    /// Limit how deeply values can be nested.
+    fn with_depth_limit(self, max_depth: usize) -> DepthLimit<Self> {
+        DepthLimit::new(self, max_depth)
+    }
+
+    /// Only visit record fields whose names match the filter.
+    fn filtered<F>(self, filter: F) -> Filtered<Self, F>
+    where
+        F: FnMut(&str) -> bool,
+    {
+        Filtered::new(self, filter)
+    }
+
+    /// Attach a context value that `Visit` implementations can read.
+    fn with_context<C>(self, context: C) -> WithContext<Self, C>
+    where
+        C: Any,
+    {
+        WithContext::new(self, context)
+    }
+}
+
+impl<'v, V> VisitorExt<'v> for V where V: Visitor<'v> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Discard;
+
+    impl<'v> Visitor<'v> for Discard {
+        fn visit_fmt(&mut self, _: &fmt::Arguments) -> Result<(), Error> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn depth_limit() {
+        let mut visitor = DepthLimit::new(Discard, 2);
+
+        visitor.map_begin(None).unwrap();
+        visitor.seq_begin(None).unwrap();
+        assert!(visitor.record_begin("Record", None).is_err());
+
+        visitor.seq_end().unwrap();
+        visitor.record_begin("Record", None).unwrap();
+        assert_eq!(2, visitor.depth());
+    }
+
+    struct Count(usize);
+
+    impl<'v> Visitor<'v> for Count {
+        fn visit_fmt(&mut self, _: &fmt::Arguments) -> Result<(), Error> {
+            self.0 += 1;
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn tee() {
+        let mut visitor = Count(0).tee(Count(0));
+
+        visitor.visit_u64(1).unwrap();
+        visitor.visit_str("a").unwrap();
+
+        let (a, b) = visitor.into_inner();
+        assert_eq!((2, 2), (a.0, b.0));
+    }
+
+    #[test]
+    fn tee_human_readable() {
+        struct Compact;
+
+        impl<'v> Visitor<'v> for Compact {
+            fn is_human_readable(&self) -> bool {
+                false
+            }
+
+            fn visit_fmt(&mut self, _: &fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        assert!(Discard.tee(Compact).is_human_readable());
+        assert!(Compact.tee(Discard).is_human_readable());
+        assert!(!Compact.tee(Compact).is_human_readable());
+    }
+
+    #[test]
+    fn filtered() {
+        let mut visitor = Count(0).filtered(|name| name != "password");
+
+        visitor.record_begin("Record", Some(3)).unwrap();
+
+        visitor.record_field("id").unwrap();
+        visitor.visit_u64(1).unwrap();
+
+        visitor.record_field("password").unwrap();
+        visitor.visit_some().unwrap();
+        visitor.seq_begin(Some(2)).unwrap();
+        visitor.seq_elem().unwrap();
+        visitor.visit_str("a").unwrap();
+        visitor.seq_elem().unwrap();
+        visitor.visit_str("b").unwrap();
+        visitor.seq_end().unwrap();
+
+        visitor.record_field("name").unwrap();
+        visitor.visit_str("alice").unwrap();
+
+        visitor.record_end().unwrap();
+
+        assert_eq!(2, visitor.into_inner().0);
+    }
+
+    #[test]
+    fn with_context() {
+        let visitor = Discard.with_context(42u32).with_depth_limit(1);
+
+        assert_eq!(
+            Some(&42u32),
+            visitor
+                .context()
+                .and_then(|context| context.downcast_ref::<u32>()),
+        );
+
+        assert!(Discard.context().is_none());
+        assert!(Discard.tee(Discard.with_context("a")).context().is_some());
+    }
+
+    #[test]
+    fn with_depth_limit() {
+        let mut visitor = Discard.with_depth_limit(0);
+
+        assert!(visitor.seq_begin(None).is_err());
+    }
+}
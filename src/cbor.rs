@@ -0,0 +1,627 @@
+//! A CBOR serializer for visited values.
+//!
+//! Values are encoded following RFC 8949. Records and maps are encoded
+//! as maps, sequences as arrays, and enum variants are externally tagged.
+//! Containers and strings without a known length use indefinite-length
+//! encoding. Timestamps, arbitrary-precision numbers, big integers, and
+//! UUIDs use their standard CBOR tags. Other values that CBOR has no
+//! representation for, like durations, are encoded as text.
+
+use crate::std::{convert::TryFrom, fmt};
+
+use crate::{timestamp::Rfc3339, Capability, Error, Visitor, WriteBytes};
+
+const UINT: u8 = 0;
+const NEGINT: u8 = 1;
+const BYTES: u8 = 2;
+const TEXT: u8 = 3;
+const ARRAY: u8 = 4;
+const MAP: u8 = 5;
+const TAG: u8 = 6;
+
+const FALSE: u8 = 0xf4;
+const TRUE: u8 = 0xf5;
+const NULL: u8 = 0xf6;
+const F32: u8 = 0xfa;
+const F64: u8 = 0xfb;
+const BREAK: u8 = 0xff;
+
+const TAG_DATETIME: u64 = 0;
+const TAG_EPOCH: u64 = 1;
+const TAG_BIGNUM: u64 = 2;
+const TAG_NEG_BIGNUM: u64 = 3;
+const TAG_DECIMAL: u64 = 4;
+const TAG_UUID: u64 = 37;
+
+/// The deepest values can be nested.
+const MAX_DEPTH: usize = 128;
+
+/// The longest formatted text that's written with a definite length.
+const INLINE_LEN: usize = 64;
+
+/// A visitor that encodes values as CBOR.
+///
+/// The writer doesn't allocate, so it can be used without `std`
+/// by writing into a `&mut [u8]`.
+///
+/// ```
+/// let mut buf = [0; 8];
+/// let mut cbor = ser::cbor::Writer::new(&mut buf[..]);
+///
+/// ser::Visit::visit(&[1, 2, 3][..], &mut cbor).unwrap();
+/// let remaining = cbor.into_inner().len();
+///
+/// assert_eq!([0x83, 0x01, 0x02, 0x03], buf[..8 - remaining]);
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    depth: usize,
+    // A bit for each level of nesting that's set if its length is indefinite
+    indefinite: u128,
+}
+
+impl<W> Writer<W>
+where
+    W: WriteBytes,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            depth: 0,
+            indefinite: 0,
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write the initial bytes of a data item.
+    fn head(&mut self, major: u8, n: u64) -> Result<(), Error> {
+        let major = major << 5;
+
+        if n < 24 {
+            self.out.write_bytes(&[major | n as u8])
+        } else if let Ok(n) = u8::try_from(n) {
+            self.out.write_bytes(&[major | 24, n])
+        } else if let Ok(n) = u16::try_from(n) {
+            let [a, b] = n.to_be_bytes();
+            self.out.write_bytes(&[major | 25, a, b])
+        } else if let Ok(n) = u32::try_from(n) {
+            let [a, b, c, d] = n.to_be_bytes();
+            self.out.write_bytes(&[major | 26, a, b, c, d])
+        } else {
+            let [a, b, c, d, e, f, g, h] = n.to_be_bytes();
+            self.out.write_bytes(&[major | 27, a, b, c, d, e, f, g, h])
+        }
+    }
+
+    fn text(&mut self, v: &str) -> Result<(), Error> {
+        self.head(TEXT, v.len() as u64)?;
+        self.out.write_bytes(v.as_bytes())
+    }
+
+    /// Write a big integer that doesn't fit in 64 bits.
+    fn bignum(&mut self, tag: u64, n: u128) -> Result<(), Error> {
+        let bytes = n.to_be_bytes();
+        let bytes = &bytes[(n.leading_zeros() / 8) as usize..];
+
+        self.head(TAG, tag)?;
+        self.head(BYTES, bytes.len() as u64)?;
+        self.out.write_bytes(bytes)
+    }
+
+    /// Begin an array or map, with an indefinite length if it's not known.
+    fn begin(&mut self, major: u8, len: Option<usize>) -> Result<(), Error> {
+        if self.depth >= MAX_DEPTH {
+            return Err(Error::msg("values are nested too deeply"));
+        }
+
+        match len {
+            Some(len) => {
+                self.head(major, len as u64)?;
+                self.indefinite &= !(1 << self.depth);
+            }
+            None => {
+                self.out.write_bytes(&[(major << 5) | 31])?;
+                self.indefinite |= 1 << self.depth;
+            }
+        }
+
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Err(Error::msg("a value was ended without being started"));
+        }
+
+        self.depth -= 1;
+
+        if self.indefinite & (1 << self.depth) != 0 {
+            self.out.write_bytes(&[BREAK])?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: WriteBytes,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        if v >= 0 {
+            self.head(UINT, v as u64)
+        } else {
+            self.head(NEGINT, !v as u64)
+        }
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.head(UINT, v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        if v >= 0 {
+            self.visit_u128(v as u128)
+        } else {
+            match u64::try_from(!v) {
+                Ok(n) => self.head(NEGINT, n),
+                Err(_) => self.bignum(TAG_NEG_BIGNUM, !v as u128),
+            }
+        }
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        match u64::try_from(v) {
+            Ok(v) => self.head(UINT, v),
+            Err(_) => self.bignum(TAG_BIGNUM, v),
+        }
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        // Use the shortest float that represents the number exactly
+        if v as f32 as f64 == v || v.is_nan() {
+            let [a, b, c, d] = (v as f32).to_be_bytes();
+            self.out.write_bytes(&[F32, a, b, c, d])
+        } else {
+            let [a, b, c, d, e, f, g, h] = v.to_be_bytes();
+            self.out.write_bytes(&[F64, a, b, c, d, e, f, g, h])
+        }
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        match decimal(v) {
+            Some((0, mantissa)) => self.visit_i64(mantissa),
+            Some((exponent, mantissa)) => {
+                self.head(TAG, TAG_DECIMAL)?;
+                self.head(ARRAY, 2)?;
+                self.visit_i64(exponent)?;
+                self.visit_i64(mantissa)
+            }
+            None => self.text(v),
+        }
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.out.write_bytes(&[if v { TRUE } else { FALSE }])
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.text(v)
+    }
+
+    fn str_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.out.write_bytes(&[(TEXT << 5) | 31])
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        if v.is_empty() {
+            return Ok(());
+        }
+
+        self.text(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.out.write_bytes(&[BREAK])
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.head(BYTES, v.len() as u64)?;
+        self.out.write_bytes(v)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.out.write_bytes(&[NULL])
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.out.write_bytes(&[NULL])
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.begin(MAP, len)
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.begin(ARRAY, len)
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn record_begin(&mut self, _: &str, len: Option<usize>) -> Result<(), Error> {
+        self.begin(MAP, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.text(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.text(variant)
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.head(MAP, 1)?;
+        self.text(variant)
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        match tag {
+            "uuid" => self.head(TAG, TAG_UUID),
+            _ => Ok(()),
+        }
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        if nanos == 0 {
+            self.head(TAG, TAG_EPOCH)?;
+            self.visit_i64(secs)
+        } else {
+            self.head(TAG, TAG_DATETIME)?;
+            self.visit_fmt(&format_args!("{}", Rfc3339 { secs, nanos }))
+        }
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::NumberStr
+                | Capability::StrFragments
+                | Capability::Tag
+                | Capability::NonFinite
+        )
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        if let Some(v) = args.as_str() {
+            return self.text(v);
+        }
+
+        let mut inline = Inline {
+            buf: [0; INLINE_LEN],
+            len: 0,
+        };
+
+        if fmt::write(&mut inline, *args).is_ok() {
+            self.head(TEXT, inline.len as u64)?;
+            return self.out.write_bytes(&inline.buf[..inline.len]);
+        }
+
+        // The text is too long to buffer, so write an indefinite-length string
+        self.str_begin(None)?;
+
+        let mut chunks = Chunks {
+            writer: self,
+            error: None,
+        };
+
+        if fmt::write(&mut chunks, *args).is_err() {
+            return Err(chunks
+                .error
+                .unwrap_or_else(|| Error::msg("an error occurred while formatting a value")));
+        }
+
+        self.str_end()
+    }
+}
+
+/// Buffer short formatted text so it can be written with a definite length.
+struct Inline {
+    buf: [u8; INLINE_LEN],
+    len: usize,
+}
+
+impl fmt::Write for Inline {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+
+        if end > INLINE_LEN {
+            return Err(fmt::Error);
+        }
+
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+}
+
+/// Write formatted text as chunks of an indefinite-length string.
+struct Chunks<'a, W> {
+    writer: &'a mut Writer<W>,
+    error: Option<Error>,
+}
+
+impl<'a, W> fmt::Write for Chunks<'a, W>
+where
+    W: WriteBytes,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.writer.visit_str_fragment(s).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// Parse a decimal number into its exponent and mantissa.
+///
+/// Numbers that aren't decimals or don't fit in 64 bits return `None`.
+fn decimal(v: &str) -> Option<(i64, i64)> {
+    let (negative, v) = match v.strip_prefix('-') {
+        Some(v) => (true, v),
+        None => (false, v),
+    };
+
+    let (v, exponent) = match v.find(['e', 'E']) {
+        Some(i) => (
+            &v[..i],
+            v[i + 1..].trim_start_matches('+').parse::<i64>().ok()?,
+        ),
+        None => (v, 0),
+    };
+
+    let (int, frac) = match v.find('.') {
+        Some(i) => (&v[..i], &v[i + 1..]),
+        None => (v, ""),
+    };
+
+    if int.is_empty() || (v.contains('.') && frac.is_empty()) {
+        return None;
+    }
+
+    let mut mantissa = 0i64;
+    for b in int.bytes().chain(frac.bytes()) {
+        if !b.is_ascii_digit() {
+            return None;
+        }
+
+        mantissa = mantissa.checked_mul(10)?.checked_sub((b - b'0') as i64)?;
+    }
+
+    // The mantissa is accumulated as a negative number so `i64::MIN` fits
+    let mantissa = if negative {
+        mantissa
+    } else {
+        mantissa.checked_neg()?
+    };
+    let exponent = exponent.checked_sub(frac.len() as i64)?;
+
+    Some((exponent, mantissa))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Visit;
+
+    fn assert_cbor(v: &dyn Visit, expected: &[u8]) {
+        let mut buf = [0; 64];
+
+        let mut cbor = Writer::new(&mut buf[..]);
+        v.visit(&mut cbor).unwrap();
+        let remaining = cbor.into_inner().len();
+
+        assert_eq!(expected, &buf[..64 - remaining]);
+    }
+
+    fn assert_cbor_with(
+        visit: impl FnOnce(&mut Writer<&mut [u8]>) -> Result<(), Error>,
+        expected: &[u8],
+    ) {
+        let mut buf = [0; 64];
+
+        let mut cbor = Writer::new(&mut buf[..]);
+        visit(&mut cbor).unwrap();
+        let remaining = cbor.into_inner().len();
+
+        assert_eq!(expected, &buf[..64 - remaining]);
+    }
+
+    #[test]
+    fn write_ints() {
+        assert_cbor(&0, &[0x00]);
+        assert_cbor(&23, &[0x17]);
+        assert_cbor(&24, &[0x18, 0x18]);
+        assert_cbor(&1000, &[0x19, 0x03, 0xe8]);
+        assert_cbor(&1000000, &[0x1a, 0x00, 0x0f, 0x42, 0x40]);
+        assert_cbor(
+            &1000000000000u64,
+            &[0x1b, 0x00, 0x00, 0x00, 0xe8, 0xd4, 0xa5, 0x10, 0x00],
+        );
+        assert_cbor(
+            &u64::MAX,
+            &[0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        );
+        assert_cbor(&-1, &[0x20]);
+        assert_cbor(&-1000, &[0x39, 0x03, 0xe7]);
+
+        assert_cbor(
+            &(u64::MAX as u128 + 1),
+            &[0xc2, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+        assert_cbor(
+            &-(u64::MAX as i128 + 1),
+            &[0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        );
+        assert_cbor(
+            &-(u64::MAX as i128 + 2),
+            &[0xc3, 0x49, 0x01, 0, 0, 0, 0, 0, 0, 0, 0],
+        );
+    }
+
+    #[test]
+    fn write_primitives() {
+        assert_cbor(&1.5, &[0xfa, 0x3f, 0xc0, 0x00, 0x00]);
+        assert_cbor(
+            &1.1,
+            &[0xfb, 0x3f, 0xf1, 0x99, 0x99, 0x99, 0x99, 0x99, 0x9a],
+        );
+        assert_cbor(&false, &[0xf4]);
+        assert_cbor(&true, &[0xf5]);
+        assert_cbor(&None::<u8>, &[0xf6]);
+        assert_cbor(&"", &[0x60]);
+        assert_cbor(&"IETF", &[0x64, 0x49, 0x45, 0x54, 0x46]);
+        assert_cbor(&"\u{00fc}", &[0x62, 0xc3, 0xbc]);
+        assert_cbor(&format_args!("{}{}", "a", 1), &[0x62, 0x61, 0x31]);
+
+        assert_cbor_with(|cbor| cbor.visit_bytes(&[1, 2]), &[0x42, 0x01, 0x02]);
+    }
+
+    #[test]
+    fn write_tagged() {
+        assert_cbor_with(
+            |cbor| cbor.visit_timestamp(1363896240, 0),
+            &[0xc1, 0x1a, 0x51, 0x4b, 0x67, 0xb0],
+        );
+        assert_cbor_with(
+            |cbor| cbor.visit_timestamp(0, 500_000_000),
+            &[
+                0xc0, 0x78, 0x1e, b'1', b'9', b'7', b'0', b'-', b'0', b'1', b'-', b'0', b'1', b'T',
+                b'0', b'0', b':', b'0', b'0', b':', b'0', b'0', b'.', b'5', b'0', b'0', b'0', b'0',
+                b'0', b'0', b'0', b'0', b'Z',
+            ],
+        );
+
+        assert_cbor_with(
+            |cbor| cbor.visit_number_str("273.15"),
+            &[0xc4, 0x82, 0x21, 0x19, 0x6a, 0xb3],
+        );
+        assert_cbor_with(|cbor| cbor.visit_number_str("-12"), &[0x2b]);
+        assert_cbor_with(
+            |cbor| cbor.visit_number_str("NaN"),
+            &[0x63, b'N', b'a', b'N'],
+        );
+
+        assert_cbor_with(
+            |cbor| {
+                cbor.visit_tag("uuid")?;
+                cbor.visit_bytes(&[0; 16])
+            },
+            &[
+                0xd8, 0x25, 0x50, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            ],
+        );
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_cbor(&&[1, 2, 3][..], &[0x83, 0x01, 0x02, 0x03]);
+
+        assert_cbor_with(
+            |cbor| {
+                cbor.map_begin(Some(2))?;
+                cbor.map_key()?;
+                cbor.visit_str("a")?;
+                cbor.map_value()?;
+                cbor.visit_u64(1)?;
+                cbor.map_key()?;
+                cbor.visit_str("b")?;
+                cbor.map_value()?;
+                cbor.seq_begin(None)?;
+                cbor.seq_elem()?;
+                cbor.visit_u64(2)?;
+                cbor.seq_end()?;
+                cbor.map_end()
+            },
+            &[0xa2, 0x61, 0x61, 0x01, 0x61, 0x62, 0x9f, 0x02, 0xff],
+        );
+
+        assert_cbor_with(
+            |cbor| {
+                cbor.record_begin("Record", None)?;
+                cbor.record_field("a")?;
+                cbor.variant_begin("Enum", "B", 1)?;
+                cbor.visit_variant("Enum", "C", 2)?;
+                cbor.variant_end()?;
+                cbor.record_end()
+            },
+            &[0xbf, 0x61, 0x61, 0xa1, 0x61, 0x42, 0x61, 0x43, 0xff],
+        );
+    }
+
+    #[test]
+    fn write_long_fmt() {
+        let mut buf = [0; 256];
+
+        let mut cbor = Writer::new(&mut buf[..]);
+        cbor.visit_fmt(&format_args!("{}{:>70}", "a", "b")).unwrap();
+        let len = 256 - cbor.into_inner().len();
+
+        assert_eq!([0x7f, 0x61, b'a'], buf[..3]);
+        assert_eq!(0xff, buf[len - 1]);
+    }
+
+    #[test]
+    fn write_too_deep() {
+        let mut buf = [0; 256];
+        let mut cbor = Writer::new(&mut buf[..]);
+
+        for _ in 0..MAX_DEPTH {
+            cbor.seq_begin(None).unwrap();
+        }
+
+        assert!(cbor.seq_begin(None).is_err());
+    }
+
+    #[test]
+    fn write_unbalanced() {
+        let mut buf = [0; 8];
+
+        assert!(Writer::new(&mut buf[..]).seq_end().is_err());
+        assert!(Writer::new(&mut buf[..]).map_end().is_err());
+    }
+
+    #[test]
+    fn parse_decimal() {
+        assert_eq!(Some((-2, 27315)), decimal("273.15"));
+        assert_eq!(Some((1, -15)), decimal("-1.5e2"));
+        assert_eq!(Some((0, i64::MIN)), decimal("-9223372036854775808"));
+        assert_eq!(None, decimal("9223372036854775808"));
+        assert_eq!(None, decimal("1."));
+        assert_eq!(None, decimal(".5"));
+        assert_eq!(None, decimal("inf"));
+    }
+}
@@ -0,0 +1,324 @@
+//! A CSV serializer for visited values.
+//!
+//! A flat sequence, record, or map is written as a single row of fields,
+//! followed by a newline. The names of record fields and the keys of maps
+//! aren't written. Fields that contain the delimiter, quotes, or newlines
+//! are quoted, with any quotes in them doubled. Optional and unit values
+//! are written as empty fields. A row with a single empty field is written
+//! as `""`, so it isn't read back as an empty row.
+
+use crate::std::{fmt, mem};
+
+use crate::{Error, TextWriter, Visitor};
+
+/// A visitor that writes flat values as CSV rows.
+///
+/// Each visited value is written as its own row, so the same writer
+/// can be used for a whole table.
+///
+/// ```
+/// let mut csv = ser::csv::Writer::new(String::new());
+///
+/// ser::Visit::visit(&["a", "b, c"][..], &mut csv).unwrap();
+/// ser::Visit::visit(&["\"d\"", ""][..], &mut csv).unwrap();
+///
+/// assert_eq!("a,\"b, c\"\n\"\"\"d\"\"\",\n", csv.into_inner());
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    delimiter: char,
+    first: bool,
+    blank: bool,
+    key: bool,
+    depth: usize,
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output that separates fields with commas.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            delimiter: ',',
+            first: true,
+            blank: false,
+            key: false,
+            depth: 0,
+        }
+    }
+
+    /// Create a writer over the given output that separates fields with the given delimiter.
+    ///
+    /// This method fails if the delimiter is a quote or newline, because
+    /// fields containing it couldn't be quoted.
+    ///
+    /// ```
+    /// let mut csv = ser::csv::Writer::delimiter(String::new(), '\t').unwrap();
+    ///
+    /// ser::Visit::visit(&["a", "b"][..], &mut csv).unwrap();
+    ///
+    /// assert_eq!("a\tb\n", csv.into_inner());
+    /// assert!(ser::csv::Writer::delimiter(String::new(), '"').is_err());
+    /// ```
+    pub fn delimiter(out: W, delimiter: char) -> Result<Self, Error> {
+        if matches!(delimiter, '"' | '\n' | '\r') {
+            return Err(Error::msg(
+                "CSV fields can't be delimited by quotes or newlines",
+            ));
+        }
+
+        Ok(Writer {
+            delimiter,
+            ..Writer::new(out)
+        })
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write a field, quoting it if it needs to be.
+    ///
+    /// Map keys aren't written.
+    fn field(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        if mem::replace(&mut self.key, false) {
+            return Ok(());
+        }
+
+        if self.depth == 0 {
+            return self.unsupported();
+        }
+
+        let first = mem::replace(&mut self.first, false);
+
+        if !first {
+            self.out.write_char(self.delimiter)?;
+        }
+
+        let mut quote = Quote {
+            delimiter: self.delimiter,
+            quote: false,
+            empty: true,
+        };
+        fmt::write(&mut quote, v)?;
+
+        // An empty first field is quoted if it's the only one in the row
+        self.blank = first && quote.empty;
+
+        if quote.quote {
+            self.out.write_char('"')?;
+            Escape(&mut self.out).write_fmt(v)?;
+            self.out.write_char('"')?;
+        } else {
+            self.out.write_fmt(v)?;
+        }
+
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        if self.depth > 0 {
+            return Err(Error::msg("CSV fields can't be nested values"));
+        }
+
+        self.depth += 1;
+        self.first = true;
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        self.depth -= 1;
+
+        if mem::replace(&mut self.blank, false) {
+            self.out.write_str("\"\"")?;
+        }
+
+        self.out.write_char('\n')?;
+
+        Ok(())
+    }
+
+    fn unsupported(&mut self) -> Result<(), Error> {
+        Err(Error::msg(
+            "only sequences, records, and maps can be written as CSV rows",
+        ))
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.field(format_args!("{}", v))
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.field(format_args!(""))
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn record_begin(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn variant_begin(&mut self, _: &str, _: &str, _: u32) -> Result<(), Error> {
+        if self.depth > 0 {
+            Err(Error::msg("CSV fields can't be nested values"))
+        } else {
+            self.unsupported()
+        }
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.field(*args)
+    }
+}
+
+/// Check whether a field needs to be quoted.
+struct Quote {
+    delimiter: char,
+    quote: bool,
+    empty: bool,
+}
+
+impl fmt::Write for Quote {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.empty &= s.is_empty();
+        self.quote |= s
+            .chars()
+            .any(|c| c == self.delimiter || c == '"' || c == '\n' || c == '\r');
+
+        Ok(())
+    }
+}
+
+/// Double any quotes in a quoted field.
+struct Escape<W>(W);
+
+impl<W> fmt::Write for Escape<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, part) in s.split('"').enumerate() {
+            if i > 0 {
+                self.0.write_str("\"\"")?;
+            }
+
+            self.0.write_str(part)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_csv(v: &dyn Visit, expected: &str) {
+        let mut csv = Writer::new(VisitArgs::new());
+        v.visit(&mut csv).unwrap();
+
+        assert_eq!(Some(expected), csv.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_row() {
+        assert_csv(&crate::value!([1, 1.5, true, null, "a"]), "1,1.5,true,,a\n");
+        assert_csv(&crate::value!({ "a": 1, "b": "c" }), "1,c\n");
+        assert_csv(&crate::value!([]), "\n");
+        assert_csv(&crate::value!([null]), "\"\"\n");
+        assert_csv(&crate::value!([""]), "\"\"\n");
+        assert_csv(&crate::value!(["", ""]), ",\n");
+        assert_csv(&crate::value!(["", "a"]), ",a\n");
+
+        let mut csv = Writer::delimiter(VisitArgs::new(), ';').unwrap();
+        csv.record_begin("Record", Some(2)).unwrap();
+        csv.record_field("a").unwrap();
+        csv.visit_str("b;c").unwrap();
+        csv.record_field("d").unwrap();
+        csv.visit_variant("Enum", "E", 0).unwrap();
+        csv.record_end().unwrap();
+
+        assert_eq!(Some("\"b;c\";E\n"), csv.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_quoted() {
+        assert_csv(
+            &crate::value!(["a,b", "\"c\"", "d\ne", "f\rg", "h i"]),
+            "\"a,b\",\"\"\"c\"\"\",\"d\ne\",\"f\rg\",h i\n",
+        );
+    }
+
+    #[test]
+    fn write_invalid() {
+        assert!(1.visit(&mut Writer::new(VisitArgs::new())).is_err());
+        assert!(crate::value!([[1]])
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!(crate::value!({ "a": {} })
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn write_invalid_delimiter() {
+        assert!(Writer::delimiter(VisitArgs::new(), '"').is_err());
+        assert!(Writer::delimiter(VisitArgs::new(), '\n').is_err());
+        assert!(Writer::delimiter(VisitArgs::new(), '\r').is_err());
+    }
+}
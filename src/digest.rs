@@ -0,0 +1,461 @@
+//! Hashing visited values with a `digest::Digest`.
+//!
+//! Values are hashed from a canonical encoding of their visited structure,
+//! so the same value produces the same hash regardless of how it's stored.
+//! All integers are hashed the same way whatever their width, and strings
+//! are hashed the same whether they're visited as a `str` or formatted.
+//! Length hints aren't hashed, because they're optional. Every string and
+//! byte slice is prefixed with its length, so values that visit the same
+//! bytes in different shapes, like `["ab"]` and `["a", "b"]`, don't collide.
+//! Formatted strings are formatted twice, once to hash their length, so
+//! values that format differently each time, like a counter, fail to hash.
+//!
+//! Each map entry is hashed into its own digest, and the hashes of all
+//! entries are summed, so maps without a stable iteration order, like
+//! `HashMap`, hash the same regardless of the order of their entries.
+//! Strings visited in fragments are buffered and hashed like a `str`.
+
+use crate::std::{fmt, mem, string::String, time, vec::Vec};
+
+use ::digest::{Digest, Output};
+
+use crate::{write, Error, Visit, Visitor, WriteBytes};
+
+const NONE: u8 = 0;
+const SOME: u8 = 1;
+const UNIT: u8 = 2;
+const BOOL: u8 = 3;
+const INT: u8 = 4;
+const UINT: u8 = 5;
+const FLOAT: u8 = 6;
+const STR: u8 = 7;
+const BYTES: u8 = 8;
+const MAP_BEGIN: u8 = 9;
+const MAP_KEY: u8 = 10;
+const MAP_VALUE: u8 = 11;
+const MAP_END: u8 = 12;
+const SEQ_BEGIN: u8 = 13;
+const SEQ_ELEM: u8 = 14;
+const SEQ_END: u8 = 15;
+const RECORD_BEGIN: u8 = 16;
+const RECORD_FIELD: u8 = 17;
+const RECORD_END: u8 = 18;
+const VARIANT: u8 = 19;
+const VARIANT_BEGIN: u8 = 20;
+const VARIANT_END: u8 = 21;
+const DURATION: u8 = 22;
+const TIMESTAMP: u8 = 23;
+
+/// Hash a value with the given digest.
+///
+/// ```
+/// use sha2::Sha256;
+///
+/// let a = ser::digest::hash::<Sha256>(&ser::value!({ "id": 1, "tags": ["a"] })).unwrap();
+/// let b = ser::digest::hash::<Sha256>(&ser::value!({ "id": 1u8, "tags": ["a"] })).unwrap();
+///
+/// assert_eq!(a, b);
+/// ```
+pub fn hash<D>(v: &dyn Visit) -> Result<Output<D>, Error>
+where
+    D: Digest,
+{
+    let mut hasher = Hasher::<D>::new();
+    v.visit(&mut hasher)?;
+
+    Ok(hasher.finalize())
+}
+
+/// A visitor that feeds the values it visits into a digest.
+///
+/// The same hasher can visit multiple values before it's finalized.
+#[derive(Debug)]
+pub struct Hasher<D>
+where
+    D: Digest,
+{
+    digest: D,
+    maps: Vec<Map<D>>,
+    str: Option<String>,
+}
+
+/// A map that's being hashed.
+///
+/// While a map is being hashed, the hasher's digest only sees its current entry.
+#[derive(Debug)]
+struct Map<D>
+where
+    D: Digest,
+{
+    outer: D,
+    sum: Output<D>,
+    len: usize,
+}
+
+impl<D> Default for Hasher<D>
+where
+    D: Digest,
+{
+    fn default() -> Self {
+        Hasher::new()
+    }
+}
+
+impl<D> Hasher<D>
+where
+    D: Digest,
+{
+    /// Create a hasher with a new digest.
+    pub fn new() -> Self {
+        Hasher::from_digest(D::new())
+    }
+
+    /// Create a hasher that updates the given digest.
+    pub fn from_digest(digest: D) -> Self {
+        Hasher {
+            digest,
+            maps: Vec::new(),
+            str: None,
+        }
+    }
+
+    /// Get the hash of the values visited.
+    pub fn finalize(self) -> Output<D> {
+        self.into_inner().finalize()
+    }
+
+    /// Get the inner digest.
+    pub fn into_inner(mut self) -> D {
+        // If a map wasn't completed then its entries are discarded
+        match self.maps.drain(..).next() {
+            Some(map) => map.outer,
+            None => self.digest,
+        }
+    }
+
+    fn update(&mut self, kind: u8, bytes: &[u8]) -> Result<(), Error> {
+        self.digest.update([kind]);
+        self.digest.update(bytes);
+
+        Ok(())
+    }
+
+    fn update_str(&mut self, kind: u8, v: &str) -> Result<(), Error> {
+        self.digest.update([kind]);
+        self.len(v.len());
+        self.digest.update(v);
+
+        Ok(())
+    }
+
+    fn len(&mut self, len: usize) {
+        self.digest.update((len as u64).to_le_bytes());
+    }
+
+    fn map_entry_end(&mut self) -> Result<(), Error> {
+        let map = self
+            .maps
+            .last_mut()
+            .ok_or_else(|| Error::msg("a map entry was visited outside of a map"))?;
+
+        if map.len > 0 {
+            let entry = mem::replace(&mut self.digest, D::new()).finalize();
+
+            // Summing entries is commutative, so their order doesn't matter
+            for (sum, b) in map.sum.iter_mut().zip(entry) {
+                *sum = sum.wrapping_add(b);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'v, D> Visitor<'v> for Hasher<D>
+where
+    D: Digest,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.visit_i128(v as i128)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.visit_i128(v as i128)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.update(INT, &v.to_le_bytes())
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        if v <= i128::MAX as u128 {
+            self.visit_i128(v as i128)
+        } else {
+            self.update(UINT, &v.to_le_bytes())
+        }
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        // All `NaN`s are hashed the same
+        let v = if v.is_nan() { f64::NAN } else { v };
+
+        self.update(FLOAT, &v.to_bits().to_le_bytes())
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.update(BOOL, &[v as u8])
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.update_str(STR, v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        if self.str.is_some() {
+            return Err(Error::msg("a string was visited inside another string"));
+        }
+
+        self.str = Some(String::with_capacity(len.unwrap_or(0)));
+
+        Ok(())
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        self.str
+            .as_mut()
+            .ok_or_else(|| Error::msg("a string fragment was visited outside of a string"))?
+            .push_str(v);
+
+        Ok(())
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        let v = self
+            .str
+            .take()
+            .ok_or_else(|| Error::msg("a string was ended outside of a string"))?;
+
+        self.visit_str(&v)
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.digest.update([BYTES]);
+        self.len(v.len());
+        self.digest.update(v);
+
+        Ok(())
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.update(NONE, &[])
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.update(SOME, &[])
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.update(UNIT, &[])
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.maps.push(Map {
+            outer: mem::replace(&mut self.digest, D::new()),
+            sum: Output::<D>::default(),
+            len: 0,
+        });
+
+        Ok(())
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.map_entry_end()?;
+
+        if let Some(map) = self.maps.last_mut() {
+            map.len += 1;
+        }
+
+        self.update(MAP_KEY, &[])
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.update(MAP_VALUE, &[])
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.map_entry_end()?;
+
+        let map = self.maps.pop().expect("missing map");
+        self.digest = map.outer;
+
+        self.update(MAP_BEGIN, &[])?;
+        self.len(map.len);
+        self.digest.update(map.sum);
+        self.update(MAP_END, &[])
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.update(SEQ_BEGIN, &[])
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.update(SEQ_ELEM, &[])
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.update(SEQ_END, &[])
+    }
+
+    fn record_begin(&mut self, name: &str, _: Option<usize>) -> Result<(), Error> {
+        self.update_str(RECORD_BEGIN, name)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.update_str(RECORD_FIELD, name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.update(RECORD_END, &[])
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.update_str(VARIANT, name)?;
+        self.visit_str(variant)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.update_str(VARIANT_BEGIN, name)?;
+        self.visit_str(variant)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.update(VARIANT_END, &[])
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        self.digest.update([DURATION]);
+        self.digest.update(v.as_secs().to_le_bytes());
+        self.digest.update(v.subsec_nanos().to_le_bytes());
+
+        Ok(())
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.digest.update([TIMESTAMP]);
+        self.digest.update(secs.to_le_bytes());
+        self.digest.update(nanos.to_le_bytes());
+
+        Ok(())
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        if let Some(v) = args.as_str() {
+            return self.visit_str(v);
+        }
+
+        // The length of the string needs to be hashed first,
+        // so the arguments are formatted twice
+        self.digest.update([STR]);
+        let len = write::fmt_len(*args)?;
+        self.len(len);
+        write::write_fmt(&mut Update(&mut self.digest), *args, len)
+    }
+}
+
+struct Update<'a, D>(&'a mut D);
+
+impl<'a, D> WriteBytes for Update<'a, D>
+where
+    D: Digest,
+{
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.0.update(bytes);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use sha2::Sha256;
+
+    fn sha256(v: &dyn Visit) -> Output<Sha256> {
+        hash::<Sha256>(v).unwrap()
+    }
+
+    #[test]
+    fn hash_stable() {
+        // Changing the encoding of values changes their hashes
+        assert_eq!(
+            [
+                162, 136, 5, 39, 9, 202, 111, 144, 102, 36, 108, 226, 155, 146, 160, 226, 106, 203,
+                120, 162, 207, 33, 216, 110, 248, 184, 122, 132, 173, 180, 170, 48
+            ],
+            &sha256(&crate::value!({ "a": [1, "b", null, 1.5] }))[..]
+        );
+    }
+
+    #[test]
+    fn hash_canonical() {
+        assert_eq!(sha256(&1u8), sha256(&1i64));
+        assert_eq!(sha256(&1u8), sha256(&1u128));
+        assert_eq!(sha256(&"a"), sha256(&'a'));
+        assert_eq!(sha256(&"a1"), sha256(&format_args!("{}{}", "a", 1)));
+        assert_eq!(sha256(&f64::NAN), sha256(&-f64::NAN));
+
+        assert_ne!(
+            sha256(&crate::value!(["ab"])),
+            sha256(&crate::value!(["a", "b"]))
+        );
+        assert_ne!(
+            sha256(&crate::value!([[1], []])),
+            sha256(&crate::value!([[], [1]]))
+        );
+        assert_ne!(sha256(&1), sha256(&1.0));
+        assert_ne!(sha256(&u128::MAX), sha256(&-1i128));
+        assert_ne!(sha256(&Some(1)), sha256(&1));
+    }
+
+    #[test]
+    fn hash_map_unordered() {
+        assert_eq!(
+            sha256(&crate::value!({ "a": 1, "b": { "c": 2, "d": [3] } })),
+            sha256(&crate::value!({ "b": { "d": [3], "c": 2 }, "a": 1 }))
+        );
+
+        assert_ne!(
+            sha256(&crate::value!({ "a": 1, "b": 2 })),
+            sha256(&crate::value!({ "a": 2, "b": 1 }))
+        );
+        assert_ne!(
+            sha256(&crate::value!({ "a": 1 })),
+            sha256(&crate::value!({ "a": 1, "a": 1 }))
+        );
+        assert_ne!(
+            sha256(&crate::value!([{ "a": 1 }, {}])),
+            sha256(&crate::value!([{}, { "a": 1 }]))
+        );
+    }
+
+    #[test]
+    fn hash_str_fragments() {
+        let mut hasher = Hasher::<Sha256>::new();
+
+        hasher.str_begin(None).unwrap();
+        hasher.visit_str_fragment("a").unwrap();
+        hasher.visit_str_fragment("b").unwrap();
+        hasher.str_end().unwrap();
+
+        assert_eq!(sha256(&"ab"), hasher.finalize());
+    }
+
+    #[test]
+    fn hash_unstable_str() {
+        use crate::{std::cell::Cell, tests::Growing};
+
+        let mut hasher = Hasher::<Sha256>::new();
+        assert!(hasher.visit_display(&Growing(Cell::new(0))).is_err());
+    }
+}
@@ -0,0 +1,65 @@
+use crate::std::fmt;
+
+#[cfg(feature = "std")]
+use crate::std::boxed::Box;
+
+/// An error encountered while visiting a value.
+///
+/// Errors are used to short-circuit visiting, like when a visitor
+/// fails to write to its output.
+pub struct Error(ErrorInner);
+
+#[derive(Debug)]
+enum ErrorInner {
+    Msg(&'static str),
+    #[cfg(feature = "std")]
+    Custom(Box<dyn std::error::Error + Send + Sync>),
+}
+
+impl Error {
+    /// Create an error with a static message.
+    pub fn msg(msg: &'static str) -> Self {
+        Error(ErrorInner::Msg(msg))
+    }
+
+    /// Create an error from some other error.
+    #[cfg(feature = "std")]
+    pub fn custom<E>(err: E) -> Self
+    where
+        E: Into<Box<dyn std::error::Error + Send + Sync>>,
+    {
+        Error(ErrorInner::Custom(err.into()))
+    }
+}
+
+impl From<fmt::Error> for Error {
+    fn from(_: fmt::Error) -> Self {
+        Error::msg("an error occurred while formatting a value")
+    }
+}
+
+impl fmt::Debug for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.0, f)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            ErrorInner::Msg(msg) => f.write_str(msg),
+            #[cfg(feature = "std")]
+            ErrorInner::Custom(ref err) => fmt::Display::fmt(err, f),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self.0 {
+            ErrorInner::Msg(_) => None,
+            ErrorInner::Custom(ref err) => Some(&**err),
+        }
+    }
+}
@@ -0,0 +1,1385 @@
+//! `Visit` implementations for types from other crates.
+//!
+//! Each crate is behind a feature of the same name. With `serde_interop`
+//! these types are visited through their own `Serialize` implementations.
+//! Crates that don't implement `Serialize`, like `http`, only get `Visit`
+//! implementations without `serde_interop`.
+
+#[allow(unused_imports)]
+use crate::{imp, EnsureVisit, Error, Stream, Visit, Visitor};
+
+#[cfg(feature = "uuid")]
+ensure_impl_visit! {
+    uuid::Uuid {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_tag("uuid")?;
+
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.visit_borrowed_bytes(self.as_bytes())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ulid")]
+ensure_impl_visit! {
+    ulid::Ulid {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_tag("ulid")?;
+
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.visit_bytes(&self.to_bytes())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "chrono")]
+ensure_impl_visit! {
+    chrono::DateTime<chrono::Utc> {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            // Leap seconds are folded into the last nanosecond of their minute
+            visitor.visit_timestamp(self.timestamp(), self.timestamp_subsec_nanos().min(999_999_999))
+        }
+    }
+    chrono::DateTime<chrono::FixedOffset> {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            // Visited as an RFC3339 string so the offset isn't lost
+            let local = self.naive_local();
+
+            visitor.visit_display(&format_args!("{}T{}{}", local.date(), local.time(), self.offset()))
+        }
+    }
+    chrono::NaiveDateTime {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(&format_args!("{}T{}", self.date(), self.time()))
+        }
+    }
+    chrono::NaiveDate {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+}
+
+#[cfg(feature = "time")]
+ensure_impl_visit! {
+    ::time::OffsetDateTime {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if self.offset().is_utc() {
+                visitor.visit_timestamp(self.unix_timestamp(), self.nanosecond())
+            } else {
+                // Visited as an RFC3339 string so the offset isn't lost
+                visitor.visit_display(&TimeRfc3339 {
+                    date: self.date(),
+                    time: self.time(),
+                    offset: Some(self.offset()),
+                })
+            }
+        }
+    }
+    ::time::PrimitiveDateTime {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(&TimeRfc3339 {
+                date: self.date(),
+                time: self.time(),
+                offset: None,
+            })
+        }
+    }
+    ::time::Date {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+    ::time::Duration {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            // Negative durations can't be represented by `core::time::Duration`
+            match <core::time::Duration as core::convert::TryFrom<_>>::try_from(*self) {
+                Ok(v) => visitor.visit_duration(v),
+                Err(_) => visitor.visit_display(self),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "jiff")]
+ensure_impl_visit! {
+    jiff::Timestamp {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let (secs, nanos) = jiff_unix_parts(*self);
+
+            visitor.visit_timestamp(secs, nanos)
+        }
+    }
+    jiff::Zoned {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if *self.time_zone() == jiff::tz::TimeZone::UTC {
+                let (secs, nanos) = jiff_unix_parts(self.timestamp());
+
+                visitor.visit_timestamp(secs, nanos)
+            } else {
+                // Visited as a string so the time zone isn't lost
+                visitor.visit_display(self)
+            }
+        }
+    }
+    jiff::Span {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            // Negative spans and spans with calendar units can't be represented by `core::time::Duration`
+            match <core::time::Duration as core::convert::TryFrom<_>>::try_from(*self) {
+                Ok(v) => visitor.visit_duration(v),
+                Err(_) => visitor.visit_display(self),
+            }
+        }
+    }
+}
+
+/// Split a `jiff` timestamp into seconds and positive nanoseconds since the unix epoch.
+///
+/// `jiff` gives times before the epoch negative nanoseconds.
+#[cfg(all(feature = "jiff", not(feature = "serde_interop")))]
+fn jiff_unix_parts(ts: jiff::Timestamp) -> (i64, u32) {
+    let secs = ts.as_second();
+    let nanos = ts.subsec_nanosecond();
+
+    if nanos < 0 {
+        (secs - 1, (nanos + 1_000_000_000) as u32)
+    } else {
+        (secs, nanos as u32)
+    }
+}
+
+/// Format a date and time from the `time` crate as an RFC3339 string.
+#[cfg(all(feature = "time", not(feature = "serde_interop")))]
+struct TimeRfc3339 {
+    date: ::time::Date,
+    time: ::time::Time,
+    offset: Option<::time::UtcOffset>,
+}
+
+#[cfg(all(feature = "time", not(feature = "serde_interop")))]
+impl core::fmt::Display for TimeRfc3339 {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(
+            f,
+            "{}T{:02}:{:02}:{:02}",
+            self.date,
+            self.time.hour(),
+            self.time.minute(),
+            self.time.second(),
+        )?;
+
+        if self.time.nanosecond() != 0 {
+            write!(f, ".{:09}", self.time.nanosecond())?;
+        }
+
+        if let Some(offset) = self.offset {
+            let (hours, minutes, _) = offset.as_hms();
+            let sign = if offset.is_negative() { '-' } else { '+' };
+
+            write!(
+                f,
+                "{}{:02}:{:02}",
+                sign,
+                hours.unsigned_abs(),
+                minutes.unsigned_abs()
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+ensure_impl_visit! {
+    rust_decimal::Decimal {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let mut buf = NumberBuf::new();
+            core::fmt::Write::write_fmt(&mut buf, format_args!("{}", self))
+                .map_err(|_| Error::msg("a decimal was too long to format"))?;
+
+            visitor.visit_number_str(buf.as_str())
+        }
+    }
+}
+
+#[cfg(feature = "bigdecimal")]
+ensure_impl_visit! {
+    bigdecimal::BigDecimal {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_number_str(&crate::std::string::ToString::to_string(self))
+        }
+    }
+}
+
+// Big integers are visited as big-endian bytes in formats that aren't human-readable.
+// A `BigUint` is visited as its magnitude, and a `BigInt` in two's complement, so the
+// bytes of a `BigInt` can have a leading zero that the same `BigUint` wouldn't, like
+// `[0, 128]` instead of `[128]`, to keep its sign bit clear.
+#[cfg(feature = "num-bigint")]
+ensure_impl_visit! {
+    num_bigint::BigUint {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_number_str(&crate::std::string::ToString::to_string(self))
+            } else {
+                visitor.visit_bytes(&self.to_bytes_be())
+            }
+        }
+    }
+    num_bigint::BigInt {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_number_str(&crate::std::string::ToString::to_string(self))
+            } else {
+                // Negative numbers are in two's complement, like `[255]` for `-1`
+                visitor.visit_bytes(&self.to_signed_bytes_be())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "ordered-float")]
+ensure_impl_visit! {
+    ordered_float::OrderedFloat<f32> {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.0 as f64;
+
+            if v.is_finite() {
+                visitor.visit_f64(v)
+            } else {
+                visitor.visit_f64_nonfinite(v)
+            }
+        }
+    }
+    ordered_float::OrderedFloat<f64> {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.0;
+
+            if v.is_finite() {
+                visitor.visit_f64(v)
+            } else {
+                visitor.visit_f64_nonfinite(v)
+            }
+        }
+    }
+    ordered_float::NotNan<f32> {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.into_inner() as f64;
+
+            if v.is_finite() {
+                visitor.visit_f64(v)
+            } else {
+                visitor.visit_f64_nonfinite(v)
+            }
+        }
+    }
+    ordered_float::NotNan<f64> {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.into_inner();
+
+            if v.is_finite() {
+                visitor.visit_f64(v)
+            } else {
+                visitor.visit_f64_nonfinite(v)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "bytes")]
+ensure_impl_visit! {
+    bytes::Bytes {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_bytes(self)
+        }
+    }
+    bytes::BytesMut {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_bytes(self)
+        }
+    }
+}
+
+#[cfg(feature = "smallvec")]
+ensure_visit!(
+    smallvec::SmallVec<[u8; 8]>,
+    smallvec::SmallVec<[&'static str; 8]>
+);
+
+// Unlike a `Vec<u8>`, which is visited as bytes, a `SmallVec` of `u8` is
+// visited as a sequence, the same as its `Serialize` implementation through `serde`
+#[cfg(all(feature = "smallvec", not(feature = "serde_interop")))]
+impl<A> Visit for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "smallvec", not(feature = "serde_interop")))]
+impl<A> imp::VisitPrivate for smallvec::SmallVec<A>
+where
+    A: smallvec::Array,
+    A::Item: Visit,
+{
+}
+
+#[cfg(feature = "arrayvec")]
+ensure_visit!(
+    arrayvec::ArrayVec<u8, 8>,
+    arrayvec::ArrayVec<&'static str, 8>,
+    arrayvec::ArrayString<8>
+);
+
+// An `ArrayVec<u8, CAP>` is visited as a sequence rather than as bytes like
+// `Vec<u8>`, to match how `arrayvec` serializes it through `serde`
+#[cfg(all(feature = "arrayvec", not(feature = "serde_interop")))]
+impl<T, const CAP: usize> Visit for arrayvec::ArrayVec<T, CAP>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "arrayvec", not(feature = "serde_interop")))]
+impl<T, const CAP: usize> imp::VisitPrivate for arrayvec::ArrayVec<T, CAP> where T: Visit {}
+
+#[cfg(all(feature = "arrayvec", not(feature = "serde_interop")))]
+impl<const CAP: usize> Visit for arrayvec::ArrayString<CAP> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_borrowed_str(self)
+    }
+}
+
+#[cfg(all(feature = "arrayvec", not(feature = "serde_interop")))]
+impl<const CAP: usize> imp::VisitPrivate for arrayvec::ArrayString<CAP> {}
+
+#[cfg(feature = "heapless")]
+ensure_visit!(
+    heapless::Vec<u8, 8>,
+    heapless::Vec<&'static str, 8>,
+    heapless::String<8>
+);
+
+// `heapless` serializes its `Vec<u8, N>` as a sequence, so it's visited as
+// one here too, even though a `Vec<u8>` from `std` is visited as bytes
+#[cfg(all(feature = "heapless", not(feature = "serde_interop")))]
+impl<T, const N: usize> Visit for heapless::Vec<T, N>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "heapless", not(feature = "serde_interop")))]
+impl<T, const N: usize> imp::VisitPrivate for heapless::Vec<T, N> where T: Visit {}
+
+#[cfg(all(feature = "heapless", not(feature = "serde_interop")))]
+impl<const N: usize> Visit for heapless::String<N> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_borrowed_str(self)
+    }
+}
+
+#[cfg(all(feature = "heapless", not(feature = "serde_interop")))]
+impl<const N: usize> imp::VisitPrivate for heapless::String<N> {}
+
+#[cfg(feature = "serde_json")]
+ensure_visit!(serde_json::Value, serde_json::Number, serde_json::Map<String, serde_json::Value>);
+
+#[cfg(all(feature = "serde_json", not(feature = "serde_interop")))]
+impl Visit for serde_json::Value {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self {
+            serde_json::Value::Null => visitor.visit_unit(),
+            serde_json::Value::Bool(v) => visitor.visit_bool(*v),
+            serde_json::Value::Number(v) => v.visit(visitor),
+            serde_json::Value::String(v) => visitor.visit_borrowed_str(v),
+            serde_json::Value::Array(v) => v.visit(visitor),
+            serde_json::Value::Object(v) => v.visit(visitor),
+        }
+    }
+}
+
+#[cfg(all(feature = "serde_json", not(feature = "serde_interop")))]
+impl imp::VisitPrivate for serde_json::Value {}
+
+#[cfg(all(feature = "serde_json", not(feature = "serde_interop")))]
+impl Visit for serde_json::Number {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        if let Some(v) = self.as_u64() {
+            visitor.visit_u64(v)
+        } else if let Some(v) = self.as_i64() {
+            visitor.visit_i64(v)
+        } else if let Some(v) = self.as_f64() {
+            visitor.visit_f64(v)
+        } else {
+            visitor.visit_number_str(&crate::std::string::ToString::to_string(self))
+        }
+    }
+}
+
+#[cfg(all(feature = "serde_json", not(feature = "serde_interop")))]
+impl imp::VisitPrivate for serde_json::Number {}
+
+#[cfg(all(feature = "serde_json", not(feature = "serde_interop")))]
+impl Visit for serde_json::Map<String, serde_json::Value> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_map(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_map(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "serde_json", not(feature = "serde_interop")))]
+impl imp::VisitPrivate for serde_json::Map<String, serde_json::Value> {}
+
+#[cfg(feature = "indexmap")]
+ensure_visit!(
+    indexmap::IndexMap<String, u64, std::collections::hash_map::RandomState>,
+    indexmap::IndexSet<String, std::collections::hash_map::RandomState>
+);
+
+#[cfg(all(feature = "indexmap", not(feature = "serde_interop")))]
+impl<K, V, H> Visit for indexmap::IndexMap<K, V, H>
+where
+    K: Visit,
+    V: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_map(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_map(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "indexmap", not(feature = "serde_interop")))]
+impl<K, V, H> imp::VisitPrivate for indexmap::IndexMap<K, V, H>
+where
+    K: Visit,
+    V: Visit,
+{
+}
+
+#[cfg(all(feature = "indexmap", not(feature = "serde_interop")))]
+impl<T, H> Visit for indexmap::IndexSet<T, H>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "indexmap", not(feature = "serde_interop")))]
+impl<T, H> imp::VisitPrivate for indexmap::IndexSet<T, H> where T: Visit {}
+
+#[cfg(feature = "url")]
+ensure_impl_visit! {
+    url::Url {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+}
+
+#[cfg(feature = "semver")]
+ensure_impl_visit! {
+    semver::Version {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+    semver::VersionReq {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+}
+
+#[cfg(feature = "camino")]
+ensure_impl_visit! {
+    camino::Utf8Path {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+    camino::Utf8PathBuf {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+}
+
+#[cfg(feature = "compact_str")]
+ensure_impl_visit! {
+    compact_str::CompactString {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+}
+
+#[cfg(feature = "smol_str")]
+ensure_impl_visit! {
+    smol_str::SmolStr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+}
+
+#[cfg(feature = "ipnet")]
+ensure_impl_visit! {
+    ipnet::IpNet {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+    ipnet::Ipv4Net {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+    ipnet::Ipv6Net {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+}
+
+// `http` doesn't implement `Serialize`, so its types can't be visited with `serde_interop`
+#[cfg(all(feature = "http", not(feature = "serde_interop")))]
+ensure_impl_visit! {
+    http::Method {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+    http::StatusCode {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u16(self.as_u16())
+        }
+    }
+    http::Uri {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_display(self)
+        }
+    }
+    http::HeaderName {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self.as_str())
+        }
+    }
+    http::HeaderValue {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Ok(v) => visitor.visit_borrowed_str(v),
+                Err(_) => visitor.visit_borrowed_bytes(self.as_bytes()),
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "http", not(feature = "serde_interop")))]
+ensure_visit!(http::HeaderMap);
+
+// Headers with multiple values are visited as repeated keys
+#[cfg(all(feature = "http", not(feature = "serde_interop")))]
+impl<T> Visit for http::HeaderMap<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        crate::visit_map(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        crate::stream_map(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "http", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for http::HeaderMap<T> where T: Visit {}
+
+#[cfg(feature = "either")]
+ensure_visit!(either::Either<u8, &'static str>);
+
+#[cfg(all(feature = "either", not(feature = "serde_interop")))]
+impl<L, R> Visit for either::Either<L, R>
+where
+    L: Visit,
+    R: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self {
+            either::Either::Left(v) => {
+                visitor.variant_begin("Either", "Left", 0)?;
+                v.visit(visitor)?;
+            }
+            either::Either::Right(v) => {
+                visitor.variant_begin("Either", "Right", 1)?;
+                v.visit(visitor)?;
+            }
+        }
+
+        visitor.variant_end()
+    }
+}
+
+#[cfg(all(feature = "either", not(feature = "serde_interop")))]
+impl<L, R> imp::VisitPrivate for either::Either<L, R>
+where
+    L: Visit,
+    R: Visit,
+{
+}
+
+/// A URL that's visited as its components instead of a single string.
+///
+/// The URL is visited as a record with `scheme`, `host`, `port`, `path`,
+/// `query`, and `fragment` fields. Components that aren't present are
+/// visited as `None`.
+#[cfg(feature = "url")]
+pub struct UrlParts<'a>(&'a url::Url);
+
+#[cfg(feature = "url")]
+impl<'a> UrlParts<'a> {
+    /// Wrap a URL so it's visited as its components.
+    pub fn new(url: &'a url::Url) -> Self {
+        UrlParts(url)
+    }
+}
+
+#[cfg(feature = "url")]
+impl<'a> core::fmt::Debug for UrlParts<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        core::fmt::Debug::fmt(self.0, f)
+    }
+}
+
+#[cfg(feature = "url")]
+ensure_visit!(UrlParts<'static>);
+
+#[cfg(all(feature = "url", not(feature = "serde_interop")))]
+impl<'a> Visit for UrlParts<'a> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        fn visit_opt<'v>(v: Option<&'v str>, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match v {
+                Some(v) => {
+                    visitor.visit_some()?;
+                    visitor.visit_borrowed_str(v)
+                }
+                None => visitor.visit_none(),
+            }
+        }
+
+        visitor.record_begin("Url", Some(6))?;
+
+        visitor.record_field("scheme")?;
+        visitor.visit_borrowed_str(self.0.scheme())?;
+
+        visitor.record_field("host")?;
+        visit_opt(self.0.host_str(), visitor)?;
+
+        visitor.record_field("port")?;
+        match self.0.port() {
+            Some(port) => {
+                visitor.visit_some()?;
+                visitor.visit_u16(port)?;
+            }
+            None => visitor.visit_none()?,
+        }
+
+        visitor.record_field("path")?;
+        visitor.visit_borrowed_str(self.0.path())?;
+
+        visitor.record_field("query")?;
+        visit_opt(self.0.query(), visitor)?;
+
+        visitor.record_field("fragment")?;
+        visit_opt(self.0.fragment(), visitor)?;
+
+        visitor.record_end()
+    }
+}
+
+#[cfg(all(feature = "url", not(feature = "serde_interop")))]
+impl<'a> imp::VisitPrivate for UrlParts<'a> {}
+
+#[cfg(all(feature = "url", feature = "serde_interop"))]
+impl<'a> serde::Serialize for UrlParts<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeStruct;
+
+        let mut record = serializer.serialize_struct("Url", 6)?;
+        record.serialize_field("scheme", self.0.scheme())?;
+        record.serialize_field("host", &self.0.host_str())?;
+        record.serialize_field("port", &self.0.port())?;
+        record.serialize_field("path", self.0.path())?;
+        record.serialize_field("query", &self.0.query())?;
+        record.serialize_field("fragment", &self.0.fragment())?;
+        record.end()
+    }
+}
+
+/// A fixed-size buffer for formatting numbers without allocating.
+///
+/// It fits the longest `Decimal`, which is a sign, 29 digits, and
+/// a decimal point, like `-7.9228162514264337593543950335`.
+#[cfg(all(feature = "rust_decimal", not(feature = "serde_interop")))]
+struct NumberBuf {
+    buf: [u8; 31],
+    len: usize,
+}
+
+#[cfg(all(feature = "rust_decimal", not(feature = "serde_interop")))]
+impl NumberBuf {
+    fn new() -> Self {
+        NumberBuf {
+            buf: [0; 31],
+            len: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole `str`s are ever written to the buffer
+        core::str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+}
+
+#[cfg(all(feature = "rust_decimal", not(feature = "serde_interop")))]
+impl core::fmt::Write for NumberBuf {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        let end = self.len + s.len();
+
+        if end > self.buf.len() {
+            return Err(core::fmt::Error);
+        }
+
+        self.buf[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[allow(unused_imports)]
+    use super::*;
+
+    #[allow(unused_imports)]
+    use crate::tests::{assert_visit, Token};
+
+    #[test]
+    #[cfg(all(feature = "uuid", not(feature = "serde_interop")))]
+    fn visit_uuid() {
+        struct Capture {
+            tag: bool,
+            human_readable: bool,
+            bytes: bool,
+        }
+
+        impl<'v> Visitor<'v> for Capture {
+            fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+                self.tag = tag == "uuid";
+                Ok(())
+            }
+
+            fn is_human_readable(&self) -> bool {
+                self.human_readable
+            }
+
+            fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+                self.bytes = v.len() == 16;
+                Ok(())
+            }
+
+            fn visit_fmt(&mut self, _: &core::fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        let v = uuid::Uuid::from_u128(1);
+
+        assert_visit(
+            &v,
+            &[
+                Token::Tag("uuid"),
+                Token::Display("00000000-0000-0000-0000-000000000001"),
+            ],
+        );
+
+        let mut visitor = Capture {
+            tag: false,
+            human_readable: false,
+            bytes: false,
+        };
+        v.visit(&mut visitor).unwrap();
+
+        assert!(visitor.tag);
+        assert!(visitor.bytes);
+    }
+
+    #[test]
+    #[cfg(all(feature = "chrono", not(feature = "serde_interop")))]
+    fn visit_chrono() {
+        use chrono::{FixedOffset, NaiveDate, TimeZone, Utc};
+
+        let utc = Utc.with_ymd_and_hms(2020, 1, 2, 3, 4, 5).unwrap();
+        assert_visit(&utc, &[Token::Timestamp(1577934245, 0)]);
+
+        let offset = FixedOffset::east_opt(3600)
+            .unwrap()
+            .from_utc_datetime(&utc.naive_utc());
+        assert_visit(&offset, &[Token::Display("2020-01-02T04:04:05+01:00")]);
+
+        assert_visit(&utc.naive_utc(), &[Token::Display("2020-01-02T03:04:05")]);
+        assert_visit(
+            &NaiveDate::from_ymd_opt(2020, 1, 2).unwrap(),
+            &[Token::Display("2020-01-02")],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "time", not(feature = "serde_interop")))]
+    fn visit_time() {
+        use ::time::{Date, Duration, Month, PrimitiveDateTime, Time, UtcOffset};
+
+        let date = Date::from_calendar_date(2020, Month::January, 2).unwrap();
+        let primitive = PrimitiveDateTime::new(date, Time::from_hms(3, 4, 5).unwrap());
+
+        assert_visit(&primitive.assume_utc(), &[Token::Timestamp(1577934245, 0)]);
+
+        let offset = primitive.assume_offset(UtcOffset::from_hms(-1, -30, 0).unwrap());
+        assert_visit(&offset, &[Token::Display("2020-01-02T03:04:05-01:30")]);
+
+        assert_visit(&primitive, &[Token::Display("2020-01-02T03:04:05")]);
+        assert_visit(&date, &[Token::Display("2020-01-02")]);
+
+        assert_visit(
+            &Duration::seconds(1),
+            &[Token::Duration(core::time::Duration::from_secs(1))],
+        );
+        assert_visit(&Duration::seconds(-1), &[Token::Display("-1s")]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "rust_decimal", not(feature = "serde_interop")))]
+    fn visit_rust_decimal() {
+        use core::str::FromStr;
+
+        use rust_decimal::Decimal;
+
+        for v in [
+            "0.1000000000000000000000000001",
+            "-79228162514264337593543950335",
+            "-7.9228162514264337593543950335",
+            "-0.0000000000000000000000000001",
+        ] {
+            assert_visit(&Decimal::from_str(v).unwrap(), &[Token::NumberStr(v)]);
+        }
+
+        assert_visit(
+            &Decimal::MIN,
+            &[Token::NumberStr("-79228162514264337593543950335")],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "bigdecimal", not(feature = "serde_interop")))]
+    fn visit_bigdecimal() {
+        use core::str::FromStr;
+
+        let v = bigdecimal::BigDecimal::from_str("12345678901234567890.123456789").unwrap();
+        assert_visit(&v, &[Token::NumberStr("12345678901234567890.123456789")]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "num-bigint", not(feature = "serde_interop")))]
+    fn visit_num_bigint() {
+        use num_bigint::{BigInt, BigUint};
+
+        let v = BigUint::from(u128::MAX) * 2u8;
+        assert_visit(
+            &v,
+            &[Token::NumberStr("680564733841876926926749214863536422910")],
+        );
+
+        let v = -BigInt::from(u128::MAX);
+        assert_visit(
+            &v,
+            &[Token::NumberStr("-340282366920938463463374607431768211455")],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "num-bigint", not(feature = "serde_interop")))]
+    fn visit_num_bigint_bytes() {
+        use crate::std::vec::Vec;
+
+        use num_bigint::{BigInt, BigUint};
+
+        struct Bytes(Vec<u8>);
+
+        impl<'v> Visitor<'v> for Bytes {
+            fn is_human_readable(&self) -> bool {
+                false
+            }
+
+            fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+                self.0 = v.to_vec();
+                Ok(())
+            }
+
+            fn visit_fmt(&mut self, _: &core::fmt::Arguments) -> Result<(), Error> {
+                panic!("expected bytes")
+            }
+        }
+
+        fn bytes(v: &dyn Visit) -> Vec<u8> {
+            let mut visitor = Bytes(Vec::new());
+            v.visit(&mut visitor).unwrap();
+
+            visitor.0
+        }
+
+        assert_eq!(vec![128], bytes(&BigUint::from(128u8)));
+        assert_eq!(vec![1, 0], bytes(&BigUint::from(256u16)));
+
+        assert_eq!(vec![0, 128], bytes(&BigInt::from(128)));
+        assert_eq!(vec![127], bytes(&BigInt::from(127)));
+        assert_eq!(vec![255], bytes(&BigInt::from(-1)));
+        assert_eq!(vec![255, 127], bytes(&BigInt::from(-129)));
+    }
+
+    #[test]
+    #[cfg(feature = "ordered-float")]
+    fn visit_ordered_float() {
+        use ordered_float::{NotNan, OrderedFloat};
+
+        assert_visit(&OrderedFloat(1.5f64), &[Token::F64(1.5)]);
+        assert_visit(&OrderedFloat(1.5f32), &[Token::F64(1.5)]);
+        assert_visit(&NotNan::new(1.5f64).unwrap(), &[Token::F64(1.5)]);
+    }
+
+    #[test]
+    #[cfg(feature = "bytes")]
+    fn visit_bytes() {
+        use bytes::{Bytes, BytesMut};
+
+        assert_visit(&Bytes::from_static(b"abc"), &[Token::Bytes(b"abc")]);
+        assert_visit(&BytesMut::from(&b"abc"[..]), &[Token::Bytes(b"abc")]);
+    }
+
+    #[test]
+    #[cfg(feature = "smallvec")]
+    fn visit_smallvec() {
+        let v: smallvec::SmallVec<[&str; 2]> = smallvec::smallvec!["a"];
+
+        assert_visit(
+            &v,
+            &[
+                Token::SeqBegin(Some(1)),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "arrayvec")]
+    fn visit_arrayvec() {
+        use arrayvec::{ArrayString, ArrayVec};
+
+        let mut v = ArrayVec::<u32, 2>::new();
+        v.push(1);
+
+        assert_visit(
+            &v,
+            &[
+                Token::SeqBegin(Some(1)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqEnd,
+            ],
+        );
+
+        assert_visit(&ArrayString::<8>::from("a").unwrap(), &[Token::Str("a")]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "smallvec", feature = "arrayvec", feature = "heapless"))]
+    fn visit_inline_u8() {
+        let tokens = [
+            Token::SeqBegin(Some(1)),
+            Token::SeqElem,
+            Token::U64(1),
+            Token::SeqEnd,
+        ];
+
+        let v: smallvec::SmallVec<[u8; 2]> = smallvec::smallvec![1];
+        assert_visit(&v, &tokens);
+
+        let mut v = arrayvec::ArrayVec::<u8, 2>::new();
+        v.push(1);
+        assert_visit(&v, &tokens);
+
+        let mut v = heapless::Vec::<u8, 2>::new();
+        v.push(1).unwrap();
+        assert_visit(&v, &tokens);
+    }
+
+    #[test]
+    #[cfg(feature = "heapless")]
+    fn visit_heapless() {
+        let mut v = heapless::Vec::<u32, 2>::new();
+        v.push(1).unwrap();
+
+        assert_visit(
+            &v,
+            &[
+                Token::SeqBegin(Some(1)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqEnd,
+            ],
+        );
+
+        let mut v = heapless::String::<8>::new();
+        v.push_str("a").unwrap();
+
+        assert_visit(&v, &[Token::Str("a")]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_json")]
+    fn visit_serde_json() {
+        let v = serde_json::json!({
+            "id": 1,
+            "tags": ["a", null],
+        });
+
+        assert_visit(
+            &v,
+            &[
+                Token::MapBegin(Some(2)),
+                Token::MapKey,
+                Token::Str("id"),
+                Token::MapValue,
+                Token::U64(1),
+                Token::MapKey,
+                Token::Str("tags"),
+                Token::MapValue,
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqElem,
+                Token::Unit,
+                Token::SeqEnd,
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "indexmap")]
+    fn visit_indexmap() {
+        use std::collections::hash_map::RandomState;
+
+        let mut map = indexmap::IndexMap::with_hasher(RandomState::new());
+        map.insert("b", 1u64);
+        map.insert("a", 2u64);
+
+        assert_visit(
+            &map,
+            &[
+                Token::MapBegin(Some(2)),
+                Token::MapKey,
+                Token::Str("b"),
+                Token::MapValue,
+                Token::U64(1),
+                Token::MapKey,
+                Token::Str("a"),
+                Token::MapValue,
+                Token::U64(2),
+                Token::MapEnd,
+            ],
+        );
+
+        let mut set = indexmap::IndexSet::with_hasher(RandomState::new());
+        set.insert("b");
+        set.insert("a");
+
+        assert_visit(
+            &set,
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::Str("b"),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "url")]
+    fn visit_url() {
+        let url: url::Url = "https://example.com:8080/a/b?q=1".parse().unwrap();
+
+        assert_visit(&url, &[Token::Str("https://example.com:8080/a/b?q=1")]);
+
+        assert_visit(
+            &crate::UrlParts::new(&url),
+            &[
+                Token::RecordBegin("Url", Some(6)),
+                Token::RecordField("scheme"),
+                Token::Str("https"),
+                Token::RecordField("host"),
+                Token::Some,
+                Token::Str("example.com"),
+                Token::RecordField("port"),
+                Token::Some,
+                Token::U64(8080),
+                Token::RecordField("path"),
+                Token::Str("/a/b"),
+                Token::RecordField("query"),
+                Token::Some,
+                Token::Str("q=1"),
+                Token::RecordField("fragment"),
+                Token::None,
+                Token::RecordEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "semver", not(feature = "serde_interop")))]
+    fn visit_semver() {
+        let version: semver::Version = "1.2.3-alpha.1".parse().unwrap();
+        let req: semver::VersionReq = ">=1.2, <2".parse().unwrap();
+
+        assert_visit(&version, &[Token::Display("1.2.3-alpha.1")]);
+        assert_visit(&req, &[Token::Display(">=1.2, <2")]);
+    }
+
+    #[test]
+    #[cfg(feature = "camino")]
+    fn visit_camino() {
+        let path = camino::Utf8PathBuf::from("/tmp/a.txt");
+
+        assert_visit(&path, &[Token::Str("/tmp/a.txt")]);
+        assert_visit(&path.as_path(), &[Token::Str("/tmp/a.txt")]);
+    }
+
+    #[test]
+    #[cfg(feature = "either")]
+    fn visit_either() {
+        use either::Either;
+
+        assert_visit(
+            &Either::<u8, &str>::Left(1),
+            &[
+                Token::VariantBegin("Either", "Left", 0),
+                Token::U64(1),
+                Token::VariantEnd,
+            ],
+        );
+
+        assert_visit(
+            &Either::<u8, &str>::Right("a"),
+            &[
+                Token::VariantBegin("Either", "Right", 1),
+                Token::Str("a"),
+                Token::VariantEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "compact_str")]
+    fn visit_compact_str() {
+        assert_visit(&compact_str::CompactString::new("a"), &[Token::Str("a")]);
+    }
+
+    #[test]
+    #[cfg(feature = "smol_str")]
+    fn visit_smol_str() {
+        assert_visit(&smol_str::SmolStr::new("a"), &[Token::Str("a")]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ipnet", not(feature = "serde_interop")))]
+    fn visit_ipnet() {
+        let v4: ipnet::Ipv4Net = "10.0.0.0/8".parse().unwrap();
+        let v6: ipnet::Ipv6Net = "fd00::/16".parse().unwrap();
+
+        assert_visit(&v4, &[Token::Display("10.0.0.0/8")]);
+        assert_visit(&v6, &[Token::Display("fd00::/16")]);
+        assert_visit(&ipnet::IpNet::V4(v4), &[Token::Display("10.0.0.0/8")]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "http", not(feature = "serde_interop")))]
+    fn visit_http() {
+        use http::{header, HeaderMap, HeaderValue, Method, StatusCode, Uri};
+
+        assert_visit(&Method::GET, &[Token::Str("GET")]);
+        assert_visit(&StatusCode::NOT_FOUND, &[Token::U64(404)]);
+        assert_visit(
+            &"/a?b=1".parse::<Uri>().unwrap(),
+            &[Token::Display("/a?b=1")],
+        );
+        assert_visit(
+            &HeaderValue::from_bytes(b"\xff").unwrap(),
+            &[Token::Bytes(b"\xff")],
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(header::ACCEPT, HeaderValue::from_static("text/plain"));
+        headers.append(header::ACCEPT, HeaderValue::from_static("text/html"));
+
+        assert_visit(
+            &headers,
+            &[
+                Token::MapBegin(Some(2)),
+                Token::MapKey,
+                Token::Str("accept"),
+                Token::MapValue,
+                Token::Str("text/plain"),
+                Token::MapKey,
+                Token::Str("accept"),
+                Token::MapValue,
+                Token::Str("text/html"),
+                Token::MapEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "jiff", not(feature = "serde_interop")))]
+    fn visit_jiff() {
+        use jiff::{tz::TimeZone, Timestamp, ToSpan};
+
+        let ts = Timestamp::new(1_577_934_245, 6).unwrap();
+
+        assert_visit(&ts, &[Token::Timestamp(1_577_934_245, 6)]);
+        assert_visit(
+            &Timestamp::new(-1, -500_000_000).unwrap(),
+            &[Token::Timestamp(-2, 500_000_000)],
+        );
+        assert_visit(
+            &ts.to_zoned(TimeZone::UTC),
+            &[Token::Timestamp(1_577_934_245, 6)],
+        );
+        assert_visit(
+            &ts.to_zoned(TimeZone::fixed(jiff::tz::offset(1))),
+            &[Token::Display(
+                "2020-01-02T04:04:05.000000006+01:00[+01:00]",
+            )],
+        );
+
+        assert_visit(
+            &90.seconds(),
+            &[Token::Duration(core::time::Duration::from_secs(90))],
+        );
+        assert_visit(&1.month(), &[Token::Display("P1M")]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "ulid", not(feature = "serde_interop")))]
+    fn visit_ulid() {
+        struct Bytes(Option<[u8; 16]>);
+
+        impl<'v> Visitor<'v> for Bytes {
+            fn is_human_readable(&self) -> bool {
+                false
+            }
+
+            fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+                self.0 = core::convert::TryFrom::try_from(v).ok();
+                Ok(())
+            }
+
+            fn visit_fmt(&mut self, _: &core::fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        let v = ulid::Ulid::from_parts(1, 2);
+
+        assert_visit(
+            &v,
+            &[
+                Token::Tag("ulid"),
+                Token::Display("00000000010000000000000002"),
+            ],
+        );
+
+        let mut visitor = Bytes(None);
+        v.visit(&mut visitor).unwrap();
+
+        assert_eq!(Some(v.to_bytes()), visitor.0);
+    }
+}
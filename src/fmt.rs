@@ -0,0 +1,543 @@
+//! Formatting visited values for humans.
+
+use crate::std::{fmt, mem, str};
+
+use crate::{Error, TextWriter, Visitor};
+
+#[cfg(feature = "std")]
+use crate::{std::string::String, Visit};
+
+const MAX_DEPTH: usize = 64;
+
+/// A visitor that writes values like `{:#?}`.
+///
+/// The output is built from the visited structure of a value rather than
+/// its `Debug` implementation, so values with unhelpful `Debug` output can
+/// still be read easily. Records are written with their names, strings are
+/// quoted and escaped, and nested values are indented on their own lines.
+/// Optional values are written as their inner value, or `None`.
+///
+/// ```
+/// let mut pretty = ser::fmt::Pretty::new(String::new());
+/// ser::Visit::visit(&ser::value!({ "id": 1, "tags": ["a"] }), &mut pretty).unwrap();
+///
+/// assert_eq!("{\n    \"id\": 1,\n    \"tags\": [\n        \"a\",\n    ],\n}", pretty.into_inner());
+/// ```
+#[derive(Debug)]
+pub struct Pretty<W> {
+    out: W,
+    first: bool,
+    key: Option<usize>,
+    variant: bool,
+    variants: u128,
+    variant_depth: usize,
+    depth: usize,
+    close: [char; MAX_DEPTH],
+}
+
+impl<W> Pretty<W>
+where
+    W: fmt::Write,
+{
+    /// Create a visitor over the given output.
+    pub fn new(out: W) -> Self {
+        Pretty {
+            out,
+            first: true,
+            key: None,
+            variant: false,
+            variants: 0,
+            variant_depth: 0,
+            depth: 0,
+            close: ['\0'; MAX_DEPTH],
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Begin a value, which might be the data of a variant.
+    ///
+    /// Sequences and records are written inside their variant, like a tuple
+    /// or struct variant. Other values are wrapped in parens. Returns whether
+    /// the value is written inside its variant.
+    fn data(&mut self, inline: bool) -> Result<bool, Error> {
+        if !mem::replace(&mut self.variant, false) {
+            return Ok(false);
+        }
+
+        if inline {
+            return Ok(true);
+        }
+
+        self.variants |= 1;
+        self.begin(format_args!("("), ')')?;
+        self.elem()?;
+
+        Ok(false)
+    }
+
+    /// Write a separator after a complete value.
+    fn done(&mut self) -> Result<(), Error> {
+        if self.key == Some(self.depth) {
+            self.key = None;
+        } else if self.depth > 0 {
+            self.out.write_char(',')?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a newline and indentation before an element, field, or key.
+    fn elem(&mut self) -> Result<(), Error> {
+        self.first = false;
+        self.newline()
+    }
+
+    fn newline(&mut self) -> Result<(), Error> {
+        self.out.write_char('\n')?;
+
+        for _ in 0..self.depth {
+            self.out.write_str("    ")?;
+        }
+
+        Ok(())
+    }
+
+    fn begin(&mut self, open: fmt::Arguments, close: char) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::msg("values are nested too deeply"));
+        }
+
+        self.out.write_fmt(open)?;
+
+        self.close[self.depth] = close;
+        self.depth += 1;
+        self.first = true;
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        self.depth -= 1;
+
+        // Empty containers are written on a single line
+        if !mem::replace(&mut self.first, false) {
+            self.newline()?;
+        }
+
+        self.out.write_char(self.close[self.depth])?;
+
+        self.done()
+    }
+}
+
+impl<W> TextWriter<W> for Pretty<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Pretty<W>
+where
+    W: fmt::Write,
+{
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.data(false)?;
+        self.begin(format_args!("{{"), '}')
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.elem()?;
+        self.key = Some(self.depth);
+
+        Ok(())
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.out.write_str(": ")?;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        if self.data(true)? {
+            self.begin(format_args!("("), ')')
+        } else {
+            self.begin(format_args!("["), ']')
+        }
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn record_begin(&mut self, name: &str, _: Option<usize>) -> Result<(), Error> {
+        if self.data(true)? {
+            self.begin(format_args!(" {{"), '}')
+        } else {
+            self.begin(format_args!("{} {{", name), '}')
+        }
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.elem()?;
+        self.out.write_str(name)?;
+        self.out.write_str(": ")?;
+
+        Ok(())
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.data(false)?;
+
+        if self.variant_depth == 128 {
+            return Err(Error::msg("values are nested too deeply"));
+        }
+
+        self.out.write_str(variant)?;
+
+        self.variants <<= 1;
+        self.variant_depth += 1;
+        self.variant = true;
+
+        Ok(())
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        let wrapped = self.variants & 1 == 1;
+
+        self.variants >>= 1;
+        self.variant_depth -= 1;
+
+        if wrapped {
+            self.end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.data(false)?;
+        self.out.write_fmt(*args)?;
+
+        self.done()
+    }
+}
+
+/// A visitor that writes formatted values into a fixed buffer.
+///
+/// The buffer doesn't need to be initialized with anything in particular,
+/// so this can be used without `std` or an allocator. If a value doesn't fit
+/// then as much of it as possible is written, and visiting returns an error.
+/// The text that was written can still be used, and the buffer can report
+/// whether it was truncated.
+///
+/// The buffer can also be used as the output for other writers.
+///
+/// ```
+/// let mut buf = [0; 8];
+///
+/// let mut short = ser::fmt::Buffer::new(&mut buf);
+/// ser::Visit::visit(&42, &mut short).unwrap();
+///
+/// assert_eq!("42", short.as_str());
+/// assert!(!short.is_truncated());
+///
+/// let mut long = ser::fmt::Buffer::new(&mut buf);
+/// assert!(ser::Visit::visit(&"a longer value", &mut long).is_err());
+///
+/// assert_eq!("\"a longe", long.as_str());
+/// assert!(long.is_truncated());
+/// ```
+#[derive(Debug)]
+pub struct Buffer<'a> {
+    buf: &'a mut [u8],
+    len: usize,
+    truncated: bool,
+}
+
+impl<'a> Buffer<'a> {
+    /// Create a buffer over the given bytes.
+    pub fn new(buf: &'a mut [u8]) -> Self {
+        Buffer {
+            buf,
+            len: 0,
+            truncated: false,
+        }
+    }
+
+    /// Get the text written to the buffer.
+    pub fn as_str(&self) -> &str {
+        // Only whole strings or prefixes of them on a `char` boundary
+        // are copied into the buffer, so this is always valid
+        str::from_utf8(&self.buf[..self.len]).unwrap_or_default()
+    }
+
+    /// Get the number of bytes written to the buffer.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Whether any text has been written to the buffer.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether text had to be discarded because the buffer was full.
+    pub fn is_truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Discard any text written to the buffer so it can be reused.
+    pub fn clear(&mut self) {
+        self.len = 0;
+        self.truncated = false;
+    }
+}
+
+impl<'a> fmt::Write for Buffer<'a> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let remaining = self.buf.len() - self.len;
+
+        let mut end = s.len();
+        if end > remaining {
+            self.truncated = true;
+
+            end = remaining;
+            while !s.is_char_boundary(end) {
+                end -= 1;
+            }
+        }
+
+        self.buf[self.len..self.len + end].copy_from_slice(&s.as_bytes()[..end]);
+        self.len += end;
+
+        if self.truncated {
+            Err(fmt::Error)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl<'a, 'v> Visitor<'v> for Buffer<'a> {
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        match fmt::Write::write_fmt(self, *args) {
+            Ok(()) => Ok(()),
+            Err(_) if self.truncated => Err(Error::msg("the buffer is full")),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+/// A text format to render values in with `to_string_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[non_exhaustive]
+pub enum Format {
+    /// Debug-like indented text, through `Pretty`.
+    #[default]
+    Pretty,
+    /// Compact JSON, through `json::Writer`.
+    Json,
+    /// A block-style YAML document, through `yaml::Writer`.
+    Yaml,
+    /// Compact RON, through `ron::Writer`.
+    Ron,
+    /// A TOML document, through `toml::Writer`.
+    Toml,
+    /// Flattened `key=value` pairs, through `logfmt::Writer`.
+    Logfmt,
+    /// An XML document, through `xml::Writer`.
+    Xml,
+}
+
+/// Render a value to a string in a human-readable format.
+///
+/// This is the same as calling `to_string_with` using `Format::Pretty`.
+///
+/// ```
+/// let s = ser::fmt::to_string(&ser::value!([1, "a"])).unwrap();
+///
+/// assert_eq!("[\n    1,\n    \"a\",\n]", s);
+/// ```
+#[cfg(feature = "std")]
+pub fn to_string(v: &dyn Visit) -> Result<String, Error> {
+    to_string_with(v, Format::default())
+}
+
+/// Render a value to a string in the given format.
+///
+/// ```
+/// use ser::fmt::Format;
+///
+/// let s = ser::fmt::to_string_with(&ser::value!({ "a": 1 }), Format::Json).unwrap();
+///
+/// assert_eq!("{\"a\":1}", s);
+/// ```
+#[cfg(feature = "std")]
+pub fn to_string_with(v: &dyn Visit, format: Format) -> Result<String, Error> {
+    let mut out = String::new();
+    write_string(v, format, &mut out)?;
+
+    Ok(out)
+}
+
+/// Render a value in the given format, appending it to a string.
+#[cfg(feature = "std")]
+pub(crate) fn write_string(v: &dyn Visit, format: Format, out: &mut String) -> Result<(), Error> {
+    use crate::{json, logfmt, ron, toml, xml, yaml};
+
+    match format {
+        Format::Pretty => v.visit(&mut Pretty::new(out)),
+        Format::Json => v.visit(&mut json::Writer::new(out)),
+        Format::Yaml => v.visit(&mut yaml::Writer::new(out)),
+        Format::Ron => v.visit(&mut ron::Writer::new(out)),
+        Format::Toml => v.visit(&mut toml::Writer::new(out)),
+        Format::Logfmt => v.visit(&mut logfmt::Writer::new(out)),
+        Format::Xml => v.visit(&mut xml::Writer::new(out)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_pretty_with(
+        visit: impl FnOnce(&mut Pretty<VisitArgs>) -> Result<(), Error>,
+        expected: &str,
+    ) {
+        let mut pretty = Pretty::new(VisitArgs::new());
+        visit(&mut pretty).unwrap();
+
+        assert_eq!(Some(expected), pretty.into_inner().to_str());
+    }
+
+    fn assert_pretty(v: &dyn Visit, expected: &str) {
+        assert_pretty_with(|pretty| v.visit(pretty), expected)
+    }
+
+    #[test]
+    fn write_primitives() {
+        assert_pretty(&1, "1");
+        assert_pretty(&1.0, "1.0");
+        assert_pretty(&"a\"\n", "\"a\\\"\\n\"");
+        assert_pretty(&'a', "\"a\"");
+        assert_pretty(&Some(true), "true");
+        assert_pretty(&None::<u8>, "None");
+        assert_pretty(&(), "()");
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_pretty(
+            &crate::value!({ "a": [], "b": [{}] }),
+            "{\n    \"a\": [],\n    \"b\": [\n        {},\n    ],\n}",
+        );
+
+        assert_pretty_with(
+            |pretty| {
+                pretty.record_begin("Point", Some(2))?;
+                pretty.record_field("x")?;
+                pretty.visit_u64(1)?;
+                pretty.record_field("y")?;
+                pretty.visit_str("a")?;
+                pretty.record_end()
+            },
+            "Point {\n    x: 1,\n    y: \"a\",\n}",
+        );
+    }
+
+    #[test]
+    fn write_variants() {
+        assert_pretty_with(|pretty| pretty.visit_variant("Shape", "Empty", 0), "Empty");
+
+        assert_pretty_with(
+            |pretty| {
+                pretty.seq_begin(Some(2))?;
+                pretty.seq_elem()?;
+                pretty.variant_begin("Shape", "Circle", 1)?;
+                pretty.variant_begin("Size", "Px", 0)?;
+                pretty.visit_u64(1)?;
+                pretty.variant_end()?;
+                pretty.variant_end()?;
+                pretty.seq_elem()?;
+                pretty.variant_begin("Shape", "Line", 2)?;
+                pretty.seq_begin(Some(1))?;
+                pretty.seq_elem()?;
+                pretty.visit_u64(2)?;
+                pretty.seq_end()?;
+                pretty.variant_end()?;
+                pretty.seq_end()
+            },
+            "[\n    Circle(\n        Px(\n            1,\n        ),\n    ),\n    Line(\n        2,\n    ),\n]",
+        );
+    }
+
+    #[test]
+    fn buffer_truncated() {
+        let mut buf = [0; 4];
+
+        let mut buffer = Buffer::new(&mut buf);
+        assert!(buffer.visit_u64(12345).is_err());
+        assert_eq!("1234", buffer.as_str());
+        assert!(buffer.is_truncated());
+
+        buffer.clear();
+        assert!(buffer.is_empty());
+        assert!(buffer.visit_str("ab\u{e9}").is_err());
+        assert_eq!("\"ab", buffer.as_str());
+
+        buffer.clear();
+        buffer.visit_u64(1234).unwrap();
+        assert_eq!("1234", buffer.as_str());
+        assert!(!buffer.is_truncated());
+    }
+
+    #[test]
+    fn buffer_output() {
+        let mut buf = [0; 16];
+
+        let mut pretty = Pretty::new(Buffer::new(&mut buf));
+        crate::value!([1]).visit(&mut pretty).unwrap();
+
+        assert_eq!("[\n    1,\n]", pretty.into_inner().as_str());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn write_string() {
+        let v = crate::value!({ "a": [1] });
+
+        assert_eq!(
+            "{\n    \"a\": [\n        1,\n    ],\n}",
+            to_string(&v).unwrap()
+        );
+        assert_eq!("a:\n  - 1", to_string_with(&v, Format::Yaml).unwrap());
+        assert_eq!("a.0=1", to_string_with(&v, Format::Logfmt).unwrap());
+        assert!(to_string_with(&1, Format::Toml).is_err());
+    }
+}
@@ -0,0 +1,632 @@
+//! Forwarding `Visitor` implementations for pointers to visitors.
+
+use crate::std::{any::Any, fmt, time};
+
+#[cfg(feature = "std")]
+use crate::std::boxed::Box;
+
+use crate::{BytesHint, Capability, Error, Visitor};
+
+impl<'v, V: ?Sized> Visitor<'v> for &mut V
+where
+    V: Visitor<'v>,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        (**self).visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        (**self).visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        (**self).visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        (**self).visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        (**self).visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        (**self).visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        (**self).visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        (**self).visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        (**self).visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        (**self).visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        (**self).visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        (**self).visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        (**self).visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        (**self).visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        (**self).visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        (**self).visit_str(v)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        (**self).visit_borrowed_str(v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        (**self).str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        (**self).visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        (**self).str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        (**self).visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        (**self).visit_borrowed_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        (**self).visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        (**self).visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        (**self).visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        (**self).visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        (**self).map_begin(len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        (**self).map_key()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        (**self).map_value()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        (**self).map_end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        (**self).seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        (**self).seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        (**self).seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        (**self).record_begin(name, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        (**self).record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        (**self).record_end()
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        (**self).visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        (**self).variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        (**self).variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        (**self).visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        (**self).visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        (**self).visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        (**self).visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        (**self).visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        (**self).visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        (**self).context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        (**self).supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        (**self).is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        (**self).visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        (**self).visit_fmt(args)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'v, V: ?Sized> Visitor<'v> for Box<V>
+where
+    V: Visitor<'v>,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        (**self).visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        (**self).visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        (**self).visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        (**self).visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        (**self).visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        (**self).visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        (**self).visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        (**self).visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        (**self).visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        (**self).visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        (**self).visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        (**self).visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        (**self).visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        (**self).visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        (**self).visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        (**self).visit_str(v)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        (**self).visit_borrowed_str(v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        (**self).str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        (**self).visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        (**self).str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        (**self).visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        (**self).visit_borrowed_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        (**self).visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        (**self).visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        (**self).visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        (**self).visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        (**self).map_begin(len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        (**self).map_key()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        (**self).map_value()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        (**self).map_end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        (**self).seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        (**self).seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        (**self).seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        (**self).record_begin(name, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        (**self).record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        (**self).record_end()
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        (**self).visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        (**self).variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        (**self).variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        (**self).visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        (**self).visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        (**self).visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        (**self).visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        (**self).visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        (**self).visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        (**self).context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        (**self).supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        (**self).is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        (**self).visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        (**self).visit_fmt(args)
+    }
+}
+
+/// A visitor for values that don't live as long as the visitor expects.
+///
+/// Borrowed strings and bytes are forwarded as short-lived ones, so values
+/// behind guards or copied out of cells can still be visited.
+// Only native `Visit` impls need this
+#[cfg_attr(feature = "serde_interop", allow(dead_code))]
+pub(crate) struct Unborrowed<'a, 'v>(pub(crate) &'a mut dyn Visitor<'v>);
+
+impl<'a, 'u, 'v> Visitor<'u> for Unborrowed<'a, 'v> {
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.0.visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.0.visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.0.visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.0.visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.0.visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.0.visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.0.visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.0.visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.0.visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.0.visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.0.visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        self.0.visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        self.0.visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.0.visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        self.0.visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.0.visit_str(v)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'u str) -> Result<(), Error> {
+        self.0.visit_str(v)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.0.str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        self.0.visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.0.str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.0.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'u [u8]) -> Result<(), Error> {
+        self.0.visit_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        self.0.visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.0.visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.0.visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.0.visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.0.map_begin(len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.0.map_key()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.0.map_value()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.0.map_end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.0.seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.0.seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.0.seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        self.0.record_begin(name, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.0.record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.0.record_end()
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.0.visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.0.variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.0.variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        self.0.visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        self.0.visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.0.visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        self.0.visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.0.visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        self.0.visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.0.context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.0.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.0.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.0.visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.0.visit_fmt(args)
+    }
+}
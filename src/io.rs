@@ -0,0 +1,491 @@
+//! Writing visited values to `std::io` outputs.
+
+use crate::std::{any::Any, fmt, io, marker::PhantomData, time};
+
+use crate::{BytesHint, Capability, Error, TextWriter, Visitor};
+
+#[cfg(feature = "tokio")]
+use crate::{
+    fmt::{write_string, Format},
+    std::{future::Future, string::String},
+    Visit,
+};
+
+/// A visitor that writes a text format to an `io::Write`.
+///
+/// The writer for the format is created over an `Output`, which buffers
+/// text and captures any IO errors. Output is buffered, so it needs to be
+/// flushed when visiting is done. Any IO errors are returned from the visitor.
+///
+/// ```
+/// let mut yaml = ser::io::Writer::new(Vec::new(), ser::yaml::Writer::new);
+/// ser::Visit::visit(&ser::value!({ "a": 1 }), &mut yaml).unwrap();
+///
+/// assert_eq!(b"a: 1", &*yaml.into_inner().unwrap());
+/// ```
+///
+/// Writers that are configured when they're created can be built in a closure:
+///
+/// ```
+/// use ser::{io, json};
+///
+/// let mut json = io::Writer::new(Vec::new(), |out| json::Writer::pretty(out, json::Pretty::new()));
+/// ser::Visit::visit(&[1][..], &mut json).unwrap();
+///
+/// assert_eq!(b"[\n  1\n]", &*json.into_inner().unwrap());
+/// ```
+#[derive(Debug)]
+pub struct Writer<W, V>
+where
+    W: io::Write,
+{
+    visitor: V,
+    _marker: PhantomData<W>,
+}
+
+/// Buffered output for a text format that writes to an `io::Write`.
+///
+/// This type is created by `Writer::new`.
+#[derive(Debug)]
+pub struct Output<W>
+where
+    W: io::Write,
+{
+    out: io::BufWriter<W>,
+    error: Option<io::Error>,
+}
+
+impl<W, V> Writer<W, V>
+where
+    W: io::Write,
+    V: TextWriter<Output<W>>,
+{
+    /// Create a writer over the given output, using a writer for a text format.
+    pub fn new(out: W, format: impl FnOnce(Output<W>) -> V) -> Self {
+        Writer {
+            visitor: format(Output {
+                out: io::BufWriter::new(out),
+                error: None,
+            }),
+            _marker: PhantomData,
+        }
+    }
+
+    /// Flush any buffered output.
+    pub fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.visitor.get_mut().out)
+    }
+
+    /// Flush any buffered output and get the inner output.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.visitor
+            .into_inner()
+            .out
+            .into_inner()
+            .map_err(|err| err.into_error())
+    }
+
+    /// Return the IO error that caused visiting to fail, if there is one.
+    fn io(&mut self, r: Result<(), Error>) -> Result<(), Error> {
+        r.map_err(|err| match self.visitor.get_mut().error.take() {
+            Some(err) => Error::custom(err),
+            None => err,
+        })
+    }
+}
+
+impl<'v, W, V> Visitor<'v> for Writer<W, V>
+where
+    W: io::Write,
+    V: Visitor<'v> + TextWriter<Output<W>>,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        let r = self.visitor.visit_i8(v);
+        self.io(r)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        let r = self.visitor.visit_i16(v);
+        self.io(r)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        let r = self.visitor.visit_i32(v);
+        self.io(r)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        let r = self.visitor.visit_u8(v);
+        self.io(r)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        let r = self.visitor.visit_u16(v);
+        self.io(r)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        let r = self.visitor.visit_u32(v);
+        self.io(r)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        let r = self.visitor.visit_i64(v);
+        self.io(r)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        let r = self.visitor.visit_u64(v);
+        self.io(r)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        let r = self.visitor.visit_i128(v);
+        self.io(r)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        let r = self.visitor.visit_u128(v);
+        self.io(r)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        let r = self.visitor.visit_f64(v);
+        self.io(r)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        let r = self.visitor.visit_f64_nonfinite(v);
+        self.io(r)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        let r = self.visitor.visit_number_str(v);
+        self.io(r)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        let r = self.visitor.visit_bool(v);
+        self.io(r)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        let r = self.visitor.visit_char(v);
+        self.io(r)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        let r = self.visitor.visit_str(v);
+        self.io(r)
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        let r = self.visitor.visit_borrowed_str(v);
+        self.io(r)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let r = self.visitor.str_begin(len);
+        self.io(r)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        let r = self.visitor.visit_str_fragment(v);
+        self.io(r)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        let r = self.visitor.str_end();
+        self.io(r)
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        let r = self.visitor.visit_bytes(v);
+        self.io(r)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        let r = self.visitor.visit_borrowed_bytes(v);
+        self.io(r)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        let r = self.visitor.visit_bytes_hint(v, hint);
+        self.io(r)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        let r = self.visitor.visit_none();
+        self.io(r)
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        let r = self.visitor.visit_some();
+        self.io(r)
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        let r = self.visitor.visit_unit();
+        self.io(r)
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let r = self.visitor.map_begin(len);
+        self.io(r)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        let r = self.visitor.map_key();
+        self.io(r)
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        let r = self.visitor.map_value();
+        self.io(r)
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        let r = self.visitor.map_end();
+        self.io(r)
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let r = self.visitor.seq_begin(len);
+        self.io(r)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        let r = self.visitor.seq_elem();
+        self.io(r)
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        let r = self.visitor.seq_end();
+        self.io(r)
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        let r = self.visitor.record_begin(name, len);
+        self.io(r)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        let r = self.visitor.record_field(name);
+        self.io(r)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        let r = self.visitor.record_end();
+        self.io(r)
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        let r = self.visitor.visit_variant(name, variant, index);
+        self.io(r)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        let r = self.visitor.variant_begin(name, variant, index);
+        self.io(r)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        let r = self.visitor.variant_end();
+        self.io(r)
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        let r = self.visitor.visit_tag(tag);
+        self.io(r)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        let r = self.visitor.visit_duration(v);
+        self.io(r)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        let r = self.visitor.visit_timestamp(secs, nanos);
+        self.io(r)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        let r = self.visitor.visit_display(v);
+        self.io(r)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        let r = self.visitor.visit_debug(v);
+        self.io(r)
+    }
+
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        let r = self.visitor.visit_error(v);
+        self.io(r)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.visitor.context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.visitor.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.visitor.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        let r = self.visitor.visit_fallback(v);
+        self.io(r)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        let r = self.visitor.visit_fmt(args);
+        self.io(r)
+    }
+}
+
+impl<W> fmt::Write for Output<W>
+where
+    W: io::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        io::Write::write_all(&mut self.out, s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// A writer for records in a text format to a `tokio::io::AsyncWrite`.
+///
+/// Each record is visited synchronously into a buffer, and then written to
+/// the output followed by a newline, so the future that writes it doesn't
+/// borrow the value. Records are written as compact JSON by default, which
+/// makes the output newline-delimited JSON.
+///
+/// ```
+/// use ser::{fmt::Format, io::AsyncWriter};
+///
+/// async fn ship(out: impl tokio::io::AsyncWrite + Unpin) -> std::io::Result<()> {
+///     let mut records = AsyncWriter::format(out, Format::Logfmt);
+///
+///     records.write(&ser::value!({ "level": "info", "msg": "started" })).await?;
+///     records.flush().await
+/// }
+/// ```
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct AsyncWriter<W> {
+    out: W,
+    buf: String,
+    format: Format,
+}
+
+#[cfg(feature = "tokio")]
+impl<W> AsyncWriter<W>
+where
+    W: tokio::io::AsyncWrite + Unpin,
+{
+    /// Create a writer over the given output that writes records as JSON.
+    pub fn new(out: W) -> Self {
+        AsyncWriter::format(out, Format::Json)
+    }
+
+    /// Create a writer over the given output that writes records in the given format.
+    pub fn format(out: W, format: Format) -> Self {
+        AsyncWriter {
+            out,
+            buf: String::new(),
+            format,
+        }
+    }
+
+    /// Write a record.
+    ///
+    /// The value is visited before the returned future is polled.
+    /// If visiting fails then nothing is written.
+    pub fn write(&mut self, v: &dyn Visit) -> impl Future<Output = io::Result<()>> + '_ {
+        use tokio::io::AsyncWriteExt;
+
+        self.buf.clear();
+
+        let r = write_string(v, self.format, &mut self.buf)
+            .map(|()| self.buf.push('\n'))
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err));
+
+        async move {
+            r?;
+            self.out.write_all(self.buf.as_bytes()).await
+        }
+    }
+
+    /// Flush the output.
+    pub async fn flush(&mut self) -> io::Result<()> {
+        use tokio::io::AsyncWriteExt;
+
+        self.out.flush().await
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{csv, json, Visit};
+
+    struct Fail;
+
+    impl io::Write for Fail {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::other("failed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_io() {
+        let mut csv = Writer::new(Vec::new(), csv::Writer::new);
+
+        crate::value!([1, "a"]).visit(&mut csv).unwrap();
+        crate::value!([2, "b"]).visit(&mut csv).unwrap();
+        csv.flush().unwrap();
+
+        assert_eq!(b"1,a\n2,b\n", &*csv.into_inner().unwrap());
+    }
+
+    #[test]
+    fn write_io_error() {
+        // Writes go through a buffer, so errors are only seen once it fills up
+        let mut json = Writer::new(Fail, json::Writer::new);
+        let err = "a".repeat(10_000).visit(&mut json).unwrap_err();
+
+        assert_eq!("failed", err.to_string());
+    }
+
+    #[tokio::test]
+    #[cfg(feature = "tokio")]
+    async fn write_async() {
+        let mut records = AsyncWriter::new(Vec::new());
+
+        records.write(&crate::value!({ "a": 1 })).await.unwrap();
+        records.write(&crate::value!({ "a": 2 })).await.unwrap();
+
+        let mut logfmt = AsyncWriter::format(records.into_inner(), Format::Logfmt);
+
+        logfmt.write(&crate::value!({ "a": 3 })).await.unwrap();
+        assert!(logfmt.write(&1).await.is_err());
+
+        assert_eq!(b"{\"a\":1}\n{\"a\":2}\na=3\n", &*logfmt.into_inner());
+    }
+}
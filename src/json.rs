@@ -0,0 +1,999 @@
+//! A JSON serializer for visited values.
+//!
+//! Records and maps are written as objects, sequences as arrays, and
+//! enum variants are externally tagged, like `serde_json`. Values that
+//! JSON has no representation for, like durations and timestamps, are
+//! written as strings. Optional and unit values are written as `null`.
+
+use crate::std::{fmt, mem};
+
+#[cfg(feature = "std")]
+use crate::std::io;
+
+use crate::{BytesHint, Capability, Error, NonFinite, TextWriter, Visitor};
+
+/// A visitor that writes values as compact JSON.
+///
+/// ```
+/// let mut json = ser::json::Writer::new(String::new());
+/// ser::Visit::visit(&[1, 2, 3][..], &mut json).unwrap();
+///
+/// assert_eq!("[1,2,3]", json.into_inner());
+/// ```
+///
+/// Use `Writer::pretty` to write indented JSON for humans to read instead.
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    first: bool,
+    key: bool,
+    depth: usize,
+    pretty: Option<Pretty>,
+}
+
+/// Configuration for writing pretty JSON.
+///
+/// ```
+/// let pretty = ser::json::Pretty::new().indent("    ").trailing_newline(true);
+///
+/// let mut json = ser::json::Writer::pretty(String::new(), pretty);
+/// ser::Visit::visit(&[1, 2][..], &mut json).unwrap();
+///
+/// assert_eq!("[\n    1,\n    2\n]\n", json.into_inner());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pretty {
+    indent: &'static str,
+    trailing_newline: bool,
+    key_spacing: bool,
+}
+
+impl Pretty {
+    /// Indent with two spaces, put a space after keys, and don't add a trailing newline.
+    pub fn new() -> Self {
+        Pretty {
+            indent: "  ",
+            trailing_newline: false,
+            key_spacing: true,
+        }
+    }
+
+    /// Set the string to indent each level of nesting with.
+    pub fn indent(self, indent: &'static str) -> Self {
+        Pretty { indent, ..self }
+    }
+
+    /// Set whether to write a newline after each complete value.
+    pub fn trailing_newline(self, trailing_newline: bool) -> Self {
+        Pretty {
+            trailing_newline,
+            ..self
+        }
+    }
+
+    /// Set whether to put a space between keys and their values.
+    pub fn key_spacing(self, key_spacing: bool) -> Self {
+        Pretty {
+            key_spacing,
+            ..self
+        }
+    }
+}
+
+impl Default for Pretty {
+    fn default() -> Self {
+        Pretty::new()
+    }
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            first: true,
+            key: false,
+            depth: 0,
+            pretty: None,
+        }
+    }
+
+    /// Create a writer over the given output that writes pretty JSON.
+    pub fn pretty(out: W, pretty: Pretty) -> Self {
+        Writer {
+            pretty: Some(pretty),
+            ..Writer::new(out)
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Check that a value other than a string or number can be visited.
+    fn value(&mut self) -> Result<(), Error> {
+        if self.key {
+            Err(Error::msg("map keys must be strings, numbers, or booleans"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a number or boolean, quoting it if it's a map key.
+    fn number(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        if mem::replace(&mut self.key, false) {
+            self.out.write_char('"')?;
+            self.out.write_fmt(v)?;
+            self.out.write_char('"')?;
+        } else {
+            self.out.write_fmt(v)?;
+        }
+
+        self.done()
+    }
+
+    /// Write a quoted and escaped string.
+    fn string(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        self.key = false;
+
+        self.out.write_char('"')?;
+        Escape(&mut self.out).write_fmt(v)?;
+        self.out.write_char('"')?;
+
+        self.done()
+    }
+
+    /// Write a trailing newline if a complete value has been written.
+    fn done(&mut self) -> Result<(), Error> {
+        if let Some(Pretty {
+            trailing_newline: true,
+            ..
+        }) = self.pretty
+        {
+            if self.depth == 0 {
+                self.out.write_char('\n')?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a newline and indentation for the current depth.
+    fn newline(&mut self) -> Result<(), Error> {
+        if let Some(pretty) = self.pretty {
+            self.out.write_char('\n')?;
+
+            for _ in 0..self.depth {
+                self.out.write_str(pretty.indent)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write a separator before an element, field, or key.
+    fn elem(&mut self) -> Result<(), Error> {
+        if !mem::replace(&mut self.first, false) {
+            self.out.write_char(',')?;
+        }
+
+        self.newline()
+    }
+
+    /// Write a separator between a key and its value.
+    fn colon(&mut self) -> Result<(), Error> {
+        match self.pretty {
+            Some(Pretty {
+                key_spacing: true, ..
+            }) => self.out.write_str(": ")?,
+            _ => self.out.write_char(':')?,
+        }
+
+        Ok(())
+    }
+
+    fn begin(&mut self, delim: char) -> Result<(), Error> {
+        self.value()?;
+
+        self.first = true;
+        self.depth += 1;
+        self.out.write_char(delim)?;
+
+        Ok(())
+    }
+
+    fn end(&mut self, delim: char) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Err(Error::msg("a value was ended without being started"));
+        }
+
+        self.depth -= 1;
+
+        // Empty containers are written on a single line
+        if !mem::replace(&mut self.first, false) {
+            self.newline()?;
+        }
+
+        self.out.write_char(delim)?;
+
+        self.done()
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.number(format_args!("{}", v))
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.number(format_args!("{}", v))
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.number(format_args!("{}", v))
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.number(format_args!("{}", v))
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        if v.is_finite() {
+            // `Debug` always includes a fractional part or exponent
+            self.number(format_args!("{:?}", v))
+        } else {
+            self.visit_f64_nonfinite(v)
+        }
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        NonFinite::None.apply(v, self)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        if is_number(v) {
+            self.number(format_args!("{}", v))
+        } else {
+            self.string(format_args!("{}", v))
+        }
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.number(format_args!("{}", v))
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.string(format_args!("{}", v))
+    }
+
+    fn str_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.key = false;
+        self.out.write_char('"')?;
+
+        Ok(())
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        Escape(&mut self.out).write_str(v)?;
+
+        Ok(())
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.out.write_char('"')?;
+
+        self.done()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.begin('[')?;
+
+        for b in v {
+            self.elem()?;
+            self.number(format_args!("{}", b))?;
+        }
+
+        self.end(']')
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        match hint {
+            BytesHint::Hex => self.string(format_args!("{}", Hex(v))),
+            BytesHint::Base64 => self.string(format_args!("{}", Base64(v))),
+        }
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.value()?;
+        self.out.write_str("null")?;
+
+        self.done()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin('{')
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.elem()?;
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.colon()
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end('}')
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin('[')
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end(']')
+    }
+
+    fn record_begin(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin('{')
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.elem()?;
+        self.string(format_args!("{}", name))?;
+        self.colon()
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end('}')
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.string(format_args!("{}", variant))
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.begin('{')?;
+        self.elem()?;
+        self.string(format_args!("{}", variant))?;
+        self.colon()
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.end('}')
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(
+            capability,
+            Capability::NumberStr
+                | Capability::StrFragments
+                | Capability::BytesHint
+                | Capability::NonFinite
+        )
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.string(*args)
+    }
+}
+
+/// A visitor that writes values as compact JSON to an `io::Write`.
+///
+/// Output is buffered, so it needs to be flushed when visiting is done.
+/// Any IO errors are returned from the visitor.
+///
+/// ```
+/// let mut json = ser::json::IoWriter::new(Vec::new());
+/// ser::Visit::visit(&[1, 2, 3][..], &mut json).unwrap();
+///
+/// assert_eq!(b"[1,2,3]", &*json.into_inner().unwrap());
+/// ```
+#[cfg(feature = "std")]
+#[derive(Debug)]
+pub struct IoWriter<W>
+where
+    W: io::Write,
+{
+    json: Writer<Io<io::BufWriter<W>>>,
+}
+
+#[cfg(feature = "std")]
+impl<W> IoWriter<W>
+where
+    W: io::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        IoWriter {
+            json: Writer::new(Io {
+                out: io::BufWriter::new(out),
+                error: None,
+            }),
+        }
+    }
+
+    /// Create a writer over the given output that writes pretty JSON.
+    pub fn pretty(out: W, pretty: Pretty) -> Self {
+        IoWriter {
+            json: Writer::pretty(
+                Io {
+                    out: io::BufWriter::new(out),
+                    error: None,
+                },
+                pretty,
+            ),
+        }
+    }
+
+    /// Flush any buffered output.
+    pub fn flush(&mut self) -> io::Result<()> {
+        io::Write::flush(&mut self.json.out.out)
+    }
+
+    /// Flush any buffered output and get the inner output.
+    pub fn into_inner(self) -> io::Result<W> {
+        self.json
+            .out
+            .out
+            .into_inner()
+            .map_err(|err| err.into_error())
+    }
+
+    /// Return the IO error that caused visiting to fail, if there is one.
+    fn io(&mut self, r: Result<(), Error>) -> Result<(), Error> {
+        r.map_err(|err| match self.json.out.error.take() {
+            Some(err) => Error::custom(err),
+            None => err,
+        })
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'v, W> Visitor<'v> for IoWriter<W>
+where
+    W: io::Write,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        let r = self.json.visit_i64(v);
+        self.io(r)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        let r = self.json.visit_u64(v);
+        self.io(r)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        let r = self.json.visit_i128(v);
+        self.io(r)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        let r = self.json.visit_u128(v);
+        self.io(r)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        let r = self.json.visit_f64(v);
+        self.io(r)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        let r = self.json.visit_f64_nonfinite(v);
+        self.io(r)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        let r = self.json.visit_number_str(v);
+        self.io(r)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        let r = self.json.visit_bool(v);
+        self.io(r)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        let r = self.json.visit_str(v);
+        self.io(r)
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let r = self.json.str_begin(len);
+        self.io(r)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        let r = self.json.visit_str_fragment(v);
+        self.io(r)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        let r = self.json.str_end();
+        self.io(r)
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        let r = self.json.visit_bytes(v);
+        self.io(r)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        let r = self.json.visit_bytes_hint(v, hint);
+        self.io(r)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        let r = self.json.visit_none();
+        self.io(r)
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        let r = self.json.visit_unit();
+        self.io(r)
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let r = self.json.map_begin(len);
+        self.io(r)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        let r = self.json.map_key();
+        self.io(r)
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        let r = self.json.map_value();
+        self.io(r)
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        let r = self.json.map_end();
+        self.io(r)
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        let r = self.json.seq_begin(len);
+        self.io(r)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        let r = self.json.seq_elem();
+        self.io(r)
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        let r = self.json.seq_end();
+        self.io(r)
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        let r = self.json.record_begin(name, len);
+        self.io(r)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        let r = self.json.record_field(name);
+        self.io(r)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        let r = self.json.record_end();
+        self.io(r)
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        let r = self.json.visit_variant(name, variant, index);
+        self.io(r)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        let r = self.json.variant_begin(name, variant, index);
+        self.io(r)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        let r = self.json.variant_end();
+        self.io(r)
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.json.supports(capability)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        let r = self.json.visit_fmt(args);
+        self.io(r)
+    }
+}
+
+/// Write to an `io::Write` through `fmt::Write`, keeping the last IO error.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct Io<W> {
+    out: W,
+    error: Option<io::Error>,
+}
+
+#[cfg(feature = "std")]
+impl<W> fmt::Write for Io<W>
+where
+    W: io::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.out.write_all(s.as_bytes()).map_err(|err| {
+            self.error = Some(err);
+            fmt::Error
+        })
+    }
+}
+
+/// Escape a string written into a JSON string.
+pub(crate) struct Escape<W>(pub(crate) W);
+
+impl<W> fmt::Write for Escape<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+
+        for (i, b) in s.bytes().enumerate() {
+            let escape = match b {
+                b'"' => "\\\"",
+                b'\\' => "\\\\",
+                b'\n' => "\\n",
+                b'\r' => "\\r",
+                b'\t' => "\\t",
+                0x08 => "\\b",
+                0x0c => "\\f",
+                0x00..=0x1f => "",
+                _ => continue,
+            };
+
+            self.0.write_str(&s[start..i])?;
+            if escape.is_empty() {
+                write!(self.0, "\\u{:04x}", b)?;
+            } else {
+                self.0.write_str(escape)?;
+            }
+
+            start = i + 1;
+        }
+
+        self.0.write_str(&s[start..])
+    }
+}
+
+/// Format bytes as lowercase hex.
+struct Hex<'a>(&'a [u8]);
+
+impl<'a> fmt::Display for Hex<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Format bytes as padded standard base64.
+pub(crate) struct Base64<'a>(pub(crate) &'a [u8]);
+
+impl<'a> fmt::Display for Base64<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::fmt::Write;
+
+        const ALPHABET: &[u8; 64] =
+            b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+        for chunk in self.0.chunks(3) {
+            let n = chunk
+                .iter()
+                .enumerate()
+                .fold(0u32, |n, (i, b)| n | (*b as u32) << (16 - i * 8));
+
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    f.write_char(ALPHABET[(n >> (18 - i * 6)) as usize & 0x3f] as char)?;
+                } else {
+                    f.write_char('=')?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a string is a valid JSON number.
+pub(crate) fn is_number(s: &str) -> bool {
+    fn digits(s: &[u8]) -> usize {
+        s.iter().take_while(|b| b.is_ascii_digit()).count()
+    }
+
+    let s = s.as_bytes();
+    let mut i = 0;
+
+    if s.first() == Some(&b'-') {
+        i += 1;
+    }
+
+    match digits(&s[i..]) {
+        0 => return false,
+        n if n > 1 && s[i] == b'0' => return false,
+        n => i += n,
+    }
+
+    if s.get(i) == Some(&b'.') {
+        i += 1;
+
+        match digits(&s[i..]) {
+            0 => return false,
+            n => i += n,
+        }
+    }
+
+    if let Some(b'e') | Some(b'E') = s.get(i) {
+        i += 1;
+
+        if let Some(b'+') | Some(b'-') = s.get(i) {
+            i += 1;
+        }
+
+        match digits(&s[i..]) {
+            0 => return false,
+            n => i += n,
+        }
+    }
+
+    i == s.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_json(v: &dyn Visit, expected: &str) {
+        let mut json = Writer::new(VisitArgs::new());
+        v.visit(&mut json).unwrap();
+
+        assert_eq!(expected, json.into_inner().to_str().unwrap());
+    }
+
+    #[test]
+    fn write_primitives() {
+        assert_json(&1u8, "1");
+        assert_json(&-1i64, "-1");
+        assert_json(&u128::MAX, "340282366920938463463374607431768211455");
+        assert_json(&1.0f64, "1.0");
+        assert_json(&1.5e300f64, "1.5e300");
+        assert_json(&f64::NAN, "null");
+        assert_json(&true, "true");
+        assert_json(&'a', "\"a\"");
+        assert_json(&(), "null");
+        assert_json(&None::<u8>, "null");
+        assert_json(&Some(1), "1");
+    }
+
+    #[test]
+    fn write_escaped_str() {
+        assert_json(&"a \"b\" \\ \n\t\u{1}", r#""a \"b\" \\ \n\t\u0001""#);
+        assert_json(&format_args!("{}\n", 1), r#""1\n""#);
+    }
+
+    #[test]
+    fn write_nested() {
+        fn visit_nested<'v>(visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.record_begin("Record", Some(3))?;
+
+            visitor.record_field("a")?;
+            visitor.seq_begin(Some(2))?;
+            visitor.seq_elem()?;
+            visitor.seq_begin(Some(0))?;
+            visitor.seq_end()?;
+            visitor.seq_elem()?;
+            visitor.map_begin(Some(2))?;
+            visitor.map_key()?;
+            visitor.visit_u64(1)?;
+            visitor.map_value()?;
+            visitor.visit_str("b")?;
+            visitor.map_key()?;
+            visitor.visit_str("c")?;
+            visitor.map_value()?;
+            visitor.visit_bool(false)?;
+            visitor.map_end()?;
+            visitor.seq_end()?;
+
+            visitor.record_field("b")?;
+            visitor.variant_begin("Enum", "Newtype", 0)?;
+            visitor.visit_i64(-1)?;
+            visitor.variant_end()?;
+
+            visitor.record_field("c")?;
+            visitor.visit_variant("Enum", "Unit", 1)?;
+
+            visitor.record_end()
+        }
+
+        let mut json = Writer::new(VisitArgs::new());
+        visit_nested(&mut json).unwrap();
+
+        assert_eq!(
+            r#"{"a":[[],{"1":"b","c":false}],"b":{"Newtype":-1},"c":"Unit"}"#,
+            json.into_inner().to_str().unwrap()
+        );
+    }
+
+    #[test]
+    fn write_invalid_key() {
+        let mut json = Writer::new(VisitArgs::new());
+
+        json.map_begin(None).unwrap();
+        json.map_key().unwrap();
+        assert!(json.seq_begin(None).is_err());
+    }
+
+    #[test]
+    fn write_unbalanced() {
+        assert!(Writer::new(VisitArgs::new()).seq_end().is_err());
+        assert!(Writer::new(VisitArgs::new()).map_end().is_err());
+    }
+
+    #[test]
+    fn write_bytes() {
+        let mut json = Writer::new(VisitArgs::new());
+        json.visit_bytes_hint(&[0xde, 0xad], BytesHint::Hex)
+            .unwrap();
+        assert_eq!(r#""dead""#, json.into_inner().to_str().unwrap());
+
+        for (bytes, expected) in &[
+            (&b""[..], r#""""#),
+            (&b"f"[..], r#""Zg==""#),
+            (&b"fo"[..], r#""Zm8=""#),
+            (&b"foo"[..], r#""Zm9v""#),
+            (&b"foob"[..], r#""Zm9vYg==""#),
+        ] {
+            let mut json = Writer::new(VisitArgs::new());
+            json.visit_bytes_hint(bytes, BytesHint::Base64).unwrap();
+            assert_eq!(*expected, json.into_inner().to_str().unwrap());
+        }
+
+        let mut json = Writer::new(VisitArgs::new());
+        json.visit_bytes(&[1, 2]).unwrap();
+        assert_eq!("[1,2]", json.into_inner().to_str().unwrap());
+    }
+
+    #[test]
+    fn number_str() {
+        for valid in &["0", "-0", "1.5", "10e5", "1E+2", "-1.0e-7"] {
+            assert!(is_number(valid), "{}", valid);
+        }
+
+        for invalid in &["", "-", "01", "1.", ".5", "1e", "NaN", "1x"] {
+            assert!(!is_number(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn write_io() {
+        use crate::std::{string::ToString, vec::Vec};
+
+        let mut json = IoWriter::new(Vec::new());
+
+        json.seq_begin(None).unwrap();
+        json.seq_elem().unwrap();
+        json.visit_str("a").unwrap();
+        json.seq_end().unwrap();
+
+        assert_eq!(br#"["a"]"#, &*json.into_inner().unwrap());
+
+        struct Fail;
+
+        impl io::Write for Fail {
+            fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+                Err(io::Error::other("the output failed"))
+            }
+
+            fn flush(&mut self) -> io::Result<()> {
+                Ok(())
+            }
+        }
+
+        // Writes that fit in the buffer only fail when they're flushed
+        let mut json = IoWriter::new(Fail);
+        json.visit_str("a").unwrap();
+        assert!(json.flush().is_err());
+
+        let mut json = IoWriter::new(Fail);
+        let err = json.visit_str(&"a".repeat(16 * 1024)).unwrap_err();
+        assert_eq!("the output failed", err.to_string());
+    }
+
+    #[test]
+    fn write_pretty() {
+        let mut json = Writer::pretty(VisitArgs::new(), Pretty::new());
+
+        json.record_begin("Record", Some(3)).unwrap();
+        json.record_field("a").unwrap();
+        json.seq_begin(Some(1)).unwrap();
+        json.seq_elem().unwrap();
+        json.visit_u64(1).unwrap();
+        json.seq_end().unwrap();
+        json.record_field("b").unwrap();
+        json.map_begin(Some(0)).unwrap();
+        json.map_end().unwrap();
+        json.record_field("c").unwrap();
+        json.variant_begin("Enum", "Newtype", 0).unwrap();
+        json.visit_bool(true).unwrap();
+        json.variant_end().unwrap();
+        json.record_end().unwrap();
+
+        assert_eq!(
+            "{\n  \"a\": [\n    1\n  ],\n  \"b\": {},\n  \"c\": {\n    \"Newtype\": true\n  }\n}",
+            json.into_inner().to_str().unwrap()
+        );
+
+        let mut json = Writer::pretty(
+            VisitArgs::new(),
+            Pretty::new()
+                .indent("\t")
+                .key_spacing(false)
+                .trailing_newline(true),
+        );
+
+        json.map_begin(Some(1)).unwrap();
+        json.map_key().unwrap();
+        json.visit_str("a").unwrap();
+        json.map_value().unwrap();
+        json.visit_none().unwrap();
+        json.map_end().unwrap();
+        json.visit_u64(1).unwrap();
+
+        assert_eq!(
+            "{\n\t\"a\":null\n}\n1\n",
+            json.into_inner().to_str().unwrap()
+        );
+    }
+}
@@ -6,79 +6,719 @@ extern crate core as std;
 #[cfg(feature = "std")]
 extern crate std;
 
-use self::std::fmt;
+use self::std::{cell, ffi, marker, net, num, ops, sync::atomic, time};
 
-/// A serializer for primitive values.
-pub trait Visitor {
-    /// Visit a signed integer.
-    fn visit_i64(&mut self, v: i64) {
-        self.visit_fmt(&format_args!("{:?}", v));
-    }
+#[cfg(not(feature = "serde_interop"))]
+use self::std::convert::TryFrom;
+
+mod error;
+mod forward;
+mod timestamp;
+mod write;
+
+pub mod adapter;
+#[cfg(feature = "cbor")]
+pub mod cbor;
+pub mod csv;
+#[cfg(feature = "digest")]
+pub mod digest;
+pub mod fmt;
+#[cfg(feature = "std")]
+pub mod io;
+pub mod json;
+pub mod logfmt;
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+#[cfg(feature = "postcard")]
+pub mod postcard;
+pub mod ron;
+pub mod text;
+pub mod toml;
+pub mod xml;
+pub mod yaml;
+
+#[doc(hidden)]
+#[path = "private.rs"]
+pub mod __private;
+
+pub use self::{
+    adapter::VisitorExt,
+    error::Error,
+    stream::{Stream, Streamer},
+    value::Value,
+    write::{TextWriter, WriteBytes},
+};
+
+#[cfg(feature = "url")]
+pub use self::ext::UrlParts;
+
+#[cfg(feature = "derive")]
+pub use ser_derive::Visit;
+
+// Lets the tests use `#[derive(Visit)]`, which refers to this crate as `ser`
+#[cfg(all(test, feature = "derive"))]
+extern crate self as ser;
+
+/// Expand the methods shared by `Visitor` and `Stream`.
+///
+/// The traits only differ in the error their methods return, so their
+/// methods and default implementations are only written once, here.
+macro_rules! visitor_methods {
+    ($error:ty) => {
+        /// Visit an 8bit signed integer.
+        ///
+        /// The default implementation forwards to `visit_i64`.
+        fn visit_i8(&mut self, v: i8) -> Result<(), $error> {
+            self.visit_i64(v as i64)
+        }
 
-    /// Visit an unsigned integer.
-    fn visit_u64(&mut self, v: u64) {
-        self.visit_fmt(&format_args!("{:?}", v));
-    }
+        /// Visit a 16bit signed integer.
+        ///
+        /// The default implementation forwards to `visit_i64`.
+        fn visit_i16(&mut self, v: i16) -> Result<(), $error> {
+            self.visit_i64(v as i64)
+        }
 
-    /// Visit a floating point number.
-    fn visit_f64(&mut self, v: f64) {
-        self.visit_fmt(&format_args!("{:?}", v));
-    }
+        /// Visit a 32bit signed integer.
+        ///
+        /// The default implementation forwards to `visit_i64`.
+        fn visit_i32(&mut self, v: i32) -> Result<(), $error> {
+            self.visit_i64(v as i64)
+        }
 
-    /// Visit a boolean.
-    fn visit_bool(&mut self, v: bool) {
-        self.visit_fmt(&format_args!("{:?}", v));
-    }
+        /// Visit an 8bit unsigned integer.
+        ///
+        /// The default implementation forwards to `visit_u64`.
+        fn visit_u8(&mut self, v: u8) -> Result<(), $error> {
+            self.visit_u64(v as u64)
+        }
 
-    /// Visit a single character.
-    fn visit_char(&mut self, v: char) {
-        let mut b = [0; 4];
-        self.visit_str(&*v.encode_utf8(&mut b));
-    }
+        /// Visit a 16bit unsigned integer.
+        ///
+        /// The default implementation forwards to `visit_u64`.
+        fn visit_u16(&mut self, v: u16) -> Result<(), $error> {
+            self.visit_u64(v as u64)
+        }
 
-    /// Visit a UTF8 string.
-    fn visit_str(&mut self, v: &str) {
-        self.visit_fmt(&format_args!("{:?}", v));
-    }
+        /// Visit a 32bit unsigned integer.
+        ///
+        /// The default implementation forwards to `visit_u64`.
+        fn visit_u32(&mut self, v: u32) -> Result<(), $error> {
+            self.visit_u64(v as u64)
+        }
 
-    /// Visit a raw byte buffer.
-    fn visit_bytes(&mut self, v: &[u8]) {
-        self.visit_fmt(&format_args!("{:?}", v));
-    }
+        /// Visit a signed integer.
+        fn visit_i64(&mut self, v: i64) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit an unsigned integer.
+        fn visit_u64(&mut self, v: u64) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a 128bit signed integer.
+        fn visit_i128(&mut self, v: i128) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a 128bit unsigned integer.
+        fn visit_u128(&mut self, v: u128) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a floating point number.
+        fn visit_f64(&mut self, v: f64) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a floating point number that's `NaN` or infinite.
+        ///
+        /// Formats that can't represent non-finite numbers can override this
+        /// method to apply a `NonFinite` policy instead of handling them
+        /// in `visit_f64`. The default implementation forwards to `visit_f64`.
+        fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), $error> {
+            self.visit_f64(v)
+        }
+
+        /// Visit an arbitrary-precision number in its textual form.
+        ///
+        /// This can be used for decimals and big integers that can't be
+        /// represented exactly by the other numeric methods. The default
+        /// implementation formats the number as-is.
+        fn visit_number_str(&mut self, v: &str) -> Result<(), $error> {
+            self.visit_fallback(&format_args!("{}", v))
+        }
+
+        /// Visit a boolean.
+        fn visit_bool(&mut self, v: bool) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a single character.
+        fn visit_char(&mut self, v: char) -> Result<(), $error> {
+            let mut b = [0; 4];
+            self.visit_str(&*v.encode_utf8(&mut b))
+        }
+
+        /// Visit a UTF8 string.
+        fn visit_str(&mut self, v: &str) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a UTF8 string that's borrowed for the lifetime of the visitor.
+        ///
+        /// Visitors can retain the string without copying it.
+        /// The default implementation forwards to `visit_str`.
+        fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), $error> {
+            self.visit_str(v)
+        }
+
+        /// Begin visiting a string in fragments.
+        ///
+        /// The string is visited by calling `visit_str_fragment` for each part of it.
+        /// The string is completed by calling `str_end`. The `len` is a hint for the
+        /// total length of the string in bytes, if it's known.
+        fn str_begin(&mut self, len: Option<usize>) -> Result<(), $error> {
+            let _ = len;
+            self.visit_fallback(&format_args!("\""))
+        }
+
+        /// Visit a fragment of a string.
+        fn visit_str_fragment(&mut self, v: &str) -> Result<(), $error> {
+            self.visit_fallback(&format_args!("{}", v.escape_debug()))
+        }
+
+        /// Complete a string visited in fragments.
+        fn str_end(&mut self) -> Result<(), $error> {
+            self.visit_fallback(&format_args!("\""))
+        }
+
+        /// Visit a raw byte buffer.
+        fn visit_bytes(&mut self, v: &[u8]) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a raw byte buffer that's borrowed for the lifetime of the visitor.
+        ///
+        /// Visitors can retain the buffer without copying it.
+        /// The default implementation forwards to `visit_bytes`.
+        fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), $error> {
+            self.visit_bytes(v)
+        }
+
+        /// Visit a raw byte buffer with a hint for how it should be encoded.
+        ///
+        /// The default implementation ignores the hint and forwards to `visit_bytes`.
+        fn visit_bytes_hint(&mut self, v: &[u8], hint: $crate::BytesHint) -> Result<(), $error> {
+            let _ = hint;
+            self.visit_bytes(v)
+        }
+
+        /// Visit an empty optional value.
+        fn visit_none(&mut self) -> Result<(), $error> {
+            self.visit_fallback(&format_args!("None"))
+        }
+
+        /// Visit a present optional value.
+        ///
+        /// The inner value is visited immediately after this call.
+        fn visit_some(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Visit a unit value.
+        fn visit_unit(&mut self) -> Result<(), $error> {
+            self.visit_fallback(&format_args!("()"))
+        }
+
+        /// Begin visiting a map.
+        ///
+        /// Each entry in the map is visited by calling `map_key` followed by
+        /// the key, then `map_value` followed by the value. The map is completed
+        /// by calling `map_end`.
+        fn map_begin(&mut self, len: Option<usize>) -> Result<(), $error> {
+            let _ = len;
+            Ok(())
+        }
+
+        /// Visit a map key.
+        ///
+        /// The key itself is visited immediately after this call.
+        fn map_key(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Visit a map value.
+        ///
+        /// The value itself is visited immediately after this call.
+        fn map_value(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Complete a map.
+        fn map_end(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Begin visiting a sequence.
+        ///
+        /// Each element in the sequence is visited by calling `seq_elem`
+        /// followed by the element. The sequence is completed by calling `seq_end`.
+        fn seq_begin(&mut self, len: Option<usize>) -> Result<(), $error> {
+            let _ = len;
+            Ok(())
+        }
+
+        /// Visit a sequence element.
+        ///
+        /// The element itself is visited immediately after this call.
+        fn seq_elem(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Complete a sequence.
+        fn seq_end(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Begin visiting a record with named fields.
+        ///
+        /// Each field in the record is visited by calling `record_field`
+        /// followed by the field value. The record is completed by calling `record_end`.
+        fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), $error> {
+            let _ = (name, len);
+            Ok(())
+        }
+
+        /// Visit a record field.
+        ///
+        /// The field value is visited immediately after this call.
+        fn record_field(&mut self, name: &str) -> Result<(), $error> {
+            let _ = name;
+            Ok(())
+        }
+
+        /// Complete a record.
+        fn record_end(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Visit an enum variant without any data.
+        fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), $error> {
+            let _ = (name, index);
+            self.visit_fallback(&format_args!("{}", variant))
+        }
+
+        /// Begin visiting an enum variant with data.
+        ///
+        /// The data is visited immediately after this call as a single value
+        /// for newtype variants, a sequence for tuple variants, or a record for
+        /// struct variants. The variant is completed by calling `variant_end`.
+        fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), $error> {
+            let _ = (name, variant, index);
+            Ok(())
+        }
+
+        /// Complete an enum variant with data.
+        fn variant_end(&mut self) -> Result<(), $error> {
+            Ok(())
+        }
+
+        /// Announce the semantic type of the next value.
+        ///
+        /// Tags like `"uuid"` let schema-aware visitors round-trip typed values
+        /// that would otherwise be seen as bare primitives. The tag applies to
+        /// the value visited immediately after it. The default implementation
+        /// ignores the tag.
+        fn visit_tag(&mut self, tag: &str) -> Result<(), $error> {
+            let _ = tag;
+            Ok(())
+        }
+
+        /// Visit a span of time.
+        fn visit_duration(&mut self, v: $crate::std::time::Duration) -> Result<(), $error> {
+            self.visit_fallback(&v)
+        }
+
+        /// Visit a point in time.
+        ///
+        /// The timestamp is given as the number of seconds and additional nanoseconds
+        /// since the unix epoch, `1970-01-01T00:00:00Z`. Times before the epoch have
+        /// negative seconds. The default implementation formats the timestamp as an
+        /// RFC3339 string in UTC.
+        fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), $error> {
+            self.visit_fallback(&format_args!(
+                "{}",
+                $crate::timestamp::Rfc3339 { secs, nanos }
+            ))
+        }
+
+        /// Visit a value that can be formatted for display.
+        fn visit_display(&mut self, v: &dyn $crate::std::fmt::Display) -> Result<(), $error> {
+            self.visit_fallback(&format_args!("{}", v))
+        }
+
+        /// Visit a value that can only be formatted for debugging.
+        ///
+        /// This is used as a fallback for values that can't be visited
+        /// in a more structured way.
+        fn visit_debug(&mut self, v: &dyn $crate::std::fmt::Debug) -> Result<(), $error> {
+            self.visit_fallback(v)
+        }
+
+        /// Visit an error.
+        ///
+        /// The default implementation visits the error as a record with
+        /// its `message` and optional `source`, following the chain of sources.
+        #[cfg(feature = "std")]
+        fn visit_error(
+            &mut self,
+            v: &(dyn $crate::std::error::Error + 'static),
+        ) -> Result<(), $error> {
+            self.record_begin("Error", Some(2))?;
+
+            self.record_field("message")?;
+            self.visit_display(&format_args!("{}", v))?;
+
+            self.record_field("source")?;
+            match v.source() {
+                Some(source) => {
+                    self.visit_some()?;
+                    self.visit_error(source)?;
+                }
+                None => self.visit_none()?,
+            }
+
+            self.record_end()
+        }
 
-    /// Visit standard arguments.
-    fn visit_fmt(&mut self, args: &fmt::Arguments);
+        /// Get request-scoped context for the current visit.
+        ///
+        /// `Visit` implementations can downcast the context to read state
+        /// like redaction rules without relying on globals. Adapters propagate
+        /// the context of the visitors they wrap. The default implementation
+        /// returns `None`.
+        fn context(&self) -> Option<&(dyn $crate::std::any::Any + 'static)> {
+            None
+        }
+
+        /// Whether this visitor opts in to a capability of the protocol.
+        ///
+        /// `Visit` implementations can probe for a capability before using the
+        /// methods it covers, and pick an older encoding when it's missing.
+        /// The default implementation returns `false` for every capability.
+        fn supports(&self, capability: $crate::Capability) -> bool {
+            let _ = capability;
+            false
+        }
+
+        /// Whether this visitor produces a human-readable format.
+        ///
+        /// Values like network addresses can be visited more compactly
+        /// in formats that aren't human-readable. The default implementation
+        /// returns `true`.
+        fn is_human_readable(&self) -> bool {
+            true
+        }
+
+        /// Visit a value that isn't handled by any other method.
+        ///
+        /// The default implementations of all other methods forward here,
+        /// so fallback behavior can be changed in one place. The default
+        /// implementation formats the value using `visit_fmt`.
+        fn visit_fallback(&mut self, v: &dyn $crate::std::fmt::Debug) -> Result<(), $error> {
+            self.visit_fmt(&format_args!("{:?}", v))
+        }
+
+        /// Visit standard arguments.
+        fn visit_fmt(&mut self, args: &$crate::std::fmt::Arguments) -> Result<(), $error>;
+    };
+}
+
+/// A serializer for primitive values.
+///
+/// # Extending the protocol
+///
+/// New methods are only ever added with a default implementation that
+/// forwards to methods that already exist, so existing visitors keep
+/// seeing the same calls they did before. Methods that carry new semantics
+/// also come with a `Capability` that visitors opt in to through
+/// `Visitor::supports`. `Visit` implementations that want to use those
+/// methods can check for the capability first.
+pub trait Visitor<'v> {
+    visitor_methods!(Error);
+}
+
+/// A hint for how a visitor should encode a byte buffer.
+///
+/// Visitors are free to ignore the hint if their format has
+/// its own encoding for bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum BytesHint {
+    /// The bytes are a digest or short identifier that's best encoded as hex.
+    Hex,
+    /// The bytes are an opaque blob that's best encoded as base64.
+    Base64,
+}
+
+/// A part of the `Visitor` protocol that visitors can opt in to.
+///
+/// See `Visitor::supports`. New capabilities are added alongside new
+/// methods on `Visitor`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// Arbitrary-precision numbers through `visit_number_str`.
+    NumberStr,
+    /// Strings in fragments through `str_begin`, `visit_str_fragment`, and `str_end`.
+    StrFragments,
+    /// Encoding hints for bytes through `visit_bytes_hint`.
+    BytesHint,
+    /// Semantic type names through `visit_tag`.
+    Tag,
+    /// Non-finite numbers through `visit_f64_nonfinite`.
+    NonFinite,
+    /// Request-scoped context through `context`.
+    Context,
+}
+
+/// A policy for visiting floating point numbers that are `NaN` or infinite.
+///
+/// Visitors for formats that can't represent non-finite numbers, like JSON,
+/// can apply a policy from `Visitor::visit_f64_nonfinite`:
+///
+/// ```
+/// # use ser::{Error, NonFinite, Visitor};
+/// # use std::fmt;
+/// struct Json;
+///
+/// impl<'v> Visitor<'v> for Json {
+///     fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+///         NonFinite::None.apply(v, self)
+///     }
+///
+///     fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+///         Ok(())
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum NonFinite {
+    /// Fail with an error.
+    Error,
+    /// Visit the number as an empty optional value.
+    None,
+    /// Visit the number as one of the strings `"NaN"`, `"Infinity"`, or `"-Infinity"`.
+    Str,
+}
+
+impl NonFinite {
+    /// Visit a non-finite number according to this policy.
+    pub fn apply<'v, V>(self, v: f64, visitor: &mut V) -> Result<(), Error>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        match self {
+            NonFinite::Error => Err(Error::msg("the number is not finite")),
+            NonFinite::None => visitor.visit_none(),
+            NonFinite::Str => visitor.visit_str(if v.is_nan() {
+                "NaN"
+            } else if v.is_sign_positive() {
+                "Infinity"
+            } else {
+                "-Infinity"
+            }),
+        }
+    }
 }
 
 /// A value that can be serialized.
-/// 
+///
 /// This type is expected to be used as a trait object, like `&dyn Visit`
 /// instead of as a generic, like `T: Visit`. It is only implemented for
 /// a selection of primitive types and cannot be implemented manually.
-/// 
+/// With the `derive` feature, it can be derived for structs and enums using
+/// `#[derive(Visit)]`. Derived types must also implement `Debug`.
+///
 /// If the `serde_interop` feature is enabled, this type can be serialized
 /// using `serde` in addition to the simple `Visitor` from this crate.
 pub trait Visit: imp::VisitPrivate {
     /// Visit the value with the given serializer.
-    fn visit(&self, visitor: &mut dyn Visitor);
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error>;
+
+    /// Stream the value with the given generic serializer.
+    ///
+    /// The default implementation visits the value through a `Streamer`.
+    /// Primitives, collections, and derived types override it so their
+    /// contents are streamed generically too. Values behind references to
+    /// unsized types, like `&[T]`, use the default.
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+        Self: Sized,
+    {
+        Streamer::new(stream).visit(self)
+    }
+
+    /// Visit a slice of values.
+    ///
+    /// Slices are visited as sequences by default. This lets `[u8]`
+    /// be visited as bytes instead.
+    #[doc(hidden)]
+    fn visit_slice<'v>(slice: &'v [Self], visitor: &mut dyn Visitor<'v>) -> Result<(), Error>
+    where
+        Self: Sized,
+    {
+        visit_seq(slice.len(), slice.iter(), visitor)
+    }
+
+    /// Stream a slice of values.
+    ///
+    /// This is the generic counterpart to `visit_slice`.
+    #[doc(hidden)]
+    fn stream_slice<'v, S>(slice: &'v [Self], stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+        Self: Sized,
+    {
+        stream_seq(slice.len(), slice.iter(), stream)
+    }
+}
+
+fn visit_seq<'v, T>(
+    len: usize,
+    elems: impl Iterator<Item = &'v T>,
+    visitor: &mut dyn Visitor<'v>,
+) -> Result<(), Error>
+where
+    T: Visit + 'v,
+{
+    visitor.seq_begin(Some(len))?;
+
+    for v in elems {
+        visitor.seq_elem()?;
+        v.visit(visitor)?;
+    }
+
+    visitor.seq_end()
+}
+
+fn stream_seq<'v, T, S>(
+    len: usize,
+    elems: impl Iterator<Item = &'v T>,
+    stream: &mut S,
+) -> Result<(), S::Error>
+where
+    T: Visit + 'v,
+    S: Stream<'v>,
+{
+    stream.seq_begin(Some(len))?;
+
+    for v in elems {
+        stream.seq_elem()?;
+        v.stream(stream)?;
+    }
+
+    stream.seq_end()
+}
+
+/// Implement `Visit` for types by visiting their `Display` implementation.
+///
+/// This is useful for newtypes, like identifiers, that are only ever
+/// captured as strings. The types must also implement `Debug`. With
+/// `serde_interop`, this implements `Serialize` instead.
+///
+/// ```
+/// use std::fmt;
+///
+/// #[derive(Debug)]
+/// struct OrderId(u64);
+///
+/// impl fmt::Display for OrderId {
+///     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+///         write!(f, "order-{}", self.0)
+///     }
+/// }
+///
+/// ser::impl_visit_via_display!(OrderId);
+///
+/// let id: &dyn ser::Visit = &OrderId(1);
+/// ```
+#[cfg(not(feature = "serde_interop"))]
+#[macro_export]
+macro_rules! impl_visit_via_display {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $crate::Visit for $ty {
+                fn visit<'v>(&'v self, visitor: &mut dyn $crate::Visitor<'v>) -> ::core::result::Result<(), $crate::Error> {
+                    visitor.visit_display(self)
+                }
+            }
+
+            impl $crate::__private::VisitPrivate for $ty {}
+        )*
+    };
+}
+
+/// Implement `Visit` for types by visiting their `Display` implementation.
+///
+/// This is useful for newtypes, like identifiers, that are only ever
+/// captured as strings. The types must also implement `Debug`. With
+/// `serde_interop`, this implements `Serialize` instead.
+#[cfg(feature = "serde_interop")]
+#[macro_export]
+macro_rules! impl_visit_via_display {
+    ($($ty:ty),* $(,)?) => {
+        $(
+            impl $crate::__private::serde::Serialize for $ty {
+                fn serialize<__S>(&self, serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error>
+                where
+                    __S: $crate::__private::serde::Serializer,
+                {
+                    serializer.collect_str(self)
+                }
+            }
+        )*
+    };
 }
 
 /// This trait is a private implementation detail for testing.
-/// 
+///
 /// All it does is make sure that our set of concrete types
 /// that implement `Visit` always implement the `Visit` trait,
 /// regardless of crate features and blanket implementations.
+#[allow(dead_code)]
 trait EnsureVisit: Visit {}
 
-macro_rules! ensure_impl_visit {
-    ($($ty:ty { $($serialize:tt)* })*) => {
+macro_rules! ensure_visit {
+    ($($ty:ty),*) => {
         $(
             impl EnsureVisit for $ty {}
             impl<'a> EnsureVisit for &'a $ty {}
+        )*
+    }
+}
+
+macro_rules! ensure_impl_visit {
+    ($($ty:ty {
+        fn visit<'v>(&'v $self:ident, $visitor:ident: &mut dyn Visitor<'v>) -> Result<(), Error> $body:block
+    })*) => {
+        $(
+            ensure_visit!($ty);
 
             #[cfg(not(feature = "serde_interop"))]
             impl Visit for $ty {
-                $($serialize)*
+                fn visit<'v>(&'v $self, $visitor: &mut dyn Visitor<'v>) -> Result<(), Error> $body
+
+                // `Visit::stream` requires `Self: Sized`, so this is dead code for unsized types like `str`
+                #[allow(dead_code)]
+                fn stream<'v, S>(&'v $self, $visitor: &mut S) -> Result<(), S::Error>
+                where
+                    S: Stream<'v>,
+                $body
             }
 
             #[cfg(not(feature = "serde_interop"))]
@@ -87,488 +727,3299 @@ macro_rules! ensure_impl_visit {
     }
 }
 
+// Declared after the macros above so they can use them
+mod ext;
+pub mod stream;
+mod value;
+
 ensure_impl_visit! {
-    u8 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_u64(*self as u64)
-        }
-    }
     u16 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_u64(*self as u64)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u16(*self)
         }
     }
     u32 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_u64(*self as u64)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u32(*self)
         }
     }
     u64 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
             visitor.visit_u64(*self)
         }
     }
+    u128 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u128(*self)
+        }
+    }
 
     i8 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_i64(*self as i64)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i8(*self)
         }
     }
     i16 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_i64(*self as i64)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i16(*self)
         }
     }
     i32 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_i64(*self as i64)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i32(*self)
         }
     }
     i64 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
             visitor.visit_i64(*self)
         }
     }
+    i128 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i128(*self)
+        }
+    }
+
+    // Sizes that don't fit in 64 bits are visited as 128bit integers
+    usize {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = *self;
+
+            match u64::try_from(v) {
+                Ok(v) => visitor.visit_u64(v),
+                Err(_) => visitor.visit_u128(v as u128),
+            }
+        }
+    }
+    isize {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = *self;
 
+            match i64::try_from(v) {
+                Ok(v) => visitor.visit_i64(v),
+                Err(_) => visitor.visit_i128(v as i128),
+            }
+        }
+    }
     f32 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_f64(*self as f64)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = *self as f64;
+
+            if v.is_finite() {
+                visitor.visit_f64(v)
+            } else {
+                visitor.visit_f64_nonfinite(v)
+            }
         }
     }
     f64 {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_f64(*self)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if self.is_finite() {
+                visitor.visit_f64(*self)
+            } else {
+                visitor.visit_f64_nonfinite(*self)
+            }
         }
     }
 
     char {
-        fn visit(&self, visitor: &mut dyn Visitor) {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
             visitor.visit_char(*self)
         }
     }
     bool {
-        fn visit(&self, visitor: &mut dyn Visitor) {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
             visitor.visit_bool(*self)
         }
     }
     str {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_str(self)
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self)
+        }
+    }
+    () {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_unit()
+        }
+    }
+    time::Duration {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_duration(*self)
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+ensure_impl_visit! {
+    String {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_borrowed_str(self)
         }
     }
-    [u8] {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_bytes(self)
+}
+
+// C strings that aren't valid UTF-8 are visited as bytes
+ensure_impl_visit! {
+    ffi::CStr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Ok(v) => visitor.visit_borrowed_str(v),
+                Err(_) => visitor.visit_borrowed_bytes(self.to_bytes()),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+ensure_impl_visit! {
+    std::ffi::CString {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Ok(v) => visitor.visit_borrowed_str(v),
+                Err(_) => visitor.visit_borrowed_bytes(self.to_bytes()),
+            }
+        }
+    }
+}
+
+// Paths and OS strings that aren't valid UTF-8 are visited as lossily converted strings
+#[cfg(feature = "std")]
+ensure_impl_visit! {
+    std::ffi::OsStr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Some(v) => visitor.visit_borrowed_str(v),
+                None => visitor.visit_str(&self.to_string_lossy()),
+            }
+        }
+    }
+    std::ffi::OsString {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Some(v) => visitor.visit_borrowed_str(v),
+                None => visitor.visit_str(&self.to_string_lossy()),
+            }
+        }
+    }
+    std::path::Path {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Some(v) => visitor.visit_borrowed_str(v),
+                None => visitor.visit_str(&self.to_string_lossy()),
+            }
+        }
+    }
+    std::path::PathBuf {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            match self.to_str() {
+                Some(v) => visitor.visit_borrowed_str(v),
+                None => visitor.visit_str(&self.to_string_lossy()),
+            }
+        }
+    }
+}
+
+ensure_impl_visit! {
+    num::NonZeroU8 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u8(self.get())
+        }
+    }
+    num::NonZeroI8 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i8(self.get())
+        }
+    }
+    num::NonZeroU16 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u16(self.get())
+        }
+    }
+    num::NonZeroI16 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i16(self.get())
+        }
+    }
+    num::NonZeroU32 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u32(self.get())
+        }
+    }
+    num::NonZeroI32 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i32(self.get())
+        }
+    }
+    num::NonZeroU64 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u64(self.get())
+        }
+    }
+    num::NonZeroI64 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i64(self.get())
+        }
+    }
+    num::NonZeroU128 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u128(self.get())
+        }
+    }
+    num::NonZeroI128 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i128(self.get())
+        }
+    }
+    num::NonZeroUsize {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.get();
+
+            match u64::try_from(v) {
+                Ok(v) => visitor.visit_u64(v),
+                Err(_) => visitor.visit_u128(v as u128),
+            }
+        }
+    }
+    num::NonZeroIsize {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.get();
+
+            match i64::try_from(v) {
+                Ok(v) => visitor.visit_i64(v),
+                Err(_) => visitor.visit_i128(v as i128),
+            }
+        }
+    }
+}
+
+// Network addresses are visited as bytes in formats that aren't human-readable
+ensure_impl_visit! {
+    net::Ipv4Addr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.visit_bytes(&self.octets())
+            }
         }
     }
-}
+    net::Ipv6Addr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.visit_bytes(&self.octets())
+            }
+        }
+    }
+    net::IpAddr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                match self {
+                    net::IpAddr::V4(ip) => visitor.visit_bytes(&ip.octets()),
+                    net::IpAddr::V6(ip) => visitor.visit_bytes(&ip.octets()),
+                }
+            }
+        }
+    }
+    net::SocketAddrV4 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.seq_begin(Some(2))?;
+                visitor.seq_elem()?;
+                visitor.visit_bytes(&self.ip().octets())?;
+                visitor.seq_elem()?;
+                visitor.visit_u16(self.port())?;
+                visitor.seq_end()
+            }
+        }
+    }
+    net::SocketAddrV6 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.seq_begin(Some(2))?;
+                visitor.seq_elem()?;
+                visitor.visit_bytes(&self.ip().octets())?;
+                visitor.seq_elem()?;
+                visitor.visit_u16(self.port())?;
+                visitor.seq_end()
+            }
+        }
+    }
+    net::SocketAddr {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            if visitor.is_human_readable() {
+                visitor.visit_display(self)
+            } else {
+                visitor.seq_begin(Some(2))?;
+                visitor.seq_elem()?;
+                match self.ip() {
+                    net::IpAddr::V4(ip) => visitor.visit_bytes(&ip.octets())?,
+                    net::IpAddr::V6(ip) => visitor.visit_bytes(&ip.octets())?,
+                }
+                visitor.seq_elem()?;
+                visitor.visit_u16(self.port())?;
+                visitor.seq_end()
+            }
+        }
+    }
+}
+
+// Atomics are visited with a `Relaxed` load
+#[cfg(target_has_atomic = "8")]
+ensure_impl_visit! {
+    atomic::AtomicBool {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_bool(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+    atomic::AtomicU8 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u8(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+    atomic::AtomicI8 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i8(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "16")]
+ensure_impl_visit! {
+    atomic::AtomicU16 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u16(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+    atomic::AtomicI16 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i16(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "32")]
+ensure_impl_visit! {
+    atomic::AtomicU32 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u32(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+    atomic::AtomicI32 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i32(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "64")]
+ensure_impl_visit! {
+    atomic::AtomicU64 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u64(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+    atomic::AtomicI64 {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_i64(self.load(atomic::Ordering::Relaxed))
+        }
+    }
+}
+
+#[cfg(target_has_atomic = "ptr")]
+ensure_impl_visit! {
+    atomic::AtomicUsize {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.load(atomic::Ordering::Relaxed);
+
+            match u64::try_from(v) {
+                Ok(v) => visitor.visit_u64(v),
+                Err(_) => visitor.visit_u128(v as u128),
+            }
+        }
+    }
+    atomic::AtomicIsize {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            let v = self.load(atomic::Ordering::Relaxed);
+
+            match i64::try_from(v) {
+                Ok(v) => visitor.visit_i64(v),
+                Err(_) => visitor.visit_i128(v as i128),
+            }
+        }
+    }
+}
+
+ensure_visit!(u8);
+
+#[cfg(not(feature = "serde_interop"))]
+impl Visit for u8 {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_u8(*self)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream.visit_u8(*self)
+    }
+
+    fn visit_slice<'v>(slice: &'v [Self], visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_borrowed_bytes(slice)
+    }
+
+    fn stream_slice<'v, S>(slice: &'v [Self], stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream.visit_borrowed_bytes(slice)
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl imp::VisitPrivate for u8 {}
+
+ensure_visit!([u8], [&'static str]);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for [T]
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        T::visit_slice(self, visitor)
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for [T] where T: Visit {}
+
+#[cfg(feature = "std")]
+ensure_visit!(Vec<u8>, Vec<String>);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for Vec<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        T::visit_slice(self, visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        T::stream_slice(self, stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for Vec<T> where T: Visit {}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+fn visit_map<'v, K, V>(
+    len: usize,
+    entries: impl Iterator<Item = (&'v K, &'v V)>,
+    visitor: &mut dyn Visitor<'v>,
+) -> Result<(), Error>
+where
+    K: Visit + 'v,
+    V: Visit + 'v,
+{
+    visitor.map_begin(Some(len))?;
+
+    for (k, v) in entries {
+        visitor.map_key()?;
+        k.visit(visitor)?;
+
+        visitor.map_value()?;
+        v.visit(visitor)?;
+    }
+
+    visitor.map_end()
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+fn stream_map<'v, K, V, S>(
+    len: usize,
+    entries: impl Iterator<Item = (&'v K, &'v V)>,
+    stream: &mut S,
+) -> Result<(), S::Error>
+where
+    K: Visit + 'v,
+    V: Visit + 'v,
+    S: Stream<'v>,
+{
+    stream.map_begin(Some(len))?;
+
+    for (k, v) in entries {
+        stream.map_key()?;
+        k.stream(stream)?;
+
+        stream.map_value()?;
+        v.stream(stream)?;
+    }
+
+    stream.map_end()
+}
+
+#[cfg(feature = "std")]
+ensure_visit!(
+    std::collections::BTreeMap<String, u8>,
+    std::collections::HashMap<String, u8>
+);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<K, V> Visit for std::collections::BTreeMap<K, V>
+where
+    K: Visit,
+    V: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_map(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_map(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<K, V> imp::VisitPrivate for std::collections::BTreeMap<K, V>
+where
+    K: Visit,
+    V: Visit,
+{
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<K, V, H> Visit for std::collections::HashMap<K, V, H>
+where
+    K: Visit,
+    V: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_map(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_map(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<K, V, H> imp::VisitPrivate for std::collections::HashMap<K, V, H>
+where
+    K: Visit,
+    V: Visit,
+{
+}
+
+#[cfg(feature = "std")]
+ensure_visit!(
+    std::collections::BTreeSet<String>,
+    std::collections::HashSet<String>
+);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::collections::BTreeSet<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::collections::BTreeSet<T> where T: Visit {}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T, H> Visit for std::collections::HashSet<T, H>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T, H> imp::VisitPrivate for std::collections::HashSet<T, H> where T: Visit {}
+
+#[cfg(feature = "std")]
+ensure_visit!(
+    std::collections::VecDeque<u8>,
+    std::collections::LinkedList<u8>,
+    std::collections::BinaryHeap<u8>
+);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::collections::VecDeque<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::collections::VecDeque<T> where T: Visit {}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::collections::LinkedList<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::collections::LinkedList<T> where T: Visit {}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::collections::BinaryHeap<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visit_seq(self.len(), self.iter(), visitor)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream_seq(self.len(), self.iter(), stream)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::collections::BinaryHeap<T> where T: Visit {}
+
+#[cfg(feature = "std")]
+ensure_visit!(
+    std::borrow::Cow<'static, str>,
+    std::borrow::Cow<'static, [u8]>
+);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T> Visit for std::borrow::Cow<'a, T>
+where
+    T: ?Sized + std::borrow::ToOwned + Visit,
+    T::Owned: std::fmt::Debug,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        (**self).visit(visitor)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T> imp::VisitPrivate for std::borrow::Cow<'a, T>
+where
+    T: ?Sized + std::borrow::ToOwned + Visit,
+    T::Owned: std::fmt::Debug,
+{
+}
+
+#[cfg(feature = "std")]
+ensure_visit!(
+    std::boxed::Box<u8>,
+    std::boxed::Box<dyn Visit>,
+    std::rc::Rc<str>,
+    std::sync::Arc<str>,
+    std::sync::Arc<dyn Visit>
+);
+
+ensure_visit!(cell::Cell<u8>, cell::RefCell<u8>);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for cell::Cell<T>
+where
+    T: Copy + Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        self.get().visit(&mut forward::Unborrowed(visitor))
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for cell::Cell<T> where T: Copy + Visit {}
+
+/// A `RefCell` is borrowed while it's being visited.
+///
+/// If the cell is already mutably borrowed then it's visited
+/// using `Visitor::visit_debug` instead of failing.
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for cell::RefCell<T>
+where
+    T: ?Sized + Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self.try_borrow() {
+            Ok(v) => v.visit(&mut forward::Unborrowed(visitor)),
+            Err(_) => visitor.visit_debug(&format_args!("{:?}", self)),
+        }
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for cell::RefCell<T> where T: ?Sized + Visit {}
+
+#[cfg(feature = "std")]
+ensure_visit!(std::sync::Mutex<u8>, std::sync::RwLock<u8>);
+
+/// A `Mutex` is locked without blocking while it's being visited.
+///
+/// If the `Mutex` is already locked then it's visited as `<locked>`
+/// using `Visitor::visit_debug` instead of risking a deadlock.
+/// A poisoned `Mutex` is still visited.
+///
+/// With `serde_interop`, a `Mutex` is visited through its `Serialize`
+/// implementation instead, which blocks until it can be locked, and is
+/// visited using its `Debug` implementation if it's poisoned. Wrap it in
+/// `TryLock` to visit it without blocking in either configuration.
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::sync::Mutex<T>
+where
+    T: ?Sized + Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self.try_lock() {
+            Ok(v) => v.visit(&mut forward::Unborrowed(visitor)),
+            Err(std::sync::TryLockError::Poisoned(err)) => {
+                err.get_ref().visit(&mut forward::Unborrowed(visitor))
+            }
+            Err(std::sync::TryLockError::WouldBlock) => {
+                visitor.visit_debug(&format_args!("<locked>"))
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::sync::Mutex<T> where T: ?Sized + Visit {}
+
+/// A `RwLock` is locked without blocking while it's being visited.
+///
+/// If the `RwLock` is already write locked then it's visited as `<locked>`
+/// using `Visitor::visit_debug` instead of risking a deadlock.
+/// A poisoned `RwLock` is still visited.
+///
+/// With `serde_interop`, a `RwLock` is visited through its `Serialize`
+/// implementation instead, which blocks until it can be locked, and is
+/// visited using its `Debug` implementation if it's poisoned. Wrap it in
+/// `TryLock` to visit it without blocking in either configuration.
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::sync::RwLock<T>
+where
+    T: ?Sized + Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self.try_read() {
+            Ok(v) => v.visit(&mut forward::Unborrowed(visitor)),
+            Err(std::sync::TryLockError::Poisoned(err)) => {
+                err.get_ref().visit(&mut forward::Unborrowed(visitor))
+            }
+            Err(std::sync::TryLockError::WouldBlock) => {
+                visitor.visit_debug(&format_args!("<locked>"))
+            }
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::sync::RwLock<T> where T: ?Sized + Visit {}
+
+/// A `Mutex` or `RwLock` that's locked without blocking when it's visited.
+///
+/// Locks are already visited this way, except with `serde_interop`, where
+/// they're visited through their `Serialize` implementations, which block
+/// until they can be locked. If the lock is held then it's visited as
+/// `<locked>`, using `Visitor::visit_debug`, or as a string through `serde`.
+#[cfg(feature = "std")]
+pub struct TryLock<'a, L: ?Sized>(&'a L);
+
+#[cfg(feature = "std")]
+impl<'a, L: ?Sized> TryLock<'a, L> {
+    /// Wrap a lock so it's locked without blocking when visited.
+    pub fn new(lock: &'a L) -> Self {
+        TryLock(lock)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T> std::fmt::Debug for TryLock<'a, std::sync::Mutex<T>>
+where
+    T: ?Sized + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0.try_lock() {
+            Ok(v) => std::fmt::Debug::fmt(&*v, f),
+            Err(std::sync::TryLockError::Poisoned(err)) => {
+                std::fmt::Debug::fmt(&**err.get_ref(), f)
+            }
+            Err(std::sync::TryLockError::WouldBlock) => f.write_str("<locked>"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T> std::fmt::Debug for TryLock<'a, std::sync::RwLock<T>>
+where
+    T: ?Sized + std::fmt::Debug,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self.0.try_read() {
+            Ok(v) => std::fmt::Debug::fmt(&*v, f),
+            Err(std::sync::TryLockError::Poisoned(err)) => {
+                std::fmt::Debug::fmt(&**err.get_ref(), f)
+            }
+            Err(std::sync::TryLockError::WouldBlock) => f.write_str("<locked>"),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+ensure_visit!(
+    TryLock<'static, std::sync::Mutex<u8>>,
+    TryLock<'static, std::sync::RwLock<u8>>
+);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T> Visit for TryLock<'a, std::sync::Mutex<T>>
+where
+    T: ?Sized + Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        self.0.visit(visitor)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T> imp::VisitPrivate for TryLock<'a, std::sync::Mutex<T>> where T: ?Sized + Visit {}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T> Visit for TryLock<'a, std::sync::RwLock<T>>
+where
+    T: ?Sized + Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        self.0.visit(visitor)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T> imp::VisitPrivate for TryLock<'a, std::sync::RwLock<T>> where T: ?Sized + Visit {}
+
+#[cfg(feature = "std")]
+ensure_visit!(std::time::SystemTime);
+
+/// A `SystemTime` is visited as a unix timestamp using `Visitor::visit_timestamp`.
+///
+/// Times before the epoch have negative seconds, with nanoseconds counting
+/// forwards from there, so one nanosecond before the epoch is `(-1, 999_999_999)`.
+/// Times too far from the epoch to fit in an `i64` are visited using `Visitor::visit_debug`.
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl Visit for std::time::SystemTime {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        let timestamp = match self.duration_since(std::time::UNIX_EPOCH) {
+            Ok(since) => i64::try_from(since.as_secs()).map(|secs| (secs, since.subsec_nanos())),
+            Err(err) => {
+                let before = err.duration();
+
+                i64::try_from(before.as_secs()).map(|secs| match before.subsec_nanos() {
+                    0 => (-secs, 0),
+                    nanos => (-secs - 1, 1_000_000_000 - nanos),
+                })
+            }
+        };
+
+        match timestamp {
+            Ok((secs, nanos)) => visitor.visit_timestamp(secs, nanos),
+            Err(_) => visitor.visit_debug(self),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl imp::VisitPrivate for std::time::SystemTime {}
+
+ensure_visit!(std::fmt::Arguments<'static>);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<'a> Visit for std::fmt::Arguments<'a> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_fmt(self)
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream.visit_fmt(self)
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<'a> imp::VisitPrivate for std::fmt::Arguments<'a> {}
+
+ensure_visit!(
+    ops::Range<u8>,
+    ops::RangeInclusive<u8>,
+    ops::RangeFrom<u8>,
+    ops::RangeTo<u8>
+);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for ops::Range<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.record_begin("Range", Some(2))?;
+
+        visitor.record_field("start")?;
+        self.start.visit(visitor)?;
+
+        visitor.record_field("end")?;
+        self.end.visit(visitor)?;
+
+        visitor.record_end()
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for ops::Range<T> where T: Visit {}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for ops::RangeInclusive<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.record_begin("RangeInclusive", Some(2))?;
+
+        visitor.record_field("start")?;
+        self.start().visit(visitor)?;
+
+        visitor.record_field("end")?;
+        self.end().visit(visitor)?;
+
+        visitor.record_end()
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for ops::RangeInclusive<T> where T: Visit {}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for ops::RangeFrom<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.record_begin("RangeFrom", Some(1))?;
+
+        visitor.record_field("start")?;
+        self.start.visit(visitor)?;
+
+        visitor.record_end()
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for ops::RangeFrom<T> where T: Visit {}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for ops::RangeTo<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.record_begin("RangeTo", Some(1))?;
+
+        visitor.record_field("end")?;
+        self.end.visit(visitor)?;
+
+        visitor.record_end()
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for ops::RangeTo<T> where T: Visit {}
+
+ensure_visit!(Result<u8, &'static str>);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T, E> Visit for Result<T, E>
+where
+    T: Visit,
+    E: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self {
+            Ok(v) => {
+                visitor.variant_begin("Result", "Ok", 0)?;
+                v.visit(visitor)?;
+            }
+            Err(err) => {
+                visitor.variant_begin("Result", "Err", 1)?;
+                err.visit(visitor)?;
+            }
+        }
+
+        visitor.variant_end()
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T, E> imp::VisitPrivate for Result<T, E>
+where
+    T: Visit,
+    E: Visit,
+{
+}
+
+// `serde` doesn't implement `Serialize` for lazily initialized cells
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for cell::OnceCell<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self.get() {
+            Some(v) => {
+                visitor.visit_some()?;
+                v.visit(visitor)
+            }
+            None => visitor.visit_none(),
+        }
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for cell::OnceCell<T> where T: Visit {}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> Visit for std::sync::OnceLock<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self.get() {
+            Some(v) => {
+                visitor.visit_some()?;
+                v.visit(visitor)
+            }
+            None => visitor.visit_none(),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T> imp::VisitPrivate for std::sync::OnceLock<T> where T: Visit {}
+
+/// A `LazyLock` isn't initialized when it's visited.
+///
+/// If the value hasn't been initialized yet then it's visited as `None`.
+/// Use `ForceLazy` to initialize the value when it's visited.
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T, F> Visit for std::sync::LazyLock<T, F>
+where
+    T: Visit,
+    F: FnOnce() -> T,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match std::sync::LazyLock::get(self) {
+            Some(v) => {
+                visitor.visit_some()?;
+                v.visit(visitor)
+            }
+            None => visitor.visit_none(),
+        }
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<T, F> imp::VisitPrivate for std::sync::LazyLock<T, F>
+where
+    T: Visit,
+    F: FnOnce() -> T,
+{
+}
+
+ensure_visit!(Option<u8>);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for Option<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match self {
+            Some(v) => {
+                visitor.visit_some()?;
+                v.visit(visitor)
+            }
+            None => visitor.visit_none(),
+        }
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        match self {
+            Some(v) => {
+                stream.visit_some()?;
+                v.stream(stream)
+            }
+            None => stream.visit_none(),
+        }
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for Option<T> where T: Visit {}
+
+ensure_visit!(num::Wrapping<u8>, num::Saturating<u8>);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for num::Wrapping<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        self.0.visit(visitor)
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for num::Wrapping<T> where T: Visit {}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for num::Saturating<T>
+where
+    T: Visit,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        self.0.visit(visitor)
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for num::Saturating<T> where T: Visit {}
+
+ensure_visit!(marker::PhantomData<u8>);
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> Visit for marker::PhantomData<T>
+where
+    T: ?Sized,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_unit()
+    }
+
+    fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+    where
+        S: Stream<'v>,
+    {
+        stream.visit_unit()
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<T> imp::VisitPrivate for marker::PhantomData<T> where T: ?Sized {}
+
+/// An error that can be visited.
+///
+/// The error and its chain of sources are visited using `Visitor::visit_error`.
+#[cfg(feature = "std")]
+pub struct ErrorValue<'a>(&'a (dyn std::error::Error + 'static));
+
+#[cfg(feature = "std")]
+impl<'a> ErrorValue<'a> {
+    /// Wrap an error so it can be visited.
+    pub fn new(err: &'a (dyn std::error::Error + 'static)) -> Self {
+        ErrorValue(err)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> std::fmt::Debug for ErrorValue<'a> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(self.0, f)
+    }
+}
+
+#[cfg(feature = "std")]
+ensure_visit!(ErrorValue<'static>);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a> Visit for ErrorValue<'a> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        visitor.visit_error(self.0)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a> imp::VisitPrivate for ErrorValue<'a> {}
+
+/// A lazily initialized value that's forced when it's visited.
+///
+/// `LazyLock` values are visited as `None` until they've been initialized.
+/// Wrapping them in `ForceLazy` initializes them when they're visited instead.
+#[cfg(feature = "std")]
+pub struct ForceLazy<'a, T, F = fn() -> T>(&'a std::sync::LazyLock<T, F>);
+
+#[cfg(feature = "std")]
+impl<'a, T, F> ForceLazy<'a, T, F> {
+    /// Wrap a lazily initialized value so it's forced when visited.
+    pub fn new(lazy: &'a std::sync::LazyLock<T, F>) -> Self {
+        ForceLazy(lazy)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a, T, F> std::fmt::Debug for ForceLazy<'a, T, F>
+where
+    T: std::fmt::Debug,
+    F: FnOnce() -> T,
+{
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        std::fmt::Debug::fmt(std::sync::LazyLock::force(self.0), f)
+    }
+}
+
+#[cfg(feature = "std")]
+ensure_visit!(ForceLazy<'static, u8>);
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T, F> Visit for ForceLazy<'a, T, F>
+where
+    T: Visit,
+    F: FnOnce() -> T,
+{
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        std::sync::LazyLock::force(self.0).visit(visitor)
+    }
+}
+
+#[cfg(all(feature = "std", not(feature = "serde_interop")))]
+impl<'a, T, F> imp::VisitPrivate for ForceLazy<'a, T, F>
+where
+    T: Visit,
+    F: FnOnce() -> T,
+{
+}
+
+#[cfg(not(feature = "serde_interop"))]
+mod imp {
+    use super::*;
+
+    #[cfg(feature = "std")]
+    use self::std::{boxed::Box, rc::Rc, sync::Arc};
+
+    #[doc(hidden)]
+    pub trait VisitPrivate: std::fmt::Debug {}
+
+    impl<T: ?Sized> Visit for &T
+    where
+        T: Visit,
+    {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            (**self).visit(visitor)
+        }
+    }
+
+    impl<T: ?Sized> VisitPrivate for &T where T: Visit {}
+
+    #[cfg(feature = "std")]
+    impl<T: ?Sized> Visit for Box<T>
+    where
+        T: Visit,
+    {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            (**self).visit(visitor)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl<T: ?Sized> VisitPrivate for Box<T> where T: Visit {}
+
+    #[cfg(feature = "std")]
+    impl<T: ?Sized> Visit for Rc<T>
+    where
+        T: Visit,
+    {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            (**self).visit(visitor)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl<T: ?Sized> VisitPrivate for Rc<T> where T: Visit {}
+
+    #[cfg(feature = "std")]
+    impl<T: ?Sized> Visit for Arc<T>
+    where
+        T: Visit,
+    {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            (**self).visit(visitor)
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl<T: ?Sized> VisitPrivate for Arc<T> where T: Visit {}
+}
+
+#[cfg(feature = "serde_interop")]
+mod imp {
+    use super::*;
+
+    use serde::{Serialize, Serializer};
+
+    #[doc(hidden)]
+    pub trait VisitPrivate: erased_serde::Serialize + std::fmt::Debug {}
+
+    impl<T: ?Sized> Visit for T
+    where
+        T: Serialize + std::fmt::Debug,
+    {
+        fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            bridge(self, visitor)
+        }
+
+        fn stream<'v, S>(&'v self, stream: &mut S) -> Result<(), S::Error>
+        where
+            S: Stream<'v>,
+            Self: Sized,
+        {
+            let mut streamer = Streamer::new(stream);
+
+            let r = bridge(self, &mut streamer);
+            streamer.into_result(r)
+        }
+    }
+
+    /// Visit a value through its `Serialize` implementation.
+    fn bridge<'v, T, V>(value: &T, visitor: &mut V) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize + std::fmt::Debug,
+        V: ?Sized + Visitor<'v>,
+    {
+        let mut forwarded = false;
+
+        let bridge = SerdeBridge {
+            visitor: &mut *visitor,
+            forwarded: &mut forwarded,
+            _marker: marker::PhantomData,
+        };
+
+        match Serialize::serialize(value, bridge) {
+            Ok(()) => Ok(()),
+            Err(BridgeError::Visitor(err)) => Err(err),
+            Err(BridgeError::Unsupported) if !forwarded => {
+                visitor.visit_debug(&format_args!("{:?}", value))
+            }
+            Err(BridgeError::Unsupported) => {
+                Err(Error::msg("a value failed to serialize after it started"))
+            }
+        }
+    }
+
+    impl<T: ?Sized> VisitPrivate for T where T: Serialize + std::fmt::Debug {}
+
+    impl<'a> Serialize for dyn Visit + 'a {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            erased_serde::serialize(self, serializer)
+        }
+    }
+
+    impl<'a, T, F> Serialize for ForceLazy<'a, T, F>
+    where
+        T: Serialize,
+        F: FnOnce() -> T,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            std::sync::LazyLock::force(self.0).serialize(serializer)
+        }
+    }
+
+    impl<'a, T> Serialize for TryLock<'a, std::sync::Mutex<T>>
+    where
+        T: ?Sized + Serialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self.0.try_lock() {
+                Ok(v) => v.serialize(serializer),
+                Err(std::sync::TryLockError::Poisoned(err)) => err.get_ref().serialize(serializer),
+                Err(std::sync::TryLockError::WouldBlock) => serializer.serialize_str("<locked>"),
+            }
+        }
+    }
+
+    impl<'a, T> Serialize for TryLock<'a, std::sync::RwLock<T>>
+    where
+        T: ?Sized + Serialize,
+    {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self.0.try_read() {
+                Ok(v) => v.serialize(serializer),
+                Err(std::sync::TryLockError::Poisoned(err)) => err.get_ref().serialize(serializer),
+                Err(std::sync::TryLockError::WouldBlock) => serializer.serialize_str("<locked>"),
+            }
+        }
+    }
+
+    impl<'a> Serialize for ErrorValue<'a> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            use serde::ser::SerializeStruct;
+
+            let mut record = serializer.serialize_struct("Error", 2)?;
+            record.serialize_field("message", &format_args!("{}", self.0))?;
+            record.serialize_field("source", &self.0.source().map(ErrorValue))?;
+            record.end()
+        }
+    }
+
+    /// A serializer that forwards to a visitor.
+    ///
+    /// It tracks whether anything has been forwarded yet, so a value that
+    /// fails partway through isn't also visited through its `Debug` fallback.
+    ///
+    /// The visitor is generic so values that are streamed don't need to go
+    /// through a trait object for each part of them.
+    struct SerdeBridge<'a, 'v, V: ?Sized> {
+        visitor: &'a mut V,
+        forwarded: &'a mut bool,
+        _marker: marker::PhantomData<&'v ()>,
+    }
+
+    impl<'a, 'v, V> SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        fn forward(&mut self) -> &mut V {
+            *self.forwarded = true;
+            &mut *self.visitor
+        }
+
+        fn nested(&mut self) -> SerdeBridge<'_, 'v, V> {
+            SerdeBridge {
+                visitor: &mut *self.visitor,
+                forwarded: &mut *self.forwarded,
+                _marker: marker::PhantomData,
+            }
+        }
+    }
+
+    /// An error serializing a value through the bridge.
+    ///
+    /// Errors from the visitor are returned to the caller.
+    /// Any other errors mean the value is unsupported and fall back
+    /// to its `Debug` implementation, unless part of the value has already
+    /// been visited, in which case they're returned to the caller too.
+    #[derive(Debug)]
+    enum BridgeError {
+        Visitor(Error),
+        Unsupported,
+    }
+
+    impl From<Error> for BridgeError {
+        fn from(err: Error) -> Self {
+            BridgeError::Visitor(err)
+        }
+    }
+
+    impl serde::ser::Error for BridgeError {
+        fn custom<T>(_msg: T) -> Self
+        where
+            T: std::fmt::Display,
+        {
+            BridgeError::Unsupported
+        }
+    }
+
+    impl std::fmt::Display for BridgeError {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            match self {
+                BridgeError::Visitor(err) => write!(f, "{}", err),
+                BridgeError::Unsupported => write!(f, "unsupported value"),
+            }
+        }
+    }
+
+    #[cfg(feature = "std")]
+    impl std::error::Error for BridgeError {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match self {
+                BridgeError::Visitor(err) => Some(err),
+                BridgeError::Unsupported => None,
+            }
+        }
+    }
+
+    impl<'a, 'v, V> Serializer for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        type SerializeSeq = Self;
+        type SerializeTuple = Self;
+        type SerializeTupleStruct = Self;
+        type SerializeTupleVariant = Self;
+        type SerializeMap = Self;
+        type SerializeStruct = Self;
+        type SerializeStructVariant = Self;
+
+        fn is_human_readable(&self) -> bool {
+            self.visitor.is_human_readable()
+        }
+
+        fn serialize_bool(mut self, v: bool) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_bool(v)?)
+        }
+
+        fn serialize_i8(mut self, v: i8) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_i8(v)?)
+        }
+
+        fn serialize_i16(mut self, v: i16) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_i16(v)?)
+        }
+
+        fn serialize_i32(mut self, v: i32) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_i32(v)?)
+        }
+
+        fn serialize_i64(mut self, v: i64) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_i64(v)?)
+        }
+
+        fn serialize_i128(mut self, v: i128) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_i128(v)?)
+        }
+
+        fn serialize_u8(mut self, v: u8) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_u8(v)?)
+        }
+
+        fn serialize_u16(mut self, v: u16) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_u16(v)?)
+        }
+
+        fn serialize_u32(mut self, v: u32) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_u32(v)?)
+        }
+
+        fn serialize_u64(mut self, v: u64) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_u64(v)?)
+        }
+
+        fn serialize_u128(mut self, v: u128) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_u128(v)?)
+        }
+
+        fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+            self.serialize_f64(v as f64)
+        }
+
+        fn serialize_f64(mut self, v: f64) -> Result<Self::Ok, Self::Error> {
+            if v.is_finite() {
+                Ok(self.forward().visit_f64(v)?)
+            } else {
+                Ok(self.forward().visit_f64_nonfinite(v)?)
+            }
+        }
+
+        fn serialize_char(mut self, v: char) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_char(v)?)
+        }
+
+        fn serialize_str(mut self, v: &str) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_str(v)?)
+        }
+
+        fn collect_str<T: std::fmt::Display + ?Sized>(
+            mut self,
+            v: &T,
+        ) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_display(&format_args!("{}", v))?)
+        }
+
+        fn serialize_bytes(mut self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_bytes(v)?)
+        }
+
+        fn serialize_none(mut self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_none()?)
+        }
+
+        fn serialize_some<T>(mut self, v: &T) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.forward().visit_some()?;
+            v.serialize(self)
+        }
+
+        fn serialize_unit(mut self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_unit()?)
+        }
+
+        fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+            self.serialize_unit()
+        }
+
+        fn serialize_unit_variant(
+            mut self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+        ) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().visit_variant(name, variant, variant_index)?)
+        }
+
+        // The name of a newtype is its Rust type name, not a semantic type,
+        // so it isn't visited as a tag
+        fn serialize_newtype_struct<T>(
+            self,
+            _name: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            value.serialize(self)
+        }
+
+        fn serialize_newtype_variant<T>(
+            mut self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            value: &T,
+        ) -> Result<Self::Ok, Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.forward().variant_begin(name, variant, variant_index)?;
+            value.serialize(self.nested())?;
+            Ok(self.forward().variant_end()?)
+        }
+
+        fn serialize_seq(mut self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+            self.forward().seq_begin(len)?;
+            Ok(self)
+        }
+
+        fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+            self.serialize_seq(Some(len))
+        }
+
+        fn serialize_tuple_struct(
+            self,
+            _name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+            self.serialize_seq(Some(len))
+        }
+
+        fn serialize_tuple_variant(
+            mut self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+            self.forward().variant_begin(name, variant, variant_index)?;
+            self.serialize_seq(Some(len))
+        }
+
+        fn serialize_map(mut self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+            self.forward().map_begin(len)?;
+            Ok(self)
+        }
+
+        fn serialize_struct(
+            mut self,
+            name: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStruct, Self::Error> {
+            self.forward().record_begin(name, Some(len))?;
+            Ok(self)
+        }
+
+        fn serialize_struct_variant(
+            mut self,
+            name: &'static str,
+            variant_index: u32,
+            variant: &'static str,
+            len: usize,
+        ) -> Result<Self::SerializeStructVariant, Self::Error> {
+            self.forward().variant_begin(name, variant, variant_index)?;
+            self.serialize_struct(variant, len)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeMap for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_key<T>(&mut self, key: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.forward().map_key()?;
+            key.serialize(self.nested())
+        }
+
+        fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.forward().map_value()?;
+            value.serialize(self.nested())
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().map_end()?)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeStruct for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_field<T>(&mut self, name: &'static str, value: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.forward().record_field(name)?;
+            value.serialize(self.nested())
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().record_end()?)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeStructVariant for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_field<T>(&mut self, name: &'static str, value: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            serde::ser::SerializeStruct::serialize_field(self, name, value)
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            self.forward().record_end()?;
+            Ok(self.forward().variant_end()?)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeSeq for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_element<T>(&mut self, elem: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            self.forward().seq_elem()?;
+            elem.serialize(self.nested())
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.forward().seq_end()?)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeTuple for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_element<T>(&mut self, elem: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            serde::ser::SerializeSeq::serialize_element(self, elem)
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            serde::ser::SerializeSeq::end(self)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeTupleVariant for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_field<T>(&mut self, field: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            serde::ser::SerializeSeq::serialize_element(self, field)
+        }
+
+        fn end(mut self) -> Result<Self::Ok, Self::Error> {
+            self.forward().seq_end()?;
+            Ok(self.forward().variant_end()?)
+        }
+    }
+
+    impl<'a, 'v, V> serde::ser::SerializeTupleStruct for SerdeBridge<'a, 'v, V>
+    where
+        V: ?Sized + Visitor<'v>,
+    {
+        type Ok = ();
+        type Error = BridgeError;
+
+        fn serialize_field<T>(&mut self, field: &T) -> Result<(), Self::Error>
+        where
+            T: ?Sized + Serialize,
+        {
+            serde::ser::SerializeSeq::serialize_element(self, field)
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            serde::ser::SerializeSeq::end(self)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::*;
+
+    #[derive(PartialEq, Debug)]
+    pub(crate) enum Token<'a> {
+        I64(i64),
+        U64(u64),
+        I128(i128),
+        U128(u128),
+        F64(f64),
+        Bool(bool),
+        Char(char),
+        Str(&'a str),
+        Bytes(&'a [u8]),
+        NumberStr(&'a str),
+        Args(&'a str),
+        Display(&'a str),
+        Debug(&'a str),
+        Duration(time::Duration),
+        Timestamp(i64, u32),
+        None,
+        Some,
+        Unit,
+        MapBegin(Option<usize>),
+        MapKey,
+        MapValue,
+        MapEnd,
+        SeqBegin(Option<usize>),
+        SeqElem,
+        SeqEnd,
+        RecordBegin(&'a str, Option<usize>),
+        RecordField(&'a str),
+        RecordEnd,
+        Variant(&'a str, &'a str, u32),
+        VariantBegin(&'a str, &'a str, u32),
+        VariantEnd,
+        Tag(&'a str),
+    }
+
+    const LEN: usize = 128;
+
+    pub(crate) struct VisitArgs {
+        buf: [u8; LEN],
+        cursor: usize,
+    }
+
+    impl VisitArgs {
+        pub(crate) fn new() -> Self {
+            VisitArgs {
+                buf: [0; LEN],
+                cursor: 0,
+            }
+        }
+
+        pub(crate) fn to_str(&self) -> Option<&str> {
+            self::std::str::from_utf8(&self.buf[0..self.cursor]).ok()
+        }
+    }
+
+    impl std::fmt::Write for VisitArgs {
+        fn write_str(&mut self, s: &str) -> std::fmt::Result {
+            let src = s.as_bytes();
+            let next_cursor = self.cursor + src.len();
+
+            if next_cursor > LEN {
+                return Err(std::fmt::Error);
+            }
+
+            unsafe {
+                let src_ptr = src.as_ptr();
+                let dst_ptr = self.buf.as_mut_ptr().add(self.cursor);
+
+                self::std::ptr::copy_nonoverlapping(src_ptr, dst_ptr, src.len());
+            }
+
+            self.cursor = next_cursor;
+
+            Ok(())
+        }
+    }
+
+    impl<'v> Visitor<'v> for VisitArgs {
+        fn visit_fmt(&mut self, v: &std::fmt::Arguments) -> Result<(), Error> {
+            Ok(std::fmt::Write::write_fmt(self, *v)?)
+        }
+    }
+
+    /// A value that formats to a longer string each time.
+    ///
+    /// It's used to test writers that format values twice,
+    /// once to get their length, and again to write them.
+    #[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+    pub(crate) struct Growing(pub(crate) cell::Cell<usize>);
+
+    #[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+    impl std::fmt::Display for Growing {
+        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+            self.0.set(self.0.get() + 1);
+            write!(f, "{:>1$}", "a", self.0.get())
+        }
+    }
+
+    // `&dyn ser::Serialize` should impl `Serialize`
+    pub(crate) fn assert_visit(v: &dyn Visit, tokens: &[Token]) {
+        use self::std::fmt::Write;
+
+        struct TestVisitor<'a, 'b>(&'b [Token<'a>]);
+
+        impl<'a, 'b> TestVisitor<'a, 'b> {
+            fn expect(&mut self, token: Token) -> Result<(), Error> {
+                let (expected, rest) = self
+                    .0
+                    .split_first()
+                    .unwrap_or_else(|| panic!("unexpected {:?}", token));
+                assert_eq!(*expected, token);
+
+                self.0 = rest;
+
+                Ok(())
+            }
+        }
+
+        impl<'a, 'b, 'v> Visitor<'v> for TestVisitor<'a, 'b> {
+            fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+                self.expect(Token::I64(v))
+            }
+
+            fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+                self.expect(Token::U64(v))
+            }
+
+            fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+                self.expect(Token::I128(v))
+            }
+
+            fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+                self.expect(Token::U128(v))
+            }
+
+            fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+                self.expect(Token::F64(v))
+            }
+
+            fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+                self.expect(Token::NumberStr(v))
+            }
+
+            fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+                self.expect(Token::Bool(v))
+            }
+
+            fn visit_char(&mut self, v: char) -> Result<(), Error> {
+                self.expect(Token::Char(v))
+            }
+
+            fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+                self.expect(Token::Str(v))
+            }
+
+            fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+                self.expect(Token::Bytes(v))
+            }
+
+            fn visit_none(&mut self) -> Result<(), Error> {
+                self.expect(Token::None)
+            }
+
+            fn visit_some(&mut self) -> Result<(), Error> {
+                self.expect(Token::Some)
+            }
+
+            fn visit_unit(&mut self) -> Result<(), Error> {
+                self.expect(Token::Unit)
+            }
+
+            fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+                self.expect(Token::MapBegin(len))
+            }
+
+            fn map_key(&mut self) -> Result<(), Error> {
+                self.expect(Token::MapKey)
+            }
+
+            fn map_value(&mut self) -> Result<(), Error> {
+                self.expect(Token::MapValue)
+            }
+
+            fn map_end(&mut self) -> Result<(), Error> {
+                self.expect(Token::MapEnd)
+            }
+
+            fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+                self.expect(Token::SeqBegin(len))
+            }
+
+            fn seq_elem(&mut self) -> Result<(), Error> {
+                self.expect(Token::SeqElem)
+            }
+
+            fn seq_end(&mut self) -> Result<(), Error> {
+                self.expect(Token::SeqEnd)
+            }
+
+            fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+                self.expect(Token::RecordBegin(name, len))
+            }
+
+            fn record_field(&mut self, name: &str) -> Result<(), Error> {
+                self.expect(Token::RecordField(name))
+            }
+
+            fn record_end(&mut self) -> Result<(), Error> {
+                self.expect(Token::RecordEnd)
+            }
+
+            fn visit_variant(
+                &mut self,
+                name: &str,
+                variant: &str,
+                index: u32,
+            ) -> Result<(), Error> {
+                self.expect(Token::Variant(name, variant, index))
+            }
+
+            fn variant_begin(
+                &mut self,
+                name: &str,
+                variant: &str,
+                index: u32,
+            ) -> Result<(), Error> {
+                self.expect(Token::VariantBegin(name, variant, index))
+            }
+
+            fn variant_end(&mut self) -> Result<(), Error> {
+                self.expect(Token::VariantEnd)
+            }
+
+            fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+                self.expect(Token::Tag(tag))
+            }
+
+            fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+                self.expect(Token::Duration(v))
+            }
+
+            fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+                self.expect(Token::Timestamp(secs, nanos))
+            }
+
+            fn visit_display(&mut self, v: &dyn std::fmt::Display) -> Result<(), Error> {
+                let mut w = VisitArgs::new();
+                w.write_fmt(format_args!("{}", v)).unwrap();
+                self.expect(Token::Display(w.to_str().unwrap()))
+            }
+
+            fn visit_debug(&mut self, v: &dyn std::fmt::Debug) -> Result<(), Error> {
+                let mut w = VisitArgs::new();
+                w.write_fmt(format_args!("{:?}", v)).unwrap();
+                self.expect(Token::Debug(w.to_str().unwrap()))
+            }
+
+            fn visit_fmt(&mut self, v: &std::fmt::Arguments) -> Result<(), Error> {
+                let mut w = VisitArgs::new();
+                w.write_fmt(format_args!("{}", v)).unwrap();
+                self.expect(Token::Args(w.to_str().unwrap()))
+            }
+        }
+
+        let mut visitor = TestVisitor(tokens);
+        v.visit(&mut visitor).unwrap();
+
+        assert!(visitor.0.is_empty(), "missing {:?}", visitor.0);
+    }
+
+    #[test]
+    fn visit_simple() {
+        assert_visit(&1u8, &[Token::U64(1u64)]);
+        assert_visit(&true, &[Token::Bool(true)]);
+        assert_visit(&"a string", &[Token::Str("a string")]);
+    }
+
+    #[test]
+    fn visit_128bit() {
+        assert_visit(&u128::MAX, &[Token::U128(u128::MAX)]);
+        assert_visit(&i128::MIN, &[Token::I128(i128::MIN)]);
+
+        let mut w = VisitArgs::new();
+        u128::MAX.visit(&mut w).unwrap();
+
+        assert_eq!(Some("340282366920938463463374607431768211455"), w.to_str());
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_duration() {
+        let v = time::Duration::from_millis(12500);
+
+        assert_visit(&v, &[Token::Duration(v)]);
+        assert_visit(&Some(v), &[Token::Some, Token::Duration(v)]);
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", not(feature = "serde_interop")))]
+    fn visit_system_time() {
+        use self::std::time::{Duration, UNIX_EPOCH};
+
+        assert_visit(
+            &(UNIX_EPOCH + Duration::new(1, 5)),
+            &[Token::Timestamp(1, 5)],
+        );
+        assert_visit(
+            &(UNIX_EPOCH - Duration::new(1, 0)),
+            &[Token::Timestamp(-1, 0)],
+        );
+        assert_visit(
+            &(UNIX_EPOCH - Duration::new(0, 1)),
+            &[Token::Timestamp(-1, 999_999_999)],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_duration_as_record() {
+        let v = time::Duration::from_millis(12500);
+
+        assert_visit(
+            &v,
+            &[
+                Token::RecordBegin("Duration", Some(2)),
+                Token::RecordField("secs"),
+                Token::U64(12),
+                Token::RecordField("nanos"),
+                Token::U64(500_000_000),
+                Token::RecordEnd,
+            ],
+        );
+    }
+
+    #[test]
+    fn visit_str_fragments_as_debug() {
+        let mut w = VisitArgs::new();
+
+        w.str_begin(None).unwrap();
+        w.visit_str_fragment("a \"quoted\"").unwrap();
+        w.visit_str_fragment(" string").unwrap();
+        w.str_end().unwrap();
+
+        assert_eq!(Some("\"a \\\"quoted\\\" string\""), w.to_str());
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_borrowed_str() {
+        struct Borrowed<'v>(Option<&'v str>);
+
+        impl<'v> Visitor<'v> for Borrowed<'v> {
+            fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+                self.0 = Some(v);
+
+                Ok(())
+            }
+
+            fn visit_fmt(&mut self, _: &std::fmt::Arguments) -> Result<(), Error> {
+                panic!("expected a borrowed string");
+            }
+        }
+
+        let mut visitor = Borrowed(None);
+        "a string".visit(&mut visitor).unwrap();
+
+        assert_eq!(Some("a string"), visitor.0);
+    }
+
+    #[test]
+    fn visitor_error_short_circuits() {
+        struct Fail;
+
+        impl<'v> Visitor<'v> for Fail {
+            fn visit_some(&mut self) -> Result<(), Error> {
+                Ok(())
+            }
+
+            fn visit_fmt(&mut self, _: &std::fmt::Arguments) -> Result<(), Error> {
+                Err(Error::msg("failed"))
+            }
+        }
+
+        assert!(Some(1u8).visit(&mut Fail).is_err());
+    }
+
+    #[test]
+    fn visit_fallback() {
+        struct Reject;
+
+        impl<'v> Visitor<'v> for Reject {
+            fn visit_fallback(&mut self, _: &dyn std::fmt::Debug) -> Result<(), Error> {
+                Err(Error::msg("unsupported"))
+            }
+
+            fn visit_fmt(&mut self, _: &std::fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        assert!(1u8.visit(&mut Reject).is_err());
+        assert!("a string".visit(&mut Reject).is_err());
+    }
+
+    #[test]
+    fn visit_width_preserving() {
+        struct Width(Option<&'static str>);
+
+        impl<'v> Visitor<'v> for Width {
+            fn visit_u8(&mut self, _: u8) -> Result<(), Error> {
+                self.0 = Some("u8");
+                Ok(())
+            }
+
+            fn visit_i16(&mut self, _: i16) -> Result<(), Error> {
+                self.0 = Some("i16");
+                Ok(())
+            }
+
+            fn visit_u64(&mut self, _: u64) -> Result<(), Error> {
+                self.0 = Some("u64");
+                Ok(())
+            }
+
+            fn visit_fmt(&mut self, _: &std::fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        for (v, expected) in &[(&1u8 as &dyn Visit, "u8"), (&1i16, "i16"), (&1u32, "u64")] {
+            let mut visitor = Width(None);
+            v.visit(&mut visitor).unwrap();
+
+            assert_eq!(Some(*expected), visitor.0);
+        }
+    }
+
+    #[test]
+    fn visit_number_str_as_debug() {
+        let mut w = VisitArgs::new();
+
+        w.visit_number_str("1.0000000000000000001").unwrap();
+
+        assert_eq!(Some("1.0000000000000000001"), w.to_str());
+    }
+
+    #[test]
+    fn visit_nonfinite() {
+        struct Policy(NonFinite);
+
+        impl<'v> Visitor<'v> for Policy {
+            fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+                self.0.apply(v, self)
+            }
+
+            fn visit_fmt(&mut self, _: &std::fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        assert_visit(&1.5f64, &[Token::F64(1.5)]);
+
+        assert!(f64::NAN.visit(&mut Policy(NonFinite::Error)).is_err());
+        assert!(1.5f64.visit(&mut Policy(NonFinite::Error)).is_ok());
+
+        let mut w = VisitArgs::new();
+        NonFinite::Str.apply(f64::NEG_INFINITY, &mut w).unwrap();
+        assert_eq!(Some("\"-Infinity\""), w.to_str());
+
+        let mut w = VisitArgs::new();
+        NonFinite::None.apply(f32::INFINITY as f64, &mut w).unwrap();
+        assert_eq!(Some("None"), w.to_str());
+    }
+
+    #[test]
+    fn visitor_supports() {
+        struct Tags;
+
+        impl<'v> Visitor<'v> for Tags {
+            fn supports(&self, capability: Capability) -> bool {
+                capability == Capability::Tag
+            }
+
+            fn visit_fmt(&mut self, _: &std::fmt::Arguments) -> Result<(), Error> {
+                Ok(())
+            }
+        }
+
+        assert!(!VisitArgs::new().supports(Capability::Tag));
+
+        assert!(Tags.supports(Capability::Tag));
+        assert!(!Tags.supports(Capability::StrFragments));
+    }
+
+    #[test]
+    fn visit_bytes_hint_as_bytes() {
+        let mut w = VisitArgs::new();
+
+        w.visit_bytes_hint(&[1, 2], BytesHint::Hex).unwrap();
+
+        assert_eq!(Some("[1, 2]"), w.to_str());
+    }
+
+    #[test]
+    fn visit_with_forwarded_visitor() {
+        let mut w = VisitArgs::new();
+
+        {
+            let mut visitor = &mut w;
+            1u8.visit(&mut visitor).unwrap();
+        }
+
+        assert_eq!(Some("1"), w.to_str());
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_with_boxed_visitor() {
+        use self::std::boxed::Box;
+
+        let mut visitor: Box<dyn Visitor> = Box::new(VisitArgs::new());
+        1u8.visit(&mut visitor).unwrap();
+    }
+
+    #[test]
+    fn visit_option() {
+        assert_visit(&Some(1u8), &[Token::Some, Token::U64(1u64)]);
+        assert_visit(&None::<u8>, &[Token::None]);
+
+        assert_visit(&Some("a"), &[Token::Some, Token::Str("a")]);
+        assert_visit(&Some(None::<u8>), &[Token::Some, Token::None]);
+        assert_visit(
+            &Some(Some(true)),
+            &[Token::Some, Token::Some, Token::Bool(true)],
+        );
+    }
+
+    #[test]
+    fn visit_via_display() {
+        #[derive(Debug)]
+        struct Id(u64);
+
+        impl std::fmt::Display for Id {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "id-{}", self.0)
+            }
+        }
+
+        impl_visit_via_display!(Id);
+
+        assert_visit(&Id(1), &[Token::Display("id-1")]);
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_args() {
+        assert_visit(&format_args!("a {}", 1), &[Token::Args("a 1")]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_display() {
+        assert_visit(&format_args!("a {}", 1), &[Token::Display("a 1")]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_error() {
+        use self::std::error::Error;
+
+        #[derive(Debug)]
+        struct Outer(Inner);
+
+        #[derive(Debug)]
+        struct Inner;
+
+        impl std::fmt::Display for Outer {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "outer")
+            }
+        }
+
+        impl std::fmt::Display for Inner {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "inner")
+            }
+        }
+
+        impl Error for Outer {
+            fn source(&self) -> Option<&(dyn Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        impl Error for Inner {}
+
+        assert_visit(
+            &ErrorValue::new(&Outer(Inner)),
+            &[
+                Token::RecordBegin("Error", Some(2)),
+                Token::RecordField("message"),
+                Token::Display("outer"),
+                Token::RecordField("source"),
+                Token::Some,
+                Token::RecordBegin("Error", Some(2)),
+                Token::RecordField("message"),
+                Token::Display("inner"),
+                Token::RecordField("source"),
+                Token::None,
+                Token::RecordEnd,
+                Token::RecordEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_unsupported_as_debug() {
+        #[derive(Debug)]
+        struct Unsupported;
+
+        impl serde::Serialize for Unsupported {
+            fn serialize<S: serde::Serializer>(&self, _: S) -> Result<S::Ok, S::Error> {
+                Err(serde::ser::Error::custom("unsupported"))
+            }
+        }
+
+        assert_visit(&Unsupported, &[Token::Debug("Unsupported")]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_unsupported_after_forwarding() {
+        use serde::ser::SerializeSeq;
+
+        #[derive(Debug)]
+        struct Partial;
+
+        impl serde::Serialize for Partial {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut seq = serializer.serialize_seq(Some(2))?;
+                seq.serialize_element(&1)?;
+
+                Err(serde::ser::Error::custom("unsupported"))
+            }
+        }
+
+        let mut json = crate::json::Writer::new(String::new());
+        assert!(Partial.visit(&mut json).is_err());
+
+        // The elements already visited aren't followed by the `Debug` fallback
+        assert_eq!("[1", json.into_inner());
+    }
+
+    #[test]
+    fn visit_slice() {
+        assert_visit(
+            &&[1u32, 2][..],
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::U64(2),
+                Token::SeqEnd,
+            ],
+        );
+
+        assert_visit(
+            &&["a"][..],
+            &[
+                Token::SeqBegin(Some(1)),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_byte_slice() {
+        assert_visit(&&[1u8, 2][..], &[Token::Bytes(&[1, 2])]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_vec() {
+        assert_visit(
+            &vec![1u64, 2],
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::U64(2),
+                Token::SeqEnd,
+            ],
+        );
+
+        assert_visit(
+            &vec!["a".to_string()],
+            &[
+                Token::SeqBegin(Some(1)),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", not(feature = "serde_interop")))]
+    fn visit_byte_vec() {
+        assert_visit(&vec![1u8, 2], &[Token::Bytes(&[1, 2])]);
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_std_map() {
+        use self::std::collections::{BTreeMap, HashMap};
+
+        let mut map = BTreeMap::new();
+        map.insert("a", 1u64);
+        map.insert("b", 2u64);
+
+        assert_visit(
+            &map,
+            &[
+                Token::MapBegin(Some(2)),
+                Token::MapKey,
+                Token::Str("a"),
+                Token::MapValue,
+                Token::U64(1),
+                Token::MapKey,
+                Token::Str("b"),
+                Token::MapValue,
+                Token::U64(2),
+                Token::MapEnd,
+            ],
+        );
+
+        let mut map = HashMap::new();
+        map.insert(1u64, true);
+
+        assert_visit(
+            &map,
+            &[
+                Token::MapBegin(Some(1)),
+                Token::MapKey,
+                Token::U64(1),
+                Token::MapValue,
+                Token::Bool(true),
+                Token::MapEnd,
+            ],
+        );
+    }
 
-#[cfg(feature = "std")]
-ensure_impl_visit! {
-    String {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_str(&*self)
-        }
-    }
-    Vec<u8> {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            visitor.visit_bytes(&*self)
-        }
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_std_set() {
+        use self::std::collections::{BTreeSet, HashSet};
+
+        let set: BTreeSet<_> = vec!["b", "a", "b"].into_iter().collect();
+
+        assert_visit(
+            &set,
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqElem,
+                Token::Str("b"),
+                Token::SeqEnd,
+            ],
+        );
+
+        let set: HashSet<_> = vec![1u64, 1].into_iter().collect();
+
+        assert_visit(
+            &set,
+            &[
+                Token::SeqBegin(Some(1)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqEnd,
+            ],
+        );
     }
-}
 
-#[cfg(not(feature = "serde_interop"))]
-mod imp {
-    use super::*;
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_std_seq() {
+        use self::std::collections::{BinaryHeap, LinkedList, VecDeque};
+
+        let tokens = [
+            Token::SeqBegin(Some(2)),
+            Token::SeqElem,
+            Token::U64(2),
+            Token::SeqElem,
+            Token::U64(1),
+            Token::SeqEnd,
+        ];
+
+        assert_visit(&vec![2u8, 1].into_iter().collect::<VecDeque<_>>(), &tokens);
+        assert_visit(
+            &vec![2u8, 1].into_iter().collect::<LinkedList<_>>(),
+            &tokens,
+        );
+        assert_visit(
+            &vec![1u8, 2].into_iter().collect::<BinaryHeap<_>>(),
+            &tokens,
+        );
+    }
 
-    #[doc(hidden)]
-    pub trait VisitPrivate: fmt::Debug {}
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_cow() {
+        use self::std::borrow::Cow;
 
-    impl<'a, T: ?Sized> Visit for &'a T
-    where
-        T: Visit,
-    {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            (**self).visit(visitor)
-        }
+        assert_visit(&Cow::Borrowed("a"), &[Token::Str("a")]);
+        assert_visit(&Cow::<str>::Owned("a".into()), &[Token::Str("a")]);
     }
 
-    impl<'a, T: ?Sized> VisitPrivate for &'a T
-    where
-        T: Visit,
-    {
+    #[test]
+    #[cfg(all(feature = "std", not(feature = "serde_interop")))]
+    fn visit_cow_bytes() {
+        use self::std::borrow::Cow;
+
+        assert_visit(&Cow::Borrowed(&[1u8][..]), &[Token::Bytes(&[1])]);
     }
-}
 
-#[cfg(feature = "serde_interop")]
-mod imp {
-    use super::*;
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_smart_pointer() {
+        use self::std::{boxed::Box, rc::Rc, sync::Arc};
 
-    use serde::{Serializer, Serialize};
+        assert_visit(&Box::new(1u8), &[Token::U64(1)]);
+        assert_visit(&Rc::<str>::from("a"), &[Token::Str("a")]);
+        assert_visit(&Arc::<str>::from("a"), &[Token::Str("a")]);
 
-    #[doc(hidden)]
-    pub trait VisitPrivate: erased_serde::Serialize + fmt::Debug {}
- 
-    impl<T: ?Sized> Visit for T
-    where
-        T: Serialize + fmt::Debug,
-    {
-        fn visit(&self, visitor: &mut dyn Visitor) {
-            if let Err(Unsupported) = Serialize::serialize(self, SerdeBridge(visitor)) {
-                visitor.visit_fmt(&format_args!("{:?}", self));
-            }
-        }
+        let shared: Arc<dyn Visit> = Arc::new(true);
+        assert_visit(&shared, &[Token::Bool(true)]);
     }
 
-    impl<T: ?Sized> VisitPrivate for T
-    where
-        T: Serialize + fmt::Debug,
-    {
-    }
+    #[test]
+    fn visit_cell() {
+        use self::std::cell::{Cell, RefCell};
 
-    impl<'a> Serialize for dyn Visit + 'a {
-        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-        where
-            S: Serializer,
-        {
-            erased_serde::serialize(self, serializer)
-        }
+        assert_visit(&Cell::new(1u8), &[Token::U64(1)]);
+        assert_visit(&RefCell::new("a"), &[Token::Str("a")]);
     }
 
-    struct SerdeBridge<'a>(&'a mut dyn Visitor);
+    #[test]
+    fn visit_mutably_borrowed_cell() {
+        use self::std::cell::RefCell;
 
-    #[derive(Debug)]
-    struct Unsupported;
+        let cell = RefCell::new(1u8);
+        let _guard = cell.borrow_mut();
 
-    impl serde::ser::Error for Unsupported {
-        fn custom<T>(_msg: T) -> Self
-        where
-            T: std::fmt::Display
-        {
-            Unsupported
-        }
+        assert_visit(&cell, &[Token::Debug("RefCell { value: <borrowed> }")]);
     }
 
-    impl std::fmt::Display for Unsupported {
-        fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-            write!(f, "unsupported value")
-        }
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_lock() {
+        use self::std::sync::{Mutex, RwLock};
+
+        assert_visit(&Mutex::new(1u8), &[Token::U64(1)]);
+        assert_visit(&RwLock::new("a"), &[Token::Str("a")]);
+
+        assert_visit(&TryLock::new(&Mutex::new(1u8)), &[Token::U64(1)]);
+        assert_visit(&TryLock::new(&RwLock::new("a")), &[Token::Str("a")]);
     }
 
+    #[test]
     #[cfg(feature = "std")]
-    impl std::error::Error for Unsupported {
-        fn cause(&self) -> Option<&dyn std::error::Error> {
-            None
+    fn visit_held_lock() {
+        use self::std::sync::{Mutex, RwLock};
+
+        // Through `serde_interop`, held locks are serialized as strings
+        fn locked() -> Token<'static> {
+            if cfg!(feature = "serde_interop") {
+                Token::Str("<locked>")
+            } else {
+                Token::Debug("<locked>")
+            }
         }
 
-        fn description(&self) -> &str {
-            "unsupported value"
-        }
+        let mutex = Mutex::new(1u8);
+        let _guard = mutex.lock().unwrap();
+
+        assert_visit(&TryLock::new(&mutex), &[locked()]);
+        #[cfg(not(feature = "serde_interop"))]
+        assert_visit(&mutex, &[Token::Debug("<locked>")]);
+
+        let rwlock = RwLock::new(1u8);
+        let _guard = rwlock.write().unwrap();
+
+        assert_visit(&TryLock::new(&rwlock), &[locked()]);
+        #[cfg(not(feature = "serde_interop"))]
+        assert_visit(&rwlock, &[Token::Debug("<locked>")]);
     }
 
-    impl<'a> Serializer for SerdeBridge<'a> {
-        type Ok = ();
-        type Error = Unsupported;
+    #[test]
+    fn visit_size() {
+        assert_visit(&1usize, &[Token::U64(1)]);
+        assert_visit(&-1isize, &[Token::I64(-1)]);
+    }
 
-        type SerializeSeq = serde::ser::Impossible<Self::Ok, Self::Error>;
-        type SerializeTuple = serde::ser::Impossible<Self::Ok, Self::Error>;
-        type SerializeTupleStruct = serde::ser::Impossible<Self::Ok, Self::Error>;
-        type SerializeTupleVariant = serde::ser::Impossible<Self::Ok, Self::Error>;
-        type SerializeMap = serde::ser::Impossible<Self::Ok, Self::Error>;
-        type SerializeStruct = serde::ser::Impossible<Self::Ok, Self::Error>;
-        type SerializeStructVariant = serde::ser::Impossible<Self::Ok, Self::Error>;
+    #[test]
+    fn visit_nonzero() {
+        use self::std::num::{NonZeroI8, NonZeroU128, NonZeroU64};
 
-        fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_bool(v))
-        }
+        assert_visit(&NonZeroU64::new(1).unwrap(), &[Token::U64(1)]);
+        assert_visit(&NonZeroI8::new(-1).unwrap(), &[Token::I64(-1)]);
+        assert_visit(&NonZeroU128::new(1).unwrap(), &[Token::U128(1)]);
+    }
 
-        fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i64(v as i64)
-        }
+    #[test]
+    fn visit_num_wrapper() {
+        use self::std::num::{Saturating, Wrapping};
 
-        fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i64(v as i64)
-        }
+        assert_visit(&Wrapping(1u8), &[Token::U64(1)]);
+        assert_visit(&Saturating(-1i32), &[Token::I64(-1)]);
+    }
 
-        fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-            self.serialize_i64(v as i64)
-        }
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_net() {
+        use self::std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
-        fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_i64(v))
-        }
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
-        fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u64(v as u64)
-        }
+        assert_visit(&ip, &[Token::Display("127.0.0.1")]);
+        assert_visit(&SocketAddr::new(ip, 80), &[Token::Display("127.0.0.1:80")]);
+    }
 
-        fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u64(v as u64)
-        }
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_net_as_bytes() {
+        use self::std::net::{IpAddr, Ipv4Addr, SocketAddr};
 
-        fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-            self.serialize_u64(v as u64)
-        }
+        struct Compact(VisitArgs);
 
-        fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_u64(v))
-        }
+        impl<'v> Visitor<'v> for Compact {
+            fn is_human_readable(&self) -> bool {
+                false
+            }
 
-        fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
-            self.serialize_f64(v as f64)
+            fn visit_fmt(&mut self, v: &std::fmt::Arguments) -> Result<(), Error> {
+                self.0.visit_fmt(v)
+            }
         }
 
-        fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_f64(v))
-        }
+        let ip = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
 
-        fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_char(v))
-        }
+        let mut w = Compact(VisitArgs::new());
+        ip.visit(&mut w).unwrap();
+        assert_eq!(Some("[127, 0, 0, 1]"), w.0.to_str());
 
-        fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_str(v))
-        }
+        let mut w = Compact(VisitArgs::new());
+        SocketAddr::new(ip, 80).visit(&mut w).unwrap();
+        assert_eq!(Some("[127, 0, 0, 1]80"), w.0.to_str());
+    }
 
-        fn collect_str<T: std::fmt::Display + ?Sized>(self, v: &T) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_fmt(&format_args!("{}", v)))
-        }
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_c_str() {
+        use self::std::ffi::CStr;
 
-        fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-            Ok(self.0.visit_bytes(v))
-        }
+        let v = CStr::from_bytes_with_nul(b"a\0").unwrap();
+        assert_visit(&v, &[Token::Str("a")]);
 
-        fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-            Err(Unsupported)
-        }
+        let v = CStr::from_bytes_with_nul(b"\xff\0").unwrap();
+        assert_visit(&v, &[Token::Bytes(b"\xff")]);
+    }
 
-        fn serialize_some<T>(self, v: &T) -> Result<Self::Ok, Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            v.serialize(self)
-        }
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_path() {
+        use self::std::path::{Path, PathBuf};
 
-        fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
-            Err(Unsupported)
-        }
+        assert_visit(&Path::new("/a/b"), &[Token::Str("/a/b")]);
+        assert_visit(&PathBuf::from("/a/b"), &[Token::Str("/a/b")]);
+    }
 
-        fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-            Err(Unsupported)
-        }
+    #[test]
+    #[cfg(all(unix, feature = "std", not(feature = "serde_interop")))]
+    fn visit_non_utf8_path() {
+        use self::std::{ffi::OsStr, os::unix::ffi::OsStrExt, path::Path};
 
-        fn serialize_unit_variant(
-            self,
-            _name: &'static str,
-            _variant_index: u32,
-            _variant: &'static str,
-        ) -> Result<Self::Ok, Self::Error> {
-            Err(Unsupported)
-        }
+        let path = Path::new(OsStr::from_bytes(b"/a/\xff"));
 
-        fn serialize_newtype_struct<T>(
-            self,
-            _name: &'static str,
-            _value: &T,
-        ) -> Result<Self::Ok, Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            Err(Unsupported)
-        }
+        assert_visit(&path, &[Token::Str("/a/\u{fffd}")]);
+    }
 
-        fn serialize_newtype_variant<T>(
-            self,
-            _name: &'static str,
-            _variant_index: u32,
-            _variant: &'static str,
-            _value: &T,
-        ) -> Result<Self::Ok, Self::Error>
-        where
-            T: ?Sized + Serialize,
-        {
-            Err(Unsupported)
-        }
+    #[test]
+    #[cfg(all(feature = "std", not(feature = "serde_interop")))]
+    fn visit_os_str() {
+        use self::std::ffi::{OsStr, OsString};
 
-        fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-            Err(Unsupported)
-        }
+        assert_visit(&OsStr::new("a"), &[Token::Str("a")]);
+        assert_visit(&OsString::from("a"), &[Token::Str("a")]);
+    }
 
-        fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-            Err(Unsupported)
-        }
+    #[test]
+    #[cfg(all(unix, feature = "std", not(feature = "serde_interop")))]
+    fn visit_non_utf8_os_str() {
+        use self::std::{ffi::OsStr, os::unix::ffi::OsStrExt};
 
-        fn serialize_tuple_struct(
-            self,
-            _name: &'static str,
-            _len: usize,
-        ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-            Err(Unsupported)
-        }
+        assert_visit(&OsStr::from_bytes(b"a\xff"), &[Token::Str("a\u{fffd}")]);
+    }
 
-        fn serialize_tuple_variant(
-            self,
-            _name: &'static str,
-            _variant_index: u32,
-            _variant: &'static str,
-            _len: usize,
-        ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-            Err(Unsupported)
-        }
+    #[test]
+    fn visit_result() {
+        assert_visit(
+            &Ok::<u8, &str>(1),
+            &[
+                Token::VariantBegin("Result", "Ok", 0),
+                Token::U64(1),
+                Token::VariantEnd,
+            ],
+        );
+
+        assert_visit(
+            &Err::<u8, &str>("failed"),
+            &[
+                Token::VariantBegin("Result", "Err", 1),
+                Token::Str("failed"),
+                Token::VariantEnd,
+            ],
+        );
+    }
 
-        fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-            Err(Unsupported)
-        }
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_once_cell() {
+        use self::std::cell::OnceCell;
 
-        fn serialize_struct(
-            self,
-            _name: &'static str,
-            _len: usize,
-        ) -> Result<Self::SerializeStruct, Self::Error> {
-            Err(Unsupported)
-        }
+        let cell = OnceCell::new();
+        assert_visit(&cell, &[Token::None]);
 
-        fn serialize_struct_variant(
-            self,
-            _name: &'static str,
-            _variant_index: u32,
-            _variant: &'static str,
-            _len: usize,
-        ) -> Result<Self::SerializeStructVariant, Self::Error> {
-            Err(Unsupported)
-        }
+        cell.set(1u8).unwrap();
+        assert_visit(&cell, &[Token::Some, Token::U64(1)]);
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::*;
+    #[test]
+    #[cfg(all(feature = "std", not(feature = "serde_interop")))]
+    fn visit_lazy_lock() {
+        use self::std::sync::LazyLock;
 
-    #[derive(PartialEq, Debug)]
-    enum Token<'a> {
-        I64(i64),
-        U64(u64),
-        F64(f64),
-        Bool(bool),
-        Char(char),
-        Str(&'a str),
-        Bytes(&'a [u8]),
-        Args(&'a str),
+        let lazy = LazyLock::new(|| 1u8);
+        assert_visit(&lazy, &[Token::None]);
+
+        assert_visit(&ForceLazy::new(&lazy), &[Token::U64(1)]);
+        assert_visit(&lazy, &[Token::Some, Token::U64(1)]);
     }
 
-    // `&dyn ser::Serialize` should impl `Serialize`
-    fn assert_visit(v: &dyn Visit, token: Token) {
-        struct TestVisitor<'a>(Token<'a>);
+    #[test]
+    #[cfg(feature = "std")]
+    fn visit_force_lazy() {
+        use self::std::sync::LazyLock;
 
-        impl<'a> Visitor for TestVisitor<'a> {
-            fn visit_i64(&mut self, v: i64) {
-                assert_eq!(self.0, Token::I64(v));
-            }
-            
-            fn visit_u64(&mut self, v: u64) {
-                assert_eq!(self.0, Token::U64(v));
-            }
+        let lazy: LazyLock<u8> = LazyLock::new(|| 1);
+        assert_visit(&ForceLazy::new(&lazy), &[Token::U64(1)]);
+    }
 
-            fn visit_f64(&mut self, v: f64) {
-                assert_eq!(self.0, Token::F64(v));
-            }
+    #[test]
+    fn visit_range() {
+        assert_visit(
+            &(1u8..3),
+            &[
+                Token::RecordBegin("Range", Some(2)),
+                Token::RecordField("start"),
+                Token::U64(1),
+                Token::RecordField("end"),
+                Token::U64(3),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &(1u8..=3),
+            &[
+                Token::RecordBegin("RangeInclusive", Some(2)),
+                Token::RecordField("start"),
+                Token::U64(1),
+                Token::RecordField("end"),
+                Token::U64(3),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &(1u8..),
+            &[
+                Token::RecordBegin("RangeFrom", Some(1)),
+                Token::RecordField("start"),
+                Token::U64(1),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &(..3u8),
+            &[
+                Token::RecordBegin("RangeTo", Some(1)),
+                Token::RecordField("end"),
+                Token::U64(3),
+                Token::RecordEnd,
+            ],
+        );
+    }
 
-            fn visit_bool(&mut self, v: bool) {
-                assert_eq!(self.0, Token::Bool(v));
-            }
+    #[test]
+    fn visit_atomic() {
+        use self::std::sync::atomic::{AtomicBool, AtomicI32, AtomicU64, AtomicUsize};
 
-            fn visit_char(&mut self, v: char) {
-                assert_eq!(self.0, Token::Char(v));
-            }
+        assert_visit(&AtomicBool::new(true), &[Token::Bool(true)]);
+        assert_visit(&AtomicI32::new(-1), &[Token::I64(-1)]);
+        assert_visit(&AtomicU64::new(1), &[Token::U64(1)]);
+        assert_visit(&AtomicUsize::new(1), &[Token::U64(1)]);
+    }
 
-            fn visit_str(&mut self, v: &str) {
-                assert_eq!(self.0, Token::Str(v));
-            }
+    #[test]
+    fn visit_unit_value() {
+        assert_visit(&(), &[Token::Unit]);
+        assert_visit(&Some(()), &[Token::Some, Token::Unit]);
+        assert_visit(&marker::PhantomData::<str>, &[Token::Unit]);
+    }
 
-            fn visit_bytes(&mut self, v: &[u8]) {
-                assert_eq!(self.0, Token::Bytes(v));
-            }
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_unit() {
+        #[derive(Debug)]
+        struct Marker;
 
-            fn visit_fmt(&mut self, v: &fmt::Arguments) {
-                use self::std::{str, ptr};
-                use self::fmt::Write;
+        impl serde::Serialize for Marker {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_unit_struct("Marker")
+            }
+        }
 
-                const LEN: usize = 128;
+        assert_visit(&Marker, &[Token::Unit]);
+    }
 
-                struct VisitArgs {
-                    buf: [u8; LEN],
-                    cursor: usize,
-                }
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_map() {
+        use serde_json::json;
 
-                impl VisitArgs {
-                    fn new() -> Self {
-                        VisitArgs {
-                            buf: [0; LEN],
-                            cursor: 0,
-                        }
-                    }
+        let v = json!({
+            "id": 123,
+            "name": "alice",
+        });
 
-                    fn to_str(&self) -> Option<&str> {
-                        str::from_utf8(&self.buf[0..self.cursor]).ok()
-                    }
-                }
+        assert_visit(
+            &v,
+            &[
+                Token::MapBegin(Some(2)),
+                Token::MapKey,
+                Token::Str("id"),
+                Token::MapValue,
+                Token::U64(123),
+                Token::MapKey,
+                Token::Str("name"),
+                Token::MapValue,
+                Token::Str("alice"),
+                Token::MapEnd,
+            ],
+        );
+    }
 
-                impl Write for VisitArgs {
-                    fn write_str(&mut self, s: &str) -> fmt::Result {
-                        let src = s.as_bytes();
-                        let next_cursor = self.cursor + src.len();
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_record() {
+        use serde::ser::{Serialize, SerializeStruct, Serializer};
 
-                        if next_cursor > LEN {
-                            return Err(fmt::Error);
-                        }
+        #[derive(Debug)]
+        struct Record {
+            id: u8,
+            name: &'static str,
+        }
 
-                        unsafe {
-                            let src_ptr = src.as_ptr();
-                            let dst_ptr = self.buf.as_mut_ptr().offset(self.cursor as isize);
+        impl Serialize for Record {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                let mut record = serializer.serialize_struct("Record", 2)?;
+                record.serialize_field("id", &self.id)?;
+                record.serialize_field("name", &self.name)?;
+                record.end()
+            }
+        }
 
-                            ptr::copy_nonoverlapping(src_ptr, dst_ptr, src.len());
-                        }
+        assert_visit(
+            &Record {
+                id: 1,
+                name: "alice",
+            },
+            &[
+                Token::RecordBegin("Record", Some(2)),
+                Token::RecordField("id"),
+                Token::U64(1),
+                Token::RecordField("name"),
+                Token::Str("alice"),
+                Token::RecordEnd,
+            ],
+        );
+    }
 
-                        self.cursor = next_cursor;
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn visit_variant() {
+        use serde::ser::{Serialize, SerializeStructVariant, SerializeTupleVariant, Serializer};
+
+        #[derive(Debug)]
+        enum Enum {
+            Unit,
+            Newtype(u8),
+            Tuple(u8, bool),
+            Struct { id: u8 },
+        }
 
-                        Ok(())
+        impl Serialize for Enum {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                match *self {
+                    Enum::Unit => serializer.serialize_unit_variant("Enum", 0, "Unit"),
+                    Enum::Newtype(ref v) => {
+                        serializer.serialize_newtype_variant("Enum", 1, "Newtype", v)
+                    }
+                    Enum::Tuple(ref a, ref b) => {
+                        let mut variant =
+                            serializer.serialize_tuple_variant("Enum", 2, "Tuple", 2)?;
+                        variant.serialize_field(a)?;
+                        variant.serialize_field(b)?;
+                        variant.end()
+                    }
+                    Enum::Struct { ref id } => {
+                        let mut variant =
+                            serializer.serialize_struct_variant("Enum", 3, "Struct", 1)?;
+                        variant.serialize_field("id", id)?;
+                        variant.end()
                     }
                 }
-
-                let mut w = VisitArgs::new();
-                w.write_fmt(format_args!("{}", v)).unwrap();
-                assert_eq!(self.0, Token::Args(w.to_str().unwrap()));
             }
         }
 
-        v.visit(&mut TestVisitor(token));
+        assert_visit(&Enum::Unit, &[Token::Variant("Enum", "Unit", 0)]);
+
+        assert_visit(
+            &Enum::Newtype(1),
+            &[
+                Token::VariantBegin("Enum", "Newtype", 1),
+                Token::U64(1),
+                Token::VariantEnd,
+            ],
+        );
+
+        assert_visit(
+            &Enum::Tuple(1, true),
+            &[
+                Token::VariantBegin("Enum", "Tuple", 2),
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::Bool(true),
+                Token::SeqEnd,
+                Token::VariantEnd,
+            ],
+        );
+
+        assert_visit(
+            &Enum::Struct { id: 1 },
+            &[
+                Token::VariantBegin("Enum", "Struct", 3),
+                Token::RecordBegin("Struct", Some(1)),
+                Token::RecordField("id"),
+                Token::U64(1),
+                Token::RecordEnd,
+                Token::VariantEnd,
+            ],
+        );
     }
 
     #[test]
-    fn visit_simple() {
-        assert_visit(&1u8, Token::U64(1u64));
-        assert_visit(&true, Token::Bool(true));
-        assert_visit(&"a string", Token::Str("a string"));
+    #[cfg(feature = "serde_interop")]
+    fn visit_newtype_untagged() {
+        use serde::ser::{Serialize, Serializer};
+
+        #[derive(Debug)]
+        struct Uuid(u128);
+
+        impl Serialize for Uuid {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.serialize_newtype_struct("Uuid", &self.0)
+            }
+        }
+
+        assert_visit(&Uuid(1), &[Token::U128(1)]);
     }
 
     #[test]
     #[cfg(feature = "serde_interop")]
-    fn visit_unsupported_as_debug() {
+    fn visit_seq() {
         use serde_json::json;
 
-        let v = json!({
-            "id": 123,
-            "name": "alice",
-        });
-
-        assert_visit(&v, Token::Args(&format!("{:?}", v)));
+        let v = json!([1, "a"]);
+
+        assert_visit(
+            &v,
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqEnd,
+            ],
+        );
+
+        assert_visit(
+            &(1u8, true),
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::Bool(true),
+                Token::SeqEnd,
+            ],
+        );
     }
 
     #[cfg(feature = "serde_interop")]
     mod serde_interop {
         use crate::*;
-        use serde_test::{Token, assert_ser_tokens};
         use serde_json::json;
+        use serde_test::{assert_ser_tokens, Token};
 
         // `&dyn ser::Serialize` should impl `Serialize`
         fn assert_visit(v: &dyn Visit, tokens: &[Token]) {
@@ -589,14 +4040,17 @@ mod tests {
                 "name": "alice",
             });
 
-            assert_visit(&v, &[
-                Token::Map { len: Some(2) },
-                Token::Str("id"),
-                Token::U64(123),
-                Token::Str("name"),
-                Token::Str("alice"),
-                Token::MapEnd,
-            ]);
+            assert_visit(
+                &v,
+                &[
+                    Token::Map { len: Some(2) },
+                    Token::Str("id"),
+                    Token::U64(123),
+                    Token::Str("name"),
+                    Token::Str("alice"),
+                    Token::MapEnd,
+                ],
+            );
         }
     }
 }
@@ -0,0 +1,431 @@
+//! A logfmt serializer for visited values.
+//!
+//! Records and maps are written as space-separated `key=value` pairs.
+//! Nested records and maps are flattened into dotted keys, like
+//! `user.id=1`, and sequence elements are keyed by their index. Values
+//! containing spaces, quotes, `=`, or control characters are quoted and
+//! escaped. Optional and unit values are written as `null`.
+
+use crate::std::fmt::{self, Write as _};
+
+use crate::{json::Escape, Error, TextWriter, Visitor};
+
+const MAX_KEY: usize = 256;
+const MAX_DEPTH: usize = 32;
+
+/// A visitor that writes records and maps as logfmt.
+///
+/// Each record is written on a single line without a trailing newline.
+/// When the same writer is used for multiple records, they're separated
+/// by newlines. Other values can only be written as the fields of a record.
+///
+/// ```
+/// let mut logfmt = ser::logfmt::Writer::new(String::new());
+///
+/// let value = ser::value!({ "level": "info", "msg": "hello world", "user": { "id": 1 } });
+/// ser::Visit::visit(&value, &mut logfmt).unwrap();
+///
+/// assert_eq!(r#"level=info msg="hello world" user.id=1"#, logfmt.into_inner());
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    first: bool,
+    key: bool,
+    written: bool,
+    path: Path,
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            first: true,
+            key: false,
+            written: false,
+            path: Path::new(),
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write a `key=value` pair for the current key, or add to the key if one is being visited.
+    fn entry(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        if self.key {
+            self.key = false;
+
+            return self
+                .path
+                .write_fmt(v)
+                .map_err(|_| Error::msg("the key is too long"));
+        }
+
+        if self.path.depth == 0 {
+            return Err(Error::msg("only records and maps can be written as logfmt"));
+        }
+
+        if !crate::std::mem::replace(&mut self.first, false) {
+            self.out.write_char(' ')?;
+        }
+
+        self.out.write_str(self.path.as_str())?;
+        self.out.write_char('=')?;
+
+        let mut quote = Quote {
+            empty: true,
+            special: false,
+        };
+        fmt::write(&mut quote, v)?;
+
+        if quote.empty || quote.special {
+            self.out.write_char('"')?;
+            Escape(&mut self.out).write_fmt(v)?;
+            self.out.write_char('"')?;
+        } else {
+            self.out.write_fmt(v)?;
+        }
+
+        Ok(())
+    }
+
+    fn begin(&mut self, index: Option<usize>) -> Result<(), Error> {
+        if self.key {
+            return Err(Error::msg("map keys must be strings, numbers, or booleans"));
+        }
+
+        if self.path.depth == 0 {
+            // Records after the first are written on their own lines
+            if crate::std::mem::replace(&mut self.written, true) {
+                self.out.write_char('\n')?;
+            }
+
+            self.first = true;
+        }
+
+        self.path.push(index)
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        self.path.pop()
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.entry(format_args!("{}", v))
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.entry(format_args!("null"))
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin(None)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.path.segment()?;
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin(Some(0))
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.path.segment()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn record_begin(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin(None)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.path.segment()?;
+        self.key = true;
+        self.entry(format_args!("{}", name))
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        // The variant is keyed by its name, like a single field record
+        self.begin(None)?;
+        self.path.segment()?;
+        self.key = true;
+        self.entry(format_args!("{}", variant))
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.entry(*args)
+    }
+}
+
+/// The dotted key of the value being visited.
+#[derive(Debug)]
+struct Path {
+    buf: [u8; MAX_KEY],
+    len: usize,
+    frames: [Frame; MAX_DEPTH],
+    depth: usize,
+}
+
+/// The start of the key for a record, map, or sequence, and the index of the next element of a sequence.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    start: usize,
+    index: Option<usize>,
+}
+
+impl Path {
+    fn new() -> Self {
+        Path {
+            buf: [0; MAX_KEY],
+            len: 0,
+            frames: [Frame {
+                start: 0,
+                index: None,
+            }; MAX_DEPTH],
+            depth: 0,
+        }
+    }
+
+    fn as_str(&self) -> &str {
+        // Only whole strings are ever written to the buffer
+        crate::std::str::from_utf8(&self.buf[..self.len]).expect("invalid key")
+    }
+
+    fn push(&mut self, index: Option<usize>) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::msg("values are nested too deeply"));
+        }
+
+        self.frames[self.depth] = Frame {
+            start: self.len,
+            index,
+        };
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Err(Error::msg("a value was ended without being started"));
+        }
+
+        self.depth -= 1;
+        self.len = self.frames[self.depth].start;
+
+        Ok(())
+    }
+
+    /// Begin a new segment of the key, replacing the last one.
+    fn segment(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Err(Error::msg(
+                "keys and elements must be inside a record, map, or sequence",
+            ));
+        }
+
+        self.try_segment()
+            .map_err(|_| Error::msg("the key is too long"))
+    }
+
+    fn try_segment(&mut self) -> fmt::Result {
+        let frame = &mut self.frames[self.depth - 1];
+        let index = frame.index.as_mut().map(|index| {
+            *index += 1;
+            *index - 1
+        });
+
+        self.len = frame.start;
+
+        if self.len > 0 {
+            self.write_char('.')?;
+        }
+
+        if let Some(index) = index {
+            write!(self, "{}", index)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Write for Path {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            // Keys can't be quoted, so characters that would need it are replaced
+            let c = if c <= ' ' || c == '=' || c == '"' {
+                '_'
+            } else {
+                c
+            };
+
+            let len = c.len_utf8();
+            if self.len + len > MAX_KEY {
+                return Err(fmt::Error);
+            }
+
+            c.encode_utf8(&mut self.buf[self.len..self.len + len]);
+            self.len += len;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check whether a value needs to be quoted.
+///
+/// Empty values are quoted so they aren't mistaken for keys without values.
+struct Quote {
+    empty: bool,
+    special: bool,
+}
+
+impl fmt::Write for Quote {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.empty &= s.is_empty();
+        self.special |= s
+            .chars()
+            .any(|c| c <= ' ' || c == '=' || c == '"' || c == '\u{7f}');
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_logfmt(v: &dyn Visit, expected: &str) {
+        let mut logfmt = Writer::new(VisitArgs::new());
+        v.visit(&mut logfmt).unwrap();
+
+        assert_eq!(Some(expected), logfmt.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_record() {
+        assert_logfmt(
+            &crate::value!({ "a": 1, "b": true, "c": null }),
+            "a=1 b=true c=null",
+        );
+        assert_logfmt(&crate::value!({}), "");
+
+        let mut logfmt = Writer::new(VisitArgs::new());
+        logfmt.record_begin("Record", Some(2)).unwrap();
+        logfmt.record_field("a").unwrap();
+        logfmt.visit_f64(1.5).unwrap();
+        logfmt.record_field("b").unwrap();
+        logfmt.visit_variant("Enum", "A", 0).unwrap();
+        logfmt.record_end().unwrap();
+
+        assert_eq!(Some("a=1.5 b=A"), logfmt.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_records() {
+        let mut logfmt = Writer::new(VisitArgs::new());
+
+        crate::value!({ "a": 1 }).visit(&mut logfmt).unwrap();
+        crate::value!({}).visit(&mut logfmt).unwrap();
+        crate::value!({ "b": 2, "c": 3 })
+            .visit(&mut logfmt)
+            .unwrap();
+
+        assert_eq!(Some("a=1\n\nb=2 c=3"), logfmt.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_quoted() {
+        assert_logfmt(&crate::value!({ "a": "" }), r#"a="""#);
+        assert_logfmt(&crate::value!({ "a": "b c" }), r#"a="b c""#);
+        assert_logfmt(&crate::value!({ "a": "b=c" }), r#"a="b=c""#);
+        assert_logfmt(&crate::value!({ "a": "\"b\"\n" }), r#"a="\"b\"\n""#);
+        assert_logfmt(&crate::value!({ "a b=\"c\"": 1 }), "a_b__c_=1");
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_logfmt(
+            &crate::value!({ "a": { "b": 1, "c": { "d": 2 } }, "e": ["f", "g"], "h": 3 }),
+            "a.b=1 a.c.d=2 e.0=f e.1=g h=3",
+        );
+
+        let mut logfmt = Writer::new(VisitArgs::new());
+        logfmt.map_begin(Some(1)).unwrap();
+        logfmt.map_key().unwrap();
+        logfmt.visit_str("shape").unwrap();
+        logfmt.map_value().unwrap();
+        logfmt.variant_begin("Shape", "Circle", 0).unwrap();
+        logfmt.record_begin("Circle", Some(1)).unwrap();
+        logfmt.record_field("radius").unwrap();
+        logfmt.visit_u64(1).unwrap();
+        logfmt.record_end().unwrap();
+        logfmt.variant_end().unwrap();
+        logfmt.map_end().unwrap();
+
+        assert_eq!(Some("shape.Circle.radius=1"), logfmt.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_invalid() {
+        assert!(1.visit(&mut Writer::new(VisitArgs::new())).is_err());
+
+        let mut logfmt = Writer::new(VisitArgs::new());
+        logfmt.map_begin(None).unwrap();
+        logfmt.map_key().unwrap();
+        assert!(logfmt.seq_begin(None).is_err());
+
+        assert!(Writer::new(VisitArgs::new()).map_key().is_err());
+        assert!(Writer::new(VisitArgs::new()).record_field("a").is_err());
+        assert!(Writer::new(VisitArgs::new()).map_end().is_err());
+    }
+}
@@ -0,0 +1,598 @@
+//! A MessagePack serializer for visited values.
+//!
+//! Records and maps are encoded as maps, sequences as arrays, and enum
+//! variants are externally tagged. Strings and bytes use the separate `str`
+//! and `bin` families. Integers use the smallest encoding that fits them,
+//! and timestamps use the timestamp extension type. Other values that
+//! MessagePack has no representation for, like durations and 128bit
+//! integers that don't fit in 64 bits, are encoded as strings.
+//!
+//! MessagePack needs the length of maps and arrays upfront. With `std`,
+//! maps and arrays visited without a length hint, like records with flattened
+//! fields, are buffered until they're complete so their length is known.
+//! Without `std`, visiting them without a length hint fails.
+
+use crate::std::{convert::TryFrom, fmt};
+
+#[cfg(feature = "std")]
+use crate::std::vec::Vec;
+
+use crate::{write, Error, Visitor, WriteBytes};
+
+const NIL: u8 = 0xc0;
+const FALSE: u8 = 0xc2;
+const TRUE: u8 = 0xc3;
+const BIN8: u8 = 0xc4;
+const EXT8: u8 = 0xc7;
+const F64: u8 = 0xcb;
+const UINT8: u8 = 0xcc;
+const INT8: u8 = 0xd0;
+const FIXEXT4: u8 = 0xd6;
+const FIXEXT8: u8 = 0xd7;
+const STR8: u8 = 0xd9;
+const ARRAY16: u8 = 0xdc;
+const MAP16: u8 = 0xde;
+
+const FIXSTR: u8 = 0xa0;
+const FIXARRAY: u8 = 0x90;
+const FIXMAP: u8 = 0x80;
+
+const EXT_TIMESTAMP: u8 = 0xff;
+
+/// A visitor that encodes values as MessagePack.
+///
+/// The writer only allocates to buffer maps and arrays without a known
+/// length, so it can be used without `std` by writing into a `&mut [u8]`.
+///
+/// ```
+/// let mut buf = [0; 8];
+/// let mut msgpack = ser::msgpack::Writer::new(&mut buf[..]);
+///
+/// ser::Visit::visit(&[1, -1, 300][..], &mut msgpack).unwrap();
+/// let remaining = msgpack.into_inner().len();
+///
+/// assert_eq!([0x93, 0x01, 0xff, 0xcd, 0x01, 0x2c], buf[..8 - remaining]);
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    #[cfg(feature = "std")]
+    pending: Vec<Pending>,
+}
+
+/// A map or array without a known length.
+///
+/// Its contents are buffered until it's complete, then written
+/// after a header with the number of entries or elements counted.
+#[cfg(feature = "std")]
+#[derive(Debug)]
+struct Pending {
+    map: bool,
+    len: usize,
+    depth: usize,
+    buf: Vec<u8>,
+}
+
+impl<W> Writer<W>
+where
+    W: WriteBytes,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            #[cfg(feature = "std")]
+            pending: Vec::new(),
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write bytes to the innermost pending map or array, or to the output.
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        {
+            if let Some(pending) = self.pending.last_mut() {
+                pending.buf.extend_from_slice(bytes);
+                return Ok(());
+            }
+        }
+
+        self.out.write_bytes(bytes)
+    }
+
+    /// Write the marker for a value with a length.
+    ///
+    /// The `fix` marker is used for lengths up to `fix_max`. Otherwise
+    /// the `wide` marker is followed by an 8bit length if `wide` has one,
+    /// then the next markers with 16 and 32bit lengths.
+    fn len(
+        &mut self,
+        fix: Option<(u8, usize)>,
+        wide: u8,
+        has_8bit: bool,
+        len: usize,
+    ) -> Result<(), Error> {
+        match fix {
+            Some((fix, fix_max)) if len <= fix_max => return self.write(&[fix | len as u8]),
+            _ => (),
+        }
+
+        let wide = if has_8bit {
+            if let Ok(len) = u8::try_from(len) {
+                return self.write(&[wide, len]);
+            }
+
+            wide + 1
+        } else {
+            wide
+        };
+
+        if let Ok(len) = u16::try_from(len) {
+            let [a, b] = len.to_be_bytes();
+            self.write(&[wide, a, b])
+        } else if let Ok(len) = u32::try_from(len) {
+            let [a, b, c, d] = len.to_be_bytes();
+            self.write(&[wide + 1, a, b, c, d])
+        } else {
+            Err(Error::msg("the value is too long to encode as MessagePack"))
+        }
+    }
+
+    fn str_len(&mut self, len: usize) -> Result<(), Error> {
+        self.len(Some((FIXSTR, 31)), STR8, true, len)
+    }
+
+    fn str(&mut self, v: &str) -> Result<(), Error> {
+        self.str_len(v.len())?;
+        self.write(v.as_bytes())
+    }
+
+    fn header(&mut self, map: bool, len: usize) -> Result<(), Error> {
+        if map {
+            self.len(Some((FIXMAP, 15)), MAP16, false, len)
+        } else {
+            self.len(Some((FIXARRAY, 15)), ARRAY16, false, len)
+        }
+    }
+
+    /// Begin a map or array, buffering it if its length isn't known.
+    fn begin(&mut self, map: bool, len: Option<usize>) -> Result<(), Error> {
+        match len {
+            Some(len) => {
+                #[cfg(feature = "std")]
+                {
+                    if let Some(pending) = self.pending.last_mut() {
+                        pending.depth += 1;
+                    }
+                }
+
+                self.header(map, len)
+            }
+            #[cfg(feature = "std")]
+            None => {
+                self.pending.push(Pending {
+                    map,
+                    len: 0,
+                    depth: 0,
+                    buf: Vec::new(),
+                });
+
+                Ok(())
+            }
+            #[cfg(not(feature = "std"))]
+            None => Err(Error::msg(if map {
+                "MessagePack maps need a known length without `std`"
+            } else {
+                "MessagePack arrays need a known length without `std`"
+            })),
+        }
+    }
+
+    /// Count an entry or element of a pending map or array.
+    fn elem(&mut self) {
+        #[cfg(feature = "std")]
+        {
+            if let Some(pending) = self.pending.last_mut() {
+                if pending.depth == 0 {
+                    pending.len += 1;
+                }
+            }
+        }
+    }
+
+    /// Complete a map or array, writing it if it was pending.
+    fn end(&mut self) -> Result<(), Error> {
+        #[cfg(feature = "std")]
+        {
+            if let Some(pending) = self.pending.last_mut() {
+                if pending.depth > 0 {
+                    pending.depth -= 1;
+                } else if let Some(pending) = self.pending.pop() {
+                    self.header(pending.map, pending.len)?;
+                    return self.write(&pending.buf);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: WriteBytes,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        if v >= 0 {
+            return self.visit_u64(v as u64);
+        }
+
+        if v >= -32 {
+            self.write(&[v as u8])
+        } else if let Ok(v) = i8::try_from(v) {
+            self.write(&[INT8, v as u8])
+        } else if let Ok(v) = i16::try_from(v) {
+            let [a, b] = v.to_be_bytes();
+            self.write(&[INT8 + 1, a, b])
+        } else if let Ok(v) = i32::try_from(v) {
+            let [a, b, c, d] = v.to_be_bytes();
+            self.write(&[INT8 + 2, a, b, c, d])
+        } else {
+            let [a, b, c, d, e, f, g, h] = v.to_be_bytes();
+            self.write(&[INT8 + 3, a, b, c, d, e, f, g, h])
+        }
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        if v <= 0x7f {
+            self.write(&[v as u8])
+        } else if let Ok(v) = u8::try_from(v) {
+            self.write(&[UINT8, v])
+        } else if let Ok(v) = u16::try_from(v) {
+            let [a, b] = v.to_be_bytes();
+            self.write(&[UINT8 + 1, a, b])
+        } else if let Ok(v) = u32::try_from(v) {
+            let [a, b, c, d] = v.to_be_bytes();
+            self.write(&[UINT8 + 2, a, b, c, d])
+        } else {
+            let [a, b, c, d, e, f, g, h] = v.to_be_bytes();
+            self.write(&[UINT8 + 3, a, b, c, d, e, f, g, h])
+        }
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        if let Ok(v) = i64::try_from(v) {
+            self.visit_i64(v)
+        } else if let Ok(v) = u64::try_from(v) {
+            self.visit_u64(v)
+        } else {
+            self.visit_fallback(&v)
+        }
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        match u64::try_from(v) {
+            Ok(v) => self.visit_u64(v),
+            Err(_) => self.visit_fallback(&v),
+        }
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        let [a, b, c, d, e, f, g, h] = v.to_be_bytes();
+        self.write(&[F64, a, b, c, d, e, f, g, h])
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.write(&[if v { TRUE } else { FALSE }])
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.str(v)
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.len(None, BIN8, true, v.len())?;
+        self.write(v)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.write(&[NIL])
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.write(&[NIL])
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.begin(true, len)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.elem();
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.begin(false, len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.elem();
+        Ok(())
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn record_begin(&mut self, _: &str, len: Option<usize>) -> Result<(), Error> {
+        self.begin(true, len)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.elem();
+        self.str(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.str(variant)
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.header(true, 1)?;
+        self.str(variant)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        // Use the smallest of the timestamp 32, 64, and 96 encodings that fits
+        match (u32::try_from(secs), nanos) {
+            (Ok(secs), 0) => {
+                let [a, b, c, d] = secs.to_be_bytes();
+                self.write(&[FIXEXT4, EXT_TIMESTAMP, a, b, c, d])
+            }
+            _ if secs >= 0 && secs >> 34 == 0 => {
+                let v = (nanos as u64) << 34 | secs as u64;
+                let [a, b, c, d, e, f, g, h] = v.to_be_bytes();
+                self.write(&[FIXEXT8, EXT_TIMESTAMP, a, b, c, d, e, f, g, h])
+            }
+            _ => {
+                let [a, b, c, d] = nanos.to_be_bytes();
+                let [e, f, g, h, i, j, k, l] = secs.to_be_bytes();
+                self.write(&[EXT8, 12, EXT_TIMESTAMP, a, b, c, d, e, f, g, h, i, j, k, l])
+            }
+        }
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        if let Some(v) = args.as_str() {
+            return self.str(v);
+        }
+
+        // The length of the string needs to be written first,
+        // so the arguments are formatted twice
+        let len = write::fmt_len(*args)?;
+        self.str_len(len)?;
+
+        #[cfg(feature = "std")]
+        {
+            if let Some(pending) = self.pending.last_mut() {
+                return write::write_fmt(&mut pending.buf, *args, len);
+            }
+        }
+
+        write::write_fmt(&mut self.out, *args, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Visit;
+
+    fn assert_msgpack_with(
+        visit: impl FnOnce(&mut Writer<&mut [u8]>) -> Result<(), Error>,
+        expected: &[u8],
+    ) {
+        let mut buf = [0; 64];
+
+        let mut msgpack = Writer::new(&mut buf[..]);
+        visit(&mut msgpack).unwrap();
+        let remaining = msgpack.into_inner().len();
+
+        assert_eq!(expected, &buf[..64 - remaining]);
+    }
+
+    fn assert_msgpack(v: &dyn Visit, expected: &[u8]) {
+        assert_msgpack_with(|msgpack| v.visit(msgpack), expected)
+    }
+
+    #[test]
+    fn write_ints() {
+        assert_msgpack(&0, &[0x00]);
+        assert_msgpack(&127, &[0x7f]);
+        assert_msgpack(&128, &[0xcc, 0x80]);
+        assert_msgpack(&256, &[0xcd, 0x01, 0x00]);
+        assert_msgpack(&65536, &[0xce, 0x00, 0x01, 0x00, 0x00]);
+        assert_msgpack(
+            &u64::MAX,
+            &[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        );
+        assert_msgpack(&-1, &[0xff]);
+        assert_msgpack(&-32, &[0xe0]);
+        assert_msgpack(&-33, &[0xd0, 0xdf]);
+        assert_msgpack(&-129, &[0xd1, 0xff, 0x7f]);
+        assert_msgpack(&i64::MIN, &[0xd3, 0x80, 0, 0, 0, 0, 0, 0, 0]);
+        assert_msgpack(&1u128, &[0x01]);
+        assert_msgpack(&-1i128, &[0xff]);
+        assert_msgpack(
+            &(u64::MAX as i128),
+            &[0xcf, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        );
+    }
+
+    #[test]
+    fn write_primitives() {
+        assert_msgpack(&1.5, &[0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0]);
+        assert_msgpack(&false, &[0xc2]);
+        assert_msgpack(&true, &[0xc3]);
+        assert_msgpack(&None::<u8>, &[0xc0]);
+        assert_msgpack(&"", &[0xa0]);
+        assert_msgpack(&"abc", &[0xa3, b'a', b'b', b'c']);
+        assert_msgpack(&format_args!("{}{}", "a", 1), &[0xa2, b'a', b'1']);
+
+        assert_msgpack_with(|msgpack| msgpack.visit_str("a"), &[0xa1, b'a']);
+        assert_msgpack_with(
+            |msgpack| msgpack.visit_bytes(&[1, 2]),
+            &[0xc4, 0x02, 0x01, 0x02],
+        );
+        assert_msgpack_with(
+            |msgpack| msgpack.visit_u128(u64::MAX as u128 + 1),
+            &[
+                0xb4, b'1', b'8', b'4', b'4', b'6', b'7', b'4', b'4', b'0', b'7', b'3', b'7', b'0',
+                b'9', b'5', b'5', b'1', b'6', b'1', b'6',
+            ],
+        );
+    }
+
+    #[test]
+    fn write_long_str() {
+        let mut buf = [0; 64];
+
+        let mut msgpack = Writer::new(&mut buf[..]);
+        msgpack.visit_fmt(&format_args!("{:>40}", "a")).unwrap();
+        assert_eq!(64 - 42, msgpack.into_inner().len());
+
+        assert_eq!([0xd9, 40, b' '], buf[..3]);
+        assert_eq!(b'a', buf[41]);
+    }
+
+    #[test]
+    fn write_unstable_str() {
+        use crate::{std::cell::Cell, tests::Growing};
+
+        let mut buf = [0; 64];
+
+        let mut msgpack = Writer::new(&mut buf[..]);
+        assert!(msgpack.visit_display(&Growing(Cell::new(0))).is_err());
+    }
+
+    #[test]
+    fn write_timestamp() {
+        assert_msgpack_with(
+            |msgpack| msgpack.visit_timestamp(1, 0),
+            &[0xd6, 0xff, 0, 0, 0, 1],
+        );
+        assert_msgpack_with(
+            |msgpack| msgpack.visit_timestamp(1, 1),
+            &[0xd7, 0xff, 0, 0, 0, 0x04, 0, 0, 0, 0x01],
+        );
+        assert_msgpack_with(
+            |msgpack| msgpack.visit_timestamp(-1, 0),
+            &[
+                0xc7, 12, 0xff, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ],
+        );
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_msgpack(&&[1, 2, 3][..], &[0x93, 0x01, 0x02, 0x03]);
+
+        assert_msgpack_with(
+            |msgpack| {
+                msgpack.record_begin("Record", Some(2))?;
+                msgpack.record_field("a")?;
+                msgpack.variant_begin("Enum", "B", 1)?;
+                msgpack.visit_variant("Enum", "C", 2)?;
+                msgpack.variant_end()?;
+                msgpack.record_field("b")?;
+                msgpack.map_begin(Some(0))?;
+                msgpack.map_end()?;
+                msgpack.record_end()
+            },
+            &[
+                0x82, 0xa1, b'a', 0x81, 0xa1, b'B', 0xa1, b'C', 0xa1, b'b', 0x80,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn write_unknown_len() {
+        assert_msgpack_with(
+            |msgpack| {
+                msgpack.map_begin(None)?;
+                msgpack.map_key()?;
+                msgpack.visit_str("a")?;
+                msgpack.map_value()?;
+                msgpack.seq_begin(None)?;
+                msgpack.seq_elem()?;
+                msgpack.seq_begin(Some(1))?;
+                msgpack.seq_elem()?;
+                msgpack.visit_display(&1)?;
+                msgpack.seq_end()?;
+                msgpack.seq_elem()?;
+                msgpack.visit_u64(2)?;
+                msgpack.seq_end()?;
+                msgpack.map_key()?;
+                msgpack.visit_str("b")?;
+                msgpack.map_value()?;
+                msgpack.visit_unit()?;
+                msgpack.map_end()
+            },
+            &[
+                0x82, 0xa1, b'a', 0x92, 0x91, 0xa1, b'1', 0x02, 0xa1, b'b', 0xc0,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(all(feature = "std", feature = "derive"))]
+    fn write_flatten() {
+        #[derive(Debug, Visit)]
+        struct Inner {
+            b: u8,
+        }
+
+        #[derive(Debug, Visit)]
+        struct Outer {
+            a: u8,
+            #[visit(flatten)]
+            inner: Inner,
+        }
+
+        assert_msgpack(
+            &Outer {
+                a: 1,
+                inner: Inner { b: 2 },
+            },
+            &[0x82, 0xa1, b'a', 0x01, 0xa1, b'b', 0x02],
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "std"))]
+    fn write_unknown_len() {
+        let mut buf = [0; 8];
+        let mut msgpack = Writer::new(&mut buf[..]);
+        assert!(msgpack.seq_begin(None).is_err());
+        assert!(msgpack.map_begin(None).is_err());
+    }
+}
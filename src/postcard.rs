@@ -0,0 +1,282 @@
+//! A compact binary serializer for visited values, in the style of `postcard`.
+//!
+//! Integers wider than a byte are written as LEB128 varints, with signed
+//! integers zigzag encoded first. Strings, bytes, sequences, and maps are
+//! prefixed with their length as a varint. Records are written as their
+//! fields in order without their names, and enum variants as the varint
+//! index of the variant followed by its data. Optional values are prefixed
+//! with a `0` or `1` byte, and unit values aren't written at all.
+//!
+//! The format isn't self-describing, so values need to be read back with
+//! the same types they were visited with. Floats are always written as
+//! 64bit, and values without a representation of their own, like durations
+//! and timestamps, are written as strings.
+//!
+//! The output isn't compatible with the `postcard` crate. Tuples, tuple
+//! structs, and the data of tuple variants are all visited as sequences,
+//! so they're prefixed with their length, which `postcard` doesn't do.
+
+use crate::std::fmt;
+
+use crate::{write, Error, Visitor, WriteBytes};
+
+/// A visitor that encodes values in a compact binary format.
+///
+/// The writer doesn't allocate, so it can be used without `std`
+/// by writing into a `&mut [u8]`.
+///
+/// ```
+/// let mut buf = [0; 8];
+/// let mut postcard = ser::postcard::Writer::new(&mut buf[..]);
+///
+/// ser::Visit::visit(&[1, -1, 300][..], &mut postcard).unwrap();
+/// let remaining = postcard.into_inner().len();
+///
+/// assert_eq!([0x03, 0x02, 0x01, 0xd8, 0x04], buf[..8 - remaining]);
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+}
+
+impl<W> Writer<W>
+where
+    W: WriteBytes,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer { out }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    fn varint(&mut self, mut v: u128) -> Result<(), Error> {
+        let mut buf = [0; 19];
+        let mut len = 0;
+
+        loop {
+            let b = (v & 0x7f) as u8;
+            v >>= 7;
+
+            if v == 0 {
+                buf[len] = b;
+                len += 1;
+                break;
+            }
+
+            buf[len] = b | 0x80;
+            len += 1;
+        }
+
+        self.out.write_bytes(&buf[..len])
+    }
+
+    fn zigzag(&mut self, v: i128) -> Result<(), Error> {
+        self.varint(((v << 1) ^ (v >> 127)) as u128)
+    }
+
+    fn len(&mut self, len: Option<usize>) -> Result<(), Error> {
+        match len {
+            Some(len) => self.varint(len as u128),
+            None => Err(Error::msg("sequences and maps need a known length")),
+        }
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: WriteBytes,
+{
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.out.write_bytes(&[v as u8])
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.out.write_bytes(&[v])
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.zigzag(v as i128)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.varint(v as u128)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.zigzag(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.varint(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.out.write_bytes(&v.to_le_bytes())
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.out.write_bytes(&[v as u8])
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.visit_bytes(v.as_bytes())
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.varint(v.len() as u128)?;
+        self.out.write_bytes(v)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.out.write_bytes(&[0])
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.out.write_bytes(&[1])
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.len(len)
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.len(len)
+    }
+
+    fn visit_variant(&mut self, _: &str, _: &str, index: u32) -> Result<(), Error> {
+        self.varint(index as u128)
+    }
+
+    fn variant_begin(&mut self, _: &str, _: &str, index: u32) -> Result<(), Error> {
+        self.varint(index as u128)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        false
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        if let Some(v) = args.as_str() {
+            return self.visit_str(v);
+        }
+
+        // The length of the string needs to be written first,
+        // so the arguments are formatted twice
+        let len = write::fmt_len(*args)?;
+        self.varint(len as u128)?;
+        write::write_fmt(&mut self.out, *args, len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::Visit;
+
+    fn assert_postcard_with(
+        visit: impl FnOnce(&mut Writer<&mut [u8]>) -> Result<(), Error>,
+        expected: &[u8],
+    ) {
+        let mut buf = [0; 64];
+
+        let mut postcard = Writer::new(&mut buf[..]);
+        visit(&mut postcard).unwrap();
+        let remaining = postcard.into_inner().len();
+
+        assert_eq!(expected, &buf[..64 - remaining]);
+    }
+
+    fn assert_postcard(v: &dyn Visit, expected: &[u8]) {
+        assert_postcard_with(|postcard| v.visit(postcard), expected)
+    }
+
+    #[test]
+    fn write_ints() {
+        assert_postcard(&0u8, &[0x00]);
+        assert_postcard(&255u8, &[0xff]);
+        assert_postcard(&-1i8, &[0xff]);
+        assert_postcard(&127u16, &[0x7f]);
+        assert_postcard(&128u16, &[0x80, 0x01]);
+        assert_postcard(&300u32, &[0xac, 0x02]);
+        assert_postcard(&0i32, &[0x00]);
+        assert_postcard(&-1i32, &[0x01]);
+        assert_postcard(&1i32, &[0x02]);
+        assert_postcard(&-64i64, &[0x7f]);
+        assert_postcard(&64i64, &[0x80, 0x01]);
+        assert_postcard(
+            &u64::MAX,
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+        );
+        assert_postcard(
+            &i64::MIN,
+            &[0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01],
+        );
+        assert_postcard_with(
+            |postcard| postcard.visit_u128(1 << 70),
+            &[
+                0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01,
+            ],
+        );
+    }
+
+    #[test]
+    fn write_primitives() {
+        assert_postcard(&true, &[0x01]);
+        assert_postcard(&1.5, &[0, 0, 0, 0, 0, 0, 0xf8, 0x3f]);
+        assert_postcard(&"abc", &[0x03, b'a', b'b', b'c']);
+        assert_postcard(&'a', &[0x01, b'a']);
+        assert_postcard(&format_args!("{}{}", "a", 1), &[0x02, b'a', b'1']);
+        assert_postcard(&None::<u8>, &[0x00]);
+        assert_postcard(&Some(1u8), &[0x01, 0x01]);
+        assert_postcard(&(), &[]);
+
+        assert_postcard_with(
+            |postcard| postcard.visit_bytes(&[1, 2]),
+            &[0x02, 0x01, 0x02],
+        );
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_postcard_with(
+            |postcard| {
+                postcard.record_begin("Record", Some(2))?;
+                postcard.record_field("a")?;
+                postcard.visit_u8(1)?;
+                postcard.record_field("b")?;
+                postcard.variant_begin("Enum", "B", 1)?;
+                postcard.seq_begin(Some(1))?;
+                postcard.seq_elem()?;
+                postcard.visit_variant("Enum", "C", 2)?;
+                postcard.seq_end()?;
+                postcard.variant_end()?;
+                postcard.record_end()
+            },
+            // The tuple variant's data is prefixed with its length
+            &[0x01, 0x01, 0x01, 0x02],
+        );
+
+        let mut buf = [0; 8];
+        let mut postcard = Writer::new(&mut buf[..]);
+        assert!(postcard.seq_begin(None).is_err());
+    }
+
+    #[test]
+    fn write_unstable_str() {
+        use crate::{std::cell::Cell, tests::Growing};
+
+        let mut buf = [0; 64];
+
+        let mut postcard = Writer::new(&mut buf[..]);
+        assert!(postcard.visit_display(&Growing(Cell::new(0))).is_err());
+    }
+}
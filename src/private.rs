@@ -0,0 +1,1246 @@
+//! Support for `#[derive(Visit)]`.
+//!
+//! Nothing in here is public API. Derived implementations go through
+//! `__impl_visit!` so they pick up the same `serde_interop` configuration
+//! as this crate instead of the crate that uses the derive.
+
+#[cfg(not(feature = "serde_interop"))]
+pub use crate::imp::VisitPrivate;
+
+#[cfg(feature = "serde_interop")]
+pub use self::capture::serialize;
+#[cfg(feature = "serde_interop")]
+pub use serde;
+
+use crate::{
+    std::{any::Any, fmt, time},
+    BytesHint, Capability, Error, Visitor,
+};
+
+/// Implement `Visit` for a type from the bodies of its `visit` and `stream` methods.
+///
+/// The `stream` body is optional, and the default is used when it's missing.
+#[cfg(not(feature = "serde_interop"))]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __impl_visit {
+    (
+        [$($generics:tt)*] $ty:ty, [$($where:tt)*]
+        |$self:ident, $visitor:ident| $body:block
+        $(|$stream_self:ident, $stream:ident| $stream_body:block)?
+    ) => {
+        impl $($generics)* $crate::Visit for $ty $($where)* {
+            fn visit<'v>(&'v $self, $visitor: &mut dyn $crate::Visitor<'v>) -> ::core::result::Result<(), $crate::Error> $body
+
+            $(
+                fn stream<'v, __S>(&'v $stream_self, $stream: &mut __S) -> ::core::result::Result<(), __S::Error>
+                where
+                    __S: $crate::Stream<'v>,
+                $stream_body
+            )?
+        }
+
+        impl $($generics)* $crate::__private::VisitPrivate for $ty $($where)* {}
+    };
+}
+
+/// Implement `Visit` for a type from the body of its `visit` method.
+///
+/// With `serde_interop`, types are visited through their `Serialize`
+/// implementation, so the body is captured and replayed into the serializer.
+/// The `stream` body isn't used.
+#[cfg(feature = "serde_interop")]
+#[macro_export]
+#[doc(hidden)]
+macro_rules! __impl_visit {
+    (
+        [$($generics:tt)*] $ty:ty, [$($where:tt)*]
+        |$self:ident, $visitor:ident| $body:block
+        $(|$stream_self:ident, $stream:ident| $stream_body:block)?
+    ) => {
+        impl $($generics)* $crate::__private::serde::Serialize for $ty $($where)* {
+            fn serialize<__S>(&$self, serializer: __S) -> ::core::result::Result<__S::Ok, __S::Error>
+            where
+                __S: $crate::__private::serde::Serializer,
+            {
+                $crate::__private::serialize(serializer, |$visitor| $body)
+            }
+        }
+    };
+}
+
+/// A visitor that visits the fields of a record or map as fields of an enclosing record.
+///
+/// This is used for internally tagged enum variants that contain a value,
+/// and for fields with `#[visit(flatten)]`.
+/// Visiting anything other than a record, map, unit, or optional record
+/// returns an error.
+pub struct Flatten<'a, 'v> {
+    visitor: &'a mut dyn Visitor<'v>,
+    depth: usize,
+    key: bool,
+}
+
+impl<'a, 'v> Flatten<'a, 'v> {
+    pub fn new(visitor: &'a mut dyn Visitor<'v>) -> Self {
+        Flatten {
+            visitor,
+            depth: 0,
+            key: false,
+        }
+    }
+
+    /// Check that a value can be visited.
+    fn value(&mut self) -> Result<(), Error> {
+        match self.depth {
+            0 => Err(Error::msg("only records and maps can be flattened")),
+            1 if self.key => Err(Error::msg("the keys of flattened maps must be strings")),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the next string is the key of a flattened map.
+    fn key(&mut self) -> bool {
+        self.depth == 1 && crate::std::mem::replace(&mut self.key, false)
+    }
+
+    /// Begin a record or map, returning whether it's nested in the flattened value.
+    fn begin(&mut self) -> Result<bool, Error> {
+        if self.depth == 0 {
+            self.depth = 1;
+            Ok(false)
+        } else {
+            self.value()?;
+            self.depth += 1;
+            Ok(true)
+        }
+    }
+
+    /// End a record or map, returning whether it's nested in the flattened value.
+    fn end(&mut self) -> bool {
+        self.depth -= 1;
+        self.depth > 0
+    }
+}
+
+impl<'a, 'v> Visitor<'v> for Flatten<'a, 'v> {
+    fn visit_i8(&mut self, v: i8) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_i8(v)
+    }
+
+    fn visit_i16(&mut self, v: i16) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_i16(v)
+    }
+
+    fn visit_i32(&mut self, v: i32) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_i32(v)
+    }
+
+    fn visit_u8(&mut self, v: u8) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_u8(v)
+    }
+
+    fn visit_u16(&mut self, v: u16) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_u16(v)
+    }
+
+    fn visit_u32(&mut self, v: u32) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_u32(v)
+    }
+
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_i64(v)
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_u64(v)
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_i128(v)
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_u128(v)
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_f64(v)
+    }
+
+    fn visit_f64_nonfinite(&mut self, v: f64) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_f64_nonfinite(v)
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_number_str(v)
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_bool(v)
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_char(v)
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        if self.key() {
+            self.visitor.record_field(v)
+        } else {
+            self.value()?;
+            self.visitor.visit_str(v)
+        }
+    }
+
+    fn visit_borrowed_str(&mut self, v: &'v str) -> Result<(), Error> {
+        if self.key() {
+            self.visitor.record_field(v)
+        } else {
+            self.value()?;
+            self.visitor.visit_borrowed_str(v)
+        }
+    }
+
+    fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.str_begin(len)
+    }
+
+    fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+        self.visitor.visit_str_fragment(v)
+    }
+
+    fn str_end(&mut self) -> Result<(), Error> {
+        self.visitor.str_end()
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_bytes(v)
+    }
+
+    fn visit_borrowed_bytes(&mut self, v: &'v [u8]) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_borrowed_bytes(v)
+    }
+
+    fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_bytes_hint(v, hint)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        // An empty optional value has no fields to flatten
+        if self.depth == 0 {
+            return Ok(());
+        }
+
+        self.value()?;
+        self.visitor.visit_none()
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+
+        self.value()?;
+        self.visitor.visit_some()
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+
+        self.value()?;
+        self.visitor.visit_unit()
+    }
+
+    fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        if self.begin()? {
+            self.visitor.map_begin(len)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        if self.depth == 1 {
+            self.key = true;
+            Ok(())
+        } else {
+            self.visitor.map_key()
+        }
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        if self.depth == 1 {
+            Ok(())
+        } else {
+            self.visitor.map_value()
+        }
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.map_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+        self.value()?;
+        self.depth += 1;
+        self.visitor.seq_begin(len)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.visitor.seq_elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.depth -= 1;
+        self.visitor.seq_end()
+    }
+
+    fn record_begin(&mut self, name: &str, len: Option<usize>) -> Result<(), Error> {
+        if self.begin()? {
+            self.visitor.record_begin(name, len)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.visitor.record_field(name)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        if self.end() {
+            self.visitor.record_end()
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_variant(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_variant(name, variant, index)
+    }
+
+    fn variant_begin(&mut self, name: &str, variant: &str, index: u32) -> Result<(), Error> {
+        self.value()?;
+        self.depth += 1;
+        self.visitor.variant_begin(name, variant, index)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.depth -= 1;
+        self.visitor.variant_end()
+    }
+
+    fn visit_tag(&mut self, tag: &str) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+
+        self.visitor.visit_tag(tag)
+    }
+
+    fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_duration(v)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_timestamp(secs, nanos)
+    }
+
+    fn visit_display(&mut self, v: &dyn fmt::Display) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_display(v)
+    }
+
+    fn visit_debug(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_debug(v)
+    }
+
+    #[cfg(feature = "std")]
+    fn visit_error(&mut self, v: &(dyn std::error::Error + 'static)) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_error(v)
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.visitor.context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.visitor.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.visitor.is_human_readable()
+    }
+
+    fn visit_fallback(&mut self, v: &dyn fmt::Debug) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_fallback(v)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.value()?;
+        self.visitor.visit_fmt(args)
+    }
+}
+
+#[cfg(feature = "serde_interop")]
+mod capture {
+    use crate::{
+        std::{boxed::Box, fmt, string::String, time, vec::Vec},
+        Error, Visitor,
+    };
+
+    use serde::ser::{Serialize, SerializeMap, SerializeSeq, Serializer};
+
+    /// Serialize a value from the visitor calls made by `visit`.
+    ///
+    /// Records and enum variants are serialized as maps, because `serde`
+    /// needs their names to be `'static`.
+    pub fn serialize<'v, S>(
+        serializer: S,
+        visit: impl FnOnce(&mut dyn Visitor<'v>) -> Result<(), Error>,
+    ) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut capture = Capture {
+            human_readable: serializer.is_human_readable(),
+            stack: Vec::new(),
+            root: None,
+        };
+
+        visit(&mut capture).map_err(serde::ser::Error::custom)?;
+
+        match capture.root {
+            Some(ref node) if capture.stack.is_empty() => node.serialize(serializer),
+            _ => Err(serde::ser::Error::custom("the value was incomplete")),
+        }
+    }
+
+    enum Node {
+        Unit,
+        None,
+        Some(Box<Node>),
+        Bool(bool),
+        I64(i64),
+        U64(u64),
+        I128(i128),
+        U128(u128),
+        F64(f64),
+        Str(String),
+        Bytes(Vec<u8>),
+        Seq(Vec<Node>),
+        Map(Vec<(Node, Node)>),
+        Record(Vec<(String, Node)>),
+        Variant(String, Option<Box<Node>>),
+    }
+
+    enum Frame {
+        Some,
+        Str(String),
+        Seq(Vec<Node>),
+        Map(Vec<(Node, Node)>, Option<Node>),
+        Record(Vec<(String, Node)>, Option<String>),
+        Variant(String, Option<Node>),
+    }
+
+    struct Capture {
+        human_readable: bool,
+        stack: Vec<Frame>,
+        root: Option<Node>,
+    }
+
+    impl Capture {
+        fn push(&mut self, node: Node) -> Result<(), Error> {
+            match self.stack.last_mut() {
+                None if self.root.is_none() => {
+                    self.root = Some(node);
+                    Ok(())
+                }
+                None => Err(Error::msg("a value was visited after the end of the value")),
+                Some(Frame::Some) => {
+                    self.stack.pop();
+                    self.push(Node::Some(Box::new(node)))
+                }
+                Some(Frame::Seq(elems)) => {
+                    elems.push(node);
+                    Ok(())
+                }
+                Some(Frame::Map(entries, key)) => {
+                    match key.take() {
+                        Some(key) => entries.push((key, node)),
+                        None => *key = Some(node),
+                    }
+
+                    Ok(())
+                }
+                Some(Frame::Record(fields, field @ Some(_))) => {
+                    fields.push((field.take().expect("the field is set"), node));
+                    Ok(())
+                }
+                Some(Frame::Variant(_, data @ None)) => {
+                    *data = Some(node);
+                    Ok(())
+                }
+                Some(_) => Err(Error::msg("a value was visited in an unexpected position")),
+            }
+        }
+
+        fn pop(&mut self) -> Result<Frame, Error> {
+            self.stack
+                .pop()
+                .ok_or_else(|| Error::msg("a value was completed without being started"))
+        }
+    }
+
+    impl<'v> Visitor<'v> for Capture {
+        fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+            self.push(Node::I64(v))
+        }
+
+        fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+            self.push(Node::U64(v))
+        }
+
+        fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+            self.push(Node::I128(v))
+        }
+
+        fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+            self.push(Node::U128(v))
+        }
+
+        fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+            self.push(Node::F64(v))
+        }
+
+        fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+            self.push(Node::Bool(v))
+        }
+
+        fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+            self.push(Node::Str(v.into()))
+        }
+
+        fn str_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+            self.stack
+                .push(Frame::Str(String::with_capacity(len.unwrap_or(0))));
+            Ok(())
+        }
+
+        fn visit_str_fragment(&mut self, v: &str) -> Result<(), Error> {
+            match self.stack.last_mut() {
+                Some(Frame::Str(s)) => {
+                    s.push_str(v);
+                    Ok(())
+                }
+                _ => Err(Error::msg(
+                    "a string fragment was visited outside of a string",
+                )),
+            }
+        }
+
+        fn str_end(&mut self) -> Result<(), Error> {
+            match self.pop()? {
+                Frame::Str(s) => self.push(Node::Str(s)),
+                _ => Err(Error::msg("a string was completed without being started")),
+            }
+        }
+
+        fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+            self.push(Node::Bytes(v.into()))
+        }
+
+        fn visit_none(&mut self) -> Result<(), Error> {
+            self.push(Node::None)
+        }
+
+        fn visit_some(&mut self) -> Result<(), Error> {
+            self.stack.push(Frame::Some);
+            Ok(())
+        }
+
+        fn visit_unit(&mut self) -> Result<(), Error> {
+            self.push(Node::Unit)
+        }
+
+        fn map_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+            self.stack
+                .push(Frame::Map(Vec::with_capacity(len.unwrap_or(0)), None));
+            Ok(())
+        }
+
+        fn map_end(&mut self) -> Result<(), Error> {
+            match self.pop()? {
+                Frame::Map(entries, None) => self.push(Node::Map(entries)),
+                _ => Err(Error::msg("a map was completed without being started")),
+            }
+        }
+
+        fn seq_begin(&mut self, len: Option<usize>) -> Result<(), Error> {
+            self.stack
+                .push(Frame::Seq(Vec::with_capacity(len.unwrap_or(0))));
+            Ok(())
+        }
+
+        fn seq_end(&mut self) -> Result<(), Error> {
+            match self.pop()? {
+                Frame::Seq(elems) => self.push(Node::Seq(elems)),
+                _ => Err(Error::msg("a sequence was completed without being started")),
+            }
+        }
+
+        fn record_begin(&mut self, _: &str, len: Option<usize>) -> Result<(), Error> {
+            self.stack
+                .push(Frame::Record(Vec::with_capacity(len.unwrap_or(0)), None));
+            Ok(())
+        }
+
+        fn record_field(&mut self, name: &str) -> Result<(), Error> {
+            match self.stack.last_mut() {
+                Some(Frame::Record(_, field @ None)) => {
+                    *field = Some(name.into());
+                    Ok(())
+                }
+                _ => Err(Error::msg("a record field was visited outside of a record")),
+            }
+        }
+
+        fn record_end(&mut self) -> Result<(), Error> {
+            match self.pop()? {
+                Frame::Record(fields, None) => self.push(Node::Record(fields)),
+                _ => Err(Error::msg("a record was completed without being started")),
+            }
+        }
+
+        fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+            self.push(Node::Variant(variant.into(), None))
+        }
+
+        fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+            self.stack.push(Frame::Variant(variant.into(), None));
+            Ok(())
+        }
+
+        fn variant_end(&mut self) -> Result<(), Error> {
+            match self.pop()? {
+                Frame::Variant(variant, Some(data)) => {
+                    self.push(Node::Variant(variant, Some(Box::new(data))))
+                }
+                _ => Err(Error::msg("a variant was completed without being started")),
+            }
+        }
+
+        fn visit_duration(&mut self, v: time::Duration) -> Result<(), Error> {
+            self.push(Node::Record(vec![
+                ("secs".into(), Node::U64(v.as_secs())),
+                ("nanos".into(), Node::U64(v.subsec_nanos() as u64)),
+            ]))
+        }
+
+        fn is_human_readable(&self) -> bool {
+            self.human_readable
+        }
+
+        fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+            self.push(Node::Str(fmt::format(*args)))
+        }
+    }
+
+    impl Serialize for Node {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            match self {
+                Node::Unit => serializer.serialize_unit(),
+                Node::None => serializer.serialize_none(),
+                Node::Some(v) => serializer.serialize_some(v),
+                Node::Bool(v) => serializer.serialize_bool(*v),
+                Node::I64(v) => serializer.serialize_i64(*v),
+                Node::U64(v) => serializer.serialize_u64(*v),
+                Node::I128(v) => serializer.serialize_i128(*v),
+                Node::U128(v) => serializer.serialize_u128(*v),
+                Node::F64(v) => serializer.serialize_f64(*v),
+                Node::Str(v) => serializer.serialize_str(v),
+                Node::Bytes(v) => serializer.serialize_bytes(v),
+                Node::Seq(elems) => {
+                    let mut seq = serializer.serialize_seq(Some(elems.len()))?;
+                    for elem in elems {
+                        seq.serialize_element(elem)?;
+                    }
+                    seq.end()
+                }
+                Node::Map(entries) => {
+                    let mut map = serializer.serialize_map(Some(entries.len()))?;
+                    for (k, v) in entries {
+                        map.serialize_entry(k, v)?;
+                    }
+                    map.end()
+                }
+                Node::Record(fields) => {
+                    let mut map = serializer.serialize_map(Some(fields.len()))?;
+                    for (k, v) in fields {
+                        map.serialize_entry(k, v)?;
+                    }
+                    map.end()
+                }
+                Node::Variant(variant, None) => serializer.serialize_str(variant),
+                Node::Variant(variant, Some(data)) => {
+                    let mut map = serializer.serialize_map(Some(1))?;
+                    map.serialize_entry(variant, data)?;
+                    map.end()
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "derive"))]
+mod tests {
+    use crate::{
+        tests::{assert_visit, Token},
+        Visit,
+    };
+
+    #[derive(Debug, Visit)]
+    struct Record<'a, T> {
+        id: u64,
+        name: &'a str,
+        r#type: Option<T>,
+    }
+
+    #[derive(Debug, Visit)]
+    struct Tuple(u64, bool);
+
+    #[derive(Debug, Visit)]
+    struct Newtype(u64);
+
+    #[derive(Debug, Visit)]
+    #[visit(tag = "id")]
+    struct Tagged(u64);
+
+    #[derive(Debug, Visit)]
+    struct Unit;
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_struct() {
+        assert_visit(
+            &Record {
+                id: 1,
+                name: "a",
+                r#type: Some(true),
+            },
+            &[
+                Token::RecordBegin("Record", Some(3)),
+                Token::RecordField("id"),
+                Token::U64(1),
+                Token::RecordField("name"),
+                Token::Str("a"),
+                Token::RecordField("type"),
+                Token::Some,
+                Token::Bool(true),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &Tuple(1, true),
+            &[
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::Bool(true),
+                Token::SeqEnd,
+            ],
+        );
+
+        assert_visit(&Newtype(1), &[Token::U64(1)]);
+        assert_visit(&Tagged(1), &[Token::Tag("id"), Token::U64(1)]);
+        assert_visit(&Unit, &[Token::Unit]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn derive_struct_serde() {
+        assert_eq!(
+            r#"{"id":1,"name":"a","type":null}"#,
+            serde_json::to_string(&Record::<bool> {
+                id: 1,
+                name: "a",
+                r#type: None
+            })
+            .unwrap(),
+        );
+
+        assert_eq!("[1,true]", serde_json::to_string(&Tuple(1, true)).unwrap());
+        assert_eq!("1", serde_json::to_string(&Newtype(1)).unwrap());
+        assert_eq!("null", serde_json::to_string(&Unit).unwrap());
+
+        assert_visit(&Newtype(1), &[Token::U64(1)]);
+        assert_visit(&Tagged(1), &[Token::U64(1)]);
+    }
+
+    #[derive(Debug, Visit)]
+    enum External {
+        Unit,
+        Newtype(u64),
+        Tuple(u64, bool),
+        Record { a: u64 },
+    }
+
+    #[derive(Debug, Visit)]
+    #[visit(tag = "t")]
+    enum Internal {
+        Unit,
+        Newtype(Record<'static, u64>),
+        Record { a: u64 },
+    }
+
+    #[derive(Debug, Visit)]
+    #[visit(tag = "t", content = "c")]
+    enum Adjacent {
+        Unit,
+        Newtype(u64),
+    }
+
+    #[derive(Debug, Visit)]
+    #[visit(untagged)]
+    enum Untagged {
+        Unit,
+        Newtype(u64),
+        Record { a: u64 },
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_enum_external() {
+        assert_visit(&External::Unit, &[Token::Variant("External", "Unit", 0)]);
+
+        assert_visit(
+            &External::Newtype(1),
+            &[
+                Token::VariantBegin("External", "Newtype", 1),
+                Token::U64(1),
+                Token::VariantEnd,
+            ],
+        );
+
+        assert_visit(
+            &External::Tuple(1, true),
+            &[
+                Token::VariantBegin("External", "Tuple", 2),
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::U64(1),
+                Token::SeqElem,
+                Token::Bool(true),
+                Token::SeqEnd,
+                Token::VariantEnd,
+            ],
+        );
+
+        assert_visit(
+            &External::Record { a: 1 },
+            &[
+                Token::VariantBegin("External", "Record", 3),
+                Token::RecordBegin("Record", Some(1)),
+                Token::RecordField("a"),
+                Token::U64(1),
+                Token::RecordEnd,
+                Token::VariantEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_enum_internal() {
+        assert_visit(
+            &Internal::Unit,
+            &[
+                Token::RecordBegin("Internal", Some(1)),
+                Token::RecordField("t"),
+                Token::Str("Unit"),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &Internal::Newtype(Record {
+                id: 1,
+                name: "a",
+                r#type: None,
+            }),
+            &[
+                Token::RecordBegin("Internal", None),
+                Token::RecordField("t"),
+                Token::Str("Newtype"),
+                Token::RecordField("id"),
+                Token::U64(1),
+                Token::RecordField("name"),
+                Token::Str("a"),
+                Token::RecordField("type"),
+                Token::None,
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &Internal::Record { a: 1 },
+            &[
+                Token::RecordBegin("Internal", Some(2)),
+                Token::RecordField("t"),
+                Token::Str("Record"),
+                Token::RecordField("a"),
+                Token::U64(1),
+                Token::RecordEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_enum_adjacent_untagged() {
+        assert_visit(
+            &Adjacent::Unit,
+            &[
+                Token::RecordBegin("Adjacent", Some(1)),
+                Token::RecordField("t"),
+                Token::Str("Unit"),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &Adjacent::Newtype(1),
+            &[
+                Token::RecordBegin("Adjacent", Some(2)),
+                Token::RecordField("t"),
+                Token::Str("Newtype"),
+                Token::RecordField("c"),
+                Token::U64(1),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(&Untagged::Unit, &[Token::Unit]);
+        assert_visit(&Untagged::Newtype(1), &[Token::U64(1)]);
+        assert_visit(
+            &Untagged::Record { a: 1 },
+            &[
+                Token::RecordBegin("Record", Some(1)),
+                Token::RecordField("a"),
+                Token::U64(1),
+                Token::RecordEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn derive_enum_serde() {
+        let json = |v: &dyn Visit| serde_json::to_string(v).unwrap();
+
+        assert_eq!(r#""Unit""#, json(&External::Unit));
+        assert_eq!(r#"{"Newtype":1}"#, json(&External::Newtype(1)));
+        assert_eq!(r#"{"Tuple":[1,true]}"#, json(&External::Tuple(1, true)));
+        assert_eq!(r#"{"Record":{"a":1}}"#, json(&External::Record { a: 1 }));
+
+        assert_eq!(r#"{"t":"Unit"}"#, json(&Internal::Unit));
+        assert_eq!(
+            r#"{"t":"Newtype","id":1,"name":"a","type":null}"#,
+            json(&Internal::Newtype(Record {
+                id: 1,
+                name: "a",
+                r#type: None
+            })),
+        );
+        assert_eq!(r#"{"t":"Record","a":1}"#, json(&Internal::Record { a: 1 }));
+
+        assert_eq!(r#"{"t":"Unit"}"#, json(&Adjacent::Unit));
+        assert_eq!(r#"{"t":"Newtype","c":1}"#, json(&Adjacent::Newtype(1)));
+
+        assert_eq!("null", json(&Untagged::Unit));
+        assert_eq!("1", json(&Untagged::Newtype(1)));
+        assert_eq!(r#"{"a":1}"#, json(&Untagged::Record { a: 1 }));
+    }
+
+    #[derive(Debug, Visit)]
+    #[visit(rename = "renamed", rename_all = "camelCase")]
+    struct Renamed {
+        first_field: u64,
+        #[visit(rename = "second")]
+        second_field: u64,
+        #[visit(skip)]
+        #[allow(dead_code)]
+        skipped: u64,
+        #[visit(skip_if = "Option::is_none")]
+        maybe: Option<u64>,
+    }
+
+    #[derive(Debug, Visit)]
+    #[visit(rename_all = "snake_case")]
+    enum RenamedEnum {
+        UnitVariant,
+        #[visit(rename = "rec", rename_all = "UPPERCASE")]
+        Record {
+            a_b: u64,
+            #[visit(skip)]
+            #[allow(dead_code)]
+            skipped: u64,
+        },
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_attrs() {
+        assert_visit(
+            &Renamed {
+                first_field: 1,
+                second_field: 2,
+                skipped: 3,
+                maybe: None,
+            },
+            &[
+                Token::RecordBegin("renamed", Some(2)),
+                Token::RecordField("firstField"),
+                Token::U64(1),
+                Token::RecordField("second"),
+                Token::U64(2),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &Renamed {
+                first_field: 1,
+                second_field: 2,
+                skipped: 3,
+                maybe: Some(4),
+            },
+            &[
+                Token::RecordBegin("renamed", Some(3)),
+                Token::RecordField("firstField"),
+                Token::U64(1),
+                Token::RecordField("second"),
+                Token::U64(2),
+                Token::RecordField("maybe"),
+                Token::Some,
+                Token::U64(4),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &RenamedEnum::UnitVariant,
+            &[Token::Variant("RenamedEnum", "unit_variant", 0)],
+        );
+
+        assert_visit(
+            &RenamedEnum::Record { a_b: 1, skipped: 2 },
+            &[
+                Token::VariantBegin("RenamedEnum", "rec", 1),
+                Token::RecordBegin("rec", Some(1)),
+                Token::RecordField("A_B"),
+                Token::U64(1),
+                Token::RecordEnd,
+                Token::VariantEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn derive_attrs_serde() {
+        let json = |v: &dyn Visit| serde_json::to_string(v).unwrap();
+
+        assert_eq!(
+            r#"{"firstField":1,"second":2}"#,
+            json(&Renamed {
+                first_field: 1,
+                second_field: 2,
+                skipped: 3,
+                maybe: None
+            }),
+        );
+        assert_eq!(
+            r#"{"firstField":1,"second":2,"maybe":4}"#,
+            json(&Renamed {
+                first_field: 1,
+                second_field: 2,
+                skipped: 3,
+                maybe: Some(4)
+            }),
+        );
+
+        assert_eq!(r#""unit_variant""#, json(&RenamedEnum::UnitVariant));
+        assert_eq!(
+            r#"{"rec":{"A_B":1}}"#,
+            json(&RenamedEnum::Record { a_b: 1, skipped: 2 })
+        );
+    }
+
+    #[derive(Debug, Visit)]
+    struct Context {
+        trace: u64,
+        span: u64,
+    }
+
+    #[derive(Debug, Visit)]
+    struct Flattened {
+        msg: &'static str,
+        #[visit(flatten)]
+        context: Context,
+        #[visit(flatten)]
+        tenant: Option<Context>,
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_flatten() {
+        assert_visit(
+            &Flattened {
+                msg: "a",
+                context: Context { trace: 1, span: 2 },
+                tenant: None,
+            },
+            &[
+                Token::RecordBegin("Flattened", None),
+                Token::RecordField("msg"),
+                Token::Str("a"),
+                Token::RecordField("trace"),
+                Token::U64(1),
+                Token::RecordField("span"),
+                Token::U64(2),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(
+            &Flattened {
+                msg: "a",
+                context: Context { trace: 1, span: 2 },
+                tenant: Some(Context { trace: 3, span: 4 }),
+            },
+            &[
+                Token::RecordBegin("Flattened", None),
+                Token::RecordField("msg"),
+                Token::Str("a"),
+                Token::RecordField("trace"),
+                Token::U64(1),
+                Token::RecordField("span"),
+                Token::U64(2),
+                Token::RecordField("trace"),
+                Token::U64(3),
+                Token::RecordField("span"),
+                Token::U64(4),
+                Token::RecordEnd,
+            ],
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn derive_flatten_serde() {
+        assert_eq!(
+            r#"{"msg":"a","trace":1,"span":2}"#,
+            serde_json::to_string(&Flattened {
+                msg: "a",
+                context: Context { trace: 1, span: 2 },
+                tenant: None,
+            })
+            .unwrap(),
+        );
+    }
+
+    #[derive(Debug)]
+    struct Millis(u64);
+
+    fn millis<'v>(v: &'v Millis, visitor: &mut dyn crate::Visitor<'v>) -> Result<(), crate::Error> {
+        visitor.visit_u64(v.0)
+    }
+
+    fn hex<'v>(v: &'v u32, visitor: &mut dyn crate::Visitor<'v>) -> Result<(), crate::Error> {
+        visitor.visit_display(&format_args!("{:x}", v))
+    }
+
+    #[derive(Debug, Visit)]
+    struct With {
+        #[visit(with = "millis")]
+        at: Millis,
+        #[visit(with = "hex")]
+        id: u32,
+    }
+
+    #[derive(Debug, Visit)]
+    struct WithNewtype(#[visit(with = "millis")] Millis);
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn derive_with() {
+        assert_visit(
+            &With {
+                at: Millis(1),
+                id: 255,
+            },
+            &[
+                Token::RecordBegin("With", Some(2)),
+                Token::RecordField("at"),
+                Token::U64(1),
+                Token::RecordField("id"),
+                Token::Display("ff"),
+                Token::RecordEnd,
+            ],
+        );
+
+        assert_visit(&WithNewtype(Millis(1)), &[Token::U64(1)]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn derive_with_serde() {
+        assert_eq!(
+            r#"{"at":1,"id":"ff"}"#,
+            serde_json::to_string(&With {
+                at: Millis(1),
+                id: 255
+            })
+            .unwrap(),
+        );
+        assert_eq!("1", serde_json::to_string(&WithNewtype(Millis(1))).unwrap());
+    }
+}
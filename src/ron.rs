@@ -0,0 +1,468 @@
+//! A RON (Rusty Object Notation) serializer for visited values.
+//!
+//! Records are written as named structs, like `Point(x:1,y:2)`, and enum
+//! variants by their names, like `Circle(1.0)`. Sequences are written as
+//! lists, maps as maps, and optional values as `Some(..)` or `None`.
+//! Values that RON has no representation for, like durations and
+//! timestamps, are written as strings.
+//!
+//! The output can be parsed by the `ron` crate, but not every value can be
+//! read back as the type it was visited from. Tuples and tuple structs are
+//! visited as sequences, so they're written as lists instead of `(..)`, and
+//! newtype structs are written as the value they wrap instead of `Name(..)`.
+
+use crate::std::{fmt, mem};
+
+use crate::{json::is_number, Capability, Error, TextWriter, Visitor};
+
+const MAX_DEPTH: usize = 64;
+
+/// A visitor that writes values as compact RON.
+///
+/// ```
+/// let mut ron = ser::ron::Writer::new(String::new());
+/// ser::Visit::visit(&[Some(1), None][..], &mut ron).unwrap();
+///
+/// assert_eq!("[Some(1),None]", ron.into_inner());
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    first: bool,
+    inline: bool,
+    depth: usize,
+    frames: [Frame; MAX_DEPTH + 1],
+}
+
+/// The state of a level of nesting.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    // Whether the container was written inside the parens of its variant
+    inline: bool,
+    // The number of `Some(` parens to close when the current value is done
+    somes: usize,
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            first: true,
+            inline: false,
+            depth: 0,
+            frames: [Frame {
+                inline: false,
+                somes: 0,
+            }; MAX_DEPTH + 1],
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Begin a value, returning whether it's the data of a variant.
+    fn value(&mut self) -> bool {
+        mem::replace(&mut self.inline, false)
+    }
+
+    /// Write a complete value.
+    fn scalar(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        self.value();
+        self.out.write_fmt(v)?;
+
+        self.done()
+    }
+
+    /// Write a quoted and escaped string or character.
+    fn string(&mut self, quote: char, v: fmt::Arguments) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        self.value();
+
+        self.out.write_char(quote)?;
+        Escape(&mut self.out, quote).write_fmt(v)?;
+        self.out.write_char(quote)?;
+
+        self.done()
+    }
+
+    /// Close any `Some(` parens around a value that's been completed.
+    fn done(&mut self) -> Result<(), Error> {
+        for _ in 0..mem::replace(&mut self.frames[self.depth].somes, 0) {
+            self.out.write_char(')')?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a separator before an element, field, or key.
+    fn elem(&mut self) -> Result<(), Error> {
+        if !mem::replace(&mut self.first, false) {
+            self.out.write_char(',')?;
+        }
+
+        Ok(())
+    }
+
+    /// Begin a container.
+    ///
+    /// Sequences and records that are the data of a variant are written
+    /// inside its parens instead of with their own delimiters.
+    fn begin(&mut self, open: fmt::Arguments, inlinable: bool) -> Result<(), Error> {
+        let inline = self.value() && inlinable;
+
+        if self.depth == MAX_DEPTH {
+            return Err(Error::msg("values are nested too deeply"));
+        }
+
+        self.depth += 1;
+        self.frames[self.depth] = Frame { inline, somes: 0 };
+        self.first = true;
+
+        if !inline {
+            self.out.write_fmt(open)?;
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self, close: char) -> Result<(), Error> {
+        let frame = self.frames[self.depth];
+
+        self.depth -= 1;
+        self.first = false;
+
+        if !frame.inline {
+            self.out.write_char(close)?;
+        }
+
+        self.done()
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.scalar(format_args!("{}", v))
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.scalar(format_args!("{}", v))
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.scalar(format_args!("{}", v))
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.scalar(format_args!("{}", v))
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        // `Debug` always includes a fractional part or exponent, and
+        // writes non-finite numbers as `NaN`, `inf`, and `-inf` like RON
+        self.scalar(format_args!("{:?}", v))
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        if is_number(v) {
+            self.scalar(format_args!("{}", v))
+        } else {
+            self.string('"', format_args!("{}", v))
+        }
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.scalar(format_args!("{}", v))
+    }
+
+    fn visit_char(&mut self, v: char) -> Result<(), Error> {
+        self.string('\'', format_args!("{}", v))
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.string('"', format_args!("{}", v))
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.begin(format_args!("["), false)?;
+
+        for b in v {
+            self.elem()?;
+            self.scalar(format_args!("{}", b))?;
+        }
+
+        self.end(']')
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.scalar(format_args!("None"))
+    }
+
+    fn visit_some(&mut self) -> Result<(), Error> {
+        self.value();
+        self.out.write_str("Some(")?;
+        self.frames[self.depth].somes += 1;
+
+        Ok(())
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.scalar(format_args!("()"))
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin(format_args!("{{"), false)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.elem()
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.out.write_char(':')?;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end('}')
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin(format_args!("["), true)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.elem()
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end(']')
+    }
+
+    fn record_begin(&mut self, name: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin(format_args!("{}(", name), true)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.elem()?;
+        self.out.write_str(name)?;
+        self.out.write_char(':')?;
+
+        Ok(())
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end(')')
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.scalar(format_args!("{}", variant))
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.begin(format_args!("{}(", variant), false)?;
+        self.inline = true;
+
+        Ok(())
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.end(')')
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(capability, Capability::NumberStr | Capability::NonFinite)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.string('"', *args)
+    }
+}
+
+/// Escape a string written into a RON string or character.
+struct Escape<W>(W, char);
+
+impl<W> fmt::Write for Escape<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            match c {
+                '\\' => self.0.write_str("\\\\")?,
+                '\n' => self.0.write_str("\\n")?,
+                '\r' => self.0.write_str("\\r")?,
+                '\t' => self.0.write_str("\\t")?,
+                '\0' => self.0.write_str("\\0")?,
+                c if c == self.1 => {
+                    self.0.write_char('\\')?;
+                    self.0.write_char(c)?;
+                }
+                c if c.is_control() => write!(self.0, "\\u{{{:x}}}", c as u32)?,
+                c => self.0.write_char(c)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_ron_with(
+        visit: impl FnOnce(&mut Writer<VisitArgs>) -> Result<(), Error>,
+        expected: &str,
+    ) {
+        let mut ron = Writer::new(VisitArgs::new());
+        visit(&mut ron).unwrap();
+
+        assert_eq!(Some(expected), ron.into_inner().to_str());
+    }
+
+    fn assert_ron(v: &dyn Visit, expected: &str) {
+        assert_ron_with(|ron| v.visit(ron), expected)
+    }
+
+    #[test]
+    fn write_primitives() {
+        assert_ron(&1, "1");
+        assert_ron(&1.0, "1.0");
+        assert_ron(&f64::NAN, "NaN");
+        assert_ron(&true, "true");
+        assert_ron(&'\'', r"'\''");
+        assert_ron(&"a\"b\n\u{1}", r#""a\"b\n\u{1}""#);
+        assert_ron(&(), "()");
+        assert_ron(&None::<u8>, "None");
+        assert_ron(&Some(Some(1)), "Some(Some(1))");
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_ron(
+            &crate::value!({ "a": [1, 2], "b": {} }),
+            r#"{"a":[1,2],"b":{}}"#,
+        );
+
+        assert_ron_with(
+            |ron| {
+                ron.record_begin("Point", Some(2))?;
+                ron.record_field("x")?;
+                ron.visit_some()?;
+                ron.seq_begin(Some(1))?;
+                ron.seq_elem()?;
+                ron.visit_u64(1)?;
+                ron.seq_end()?;
+                ron.record_field("y")?;
+                ron.visit_none()?;
+                ron.record_end()
+            },
+            "Point(x:Some([1]),y:None)",
+        );
+    }
+
+    #[test]
+    fn write_variants() {
+        assert_ron_with(|ron| ron.visit_variant("Shape", "Empty", 0), "Empty");
+
+        assert_ron_with(
+            |ron| {
+                ron.variant_begin("Shape", "Circle", 1)?;
+                ron.visit_f64(1.0)?;
+                ron.variant_end()
+            },
+            "Circle(1.0)",
+        );
+
+        assert_ron_with(
+            |ron| {
+                ron.variant_begin("Shape", "Line", 2)?;
+                ron.seq_begin(Some(2))?;
+                ron.seq_elem()?;
+                ron.visit_u64(1)?;
+                ron.seq_elem()?;
+                ron.visit_u64(2)?;
+                ron.seq_end()?;
+                ron.variant_end()
+            },
+            "Line(1,2)",
+        );
+
+        assert_ron_with(
+            |ron| {
+                ron.visit_some()?;
+                ron.variant_begin("Shape", "Rect", 3)?;
+                ron.record_begin("Rect", Some(1))?;
+                ron.record_field("w")?;
+                ron.variant_begin("Size", "Px", 0)?;
+                ron.visit_some()?;
+                ron.visit_u64(1)?;
+                ron.variant_end()?;
+                ron.record_end()?;
+                ron.variant_end()
+            },
+            "Some(Rect(w:Px(Some(1))))",
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn write_read_back() {
+        use crate::std::{collections::BTreeMap, string::String, vec};
+
+        fn to_ron(v: &dyn Visit) -> String {
+            let mut ron = Writer::new(String::new());
+            v.visit(&mut ron).unwrap();
+
+            ron.into_inner()
+        }
+
+        let v = vec![Some(1.5), None, Some(f64::INFINITY)];
+        assert_eq!(v, ::ron::from_str::<Vec<Option<f64>>>(&to_ron(&v)).unwrap());
+
+        let v = vec![String::from("a\"b\n\u{1}"), String::from("'\\")];
+        assert_eq!(v, ::ron::from_str::<Vec<String>>(&to_ron(&v)).unwrap());
+
+        let v = vec!['\'', '"', '\0'];
+        assert_eq!(v, ::ron::from_str::<Vec<char>>(&to_ron(&v)).unwrap());
+
+        let mut v = BTreeMap::new();
+        v.insert(String::from("a"), vec![Some(1u8)]);
+        v.insert(String::from("b"), vec![]);
+        assert_eq!(
+            v,
+            ::ron::from_str::<BTreeMap<String, Vec<Option<u8>>>>(&to_ron(&v)).unwrap()
+        );
+
+        // Tuple structs are visited as sequences, which `ron` won't read as tuples
+        let v = vec![1u8, 2];
+        assert_eq!("[1,2]", to_ron(&v));
+        assert!(::ron::from_str::<(u8, u8)>(&to_ron(&v)).is_err());
+    }
+}
@@ -0,0 +1,305 @@
+//! A generic, non-object-safe counterpart to `Visitor`.
+//!
+//! A `Visitor` is always called through a trait object, which costs a virtual
+//! call for each part of a value. A `Stream` is called generically instead, so
+//! backends can be monomorphized for the values they stream. Trait object users
+//! can keep using `Visitor`, which wraps a `Stream` through `Streamer`.
+
+use crate::std::{any::Any, fmt, time};
+
+use crate::{BytesHint, Capability, Error, Visit, Visitor};
+
+/// A generic visitor that produces a result.
+///
+/// The methods on `Stream` are the same as the ones on `Visitor`,
+/// but can return the stream's own error type.
+pub trait Stream<'v> {
+    /// The result of streaming a value.
+    type Ok;
+
+    /// The error returned when streaming fails.
+    type Error: From<Error>;
+
+    visitor_methods!(Self::Error);
+
+    /// Complete the stream, producing its result.
+    fn end(self) -> Result<Self::Ok, Self::Error>
+    where
+        Self: Sized;
+}
+
+/// Stream a value, producing the stream's result.
+///
+/// Values are streamed generically where possible,
+/// falling back to a `Visitor` otherwise.
+pub fn stream<'v, T, S>(value: &'v T, mut stream: S) -> Result<S::Ok, S::Error>
+where
+    T: Visit,
+    S: Stream<'v>,
+{
+    value.stream(&mut stream)?;
+    stream.end()
+}
+
+/// A `Visitor` that wraps a `Stream`.
+///
+/// Errors returned by the stream are retained so they can be
+/// returned to the caller after visiting.
+pub struct Streamer<'a, 'v, S>
+where
+    S: Stream<'v>,
+{
+    stream: &'a mut S,
+    error: Option<S::Error>,
+}
+
+impl<'a, 'v, S> Streamer<'a, 'v, S>
+where
+    S: Stream<'v>,
+{
+    /// Wrap a stream.
+    pub fn new(stream: &'a mut S) -> Self {
+        Streamer {
+            stream,
+            error: None,
+        }
+    }
+
+    /// Visit a value with the wrapped stream.
+    pub fn visit<T>(mut self, value: &'v T) -> Result<(), S::Error>
+    where
+        T: ?Sized + Visit,
+    {
+        let r = value.visit(&mut self);
+        self.into_result(r)
+    }
+
+    /// Get the result of streaming from the result of visiting.
+    pub fn into_result(self, r: Result<(), Error>) -> Result<(), S::Error> {
+        match (self.error, r) {
+            (Some(err), _) => Err(err),
+            (None, Err(err)) => Err(err.into()),
+            (None, Ok(())) => Ok(()),
+        }
+    }
+
+    fn check(&mut self, r: Result<(), S::Error>) -> Result<(), Error> {
+        r.map_err(|err| {
+            self.error = Some(err);
+            Error::msg("the stream failed")
+        })
+    }
+}
+
+impl<'a, 'v, S> fmt::Debug for Streamer<'a, 'v, S>
+where
+    S: Stream<'v> + fmt::Debug,
+    S::Error: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Streamer")
+            .field("stream", &self.stream)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+/// Forward `Visitor` methods to the wrapped stream, retaining its errors.
+macro_rules! forward {
+    ($($(#[$attr:meta])* fn $method:ident(&mut self $(, $arg:ident: $ty:ty)*);)*) => {
+        $(
+            $(#[$attr])*
+            fn $method(&mut self $(, $arg: $ty)*) -> Result<(), Error> {
+                let r = self.stream.$method($($arg),*);
+                self.check(r)
+            }
+        )*
+    };
+}
+
+impl<'a, 'v, S> Visitor<'v> for Streamer<'a, 'v, S>
+where
+    S: Stream<'v>,
+{
+    forward! {
+        fn visit_i8(&mut self, v: i8);
+        fn visit_i16(&mut self, v: i16);
+        fn visit_i32(&mut self, v: i32);
+        fn visit_u8(&mut self, v: u8);
+        fn visit_u16(&mut self, v: u16);
+        fn visit_u32(&mut self, v: u32);
+        fn visit_i64(&mut self, v: i64);
+        fn visit_u64(&mut self, v: u64);
+        fn visit_i128(&mut self, v: i128);
+        fn visit_u128(&mut self, v: u128);
+        fn visit_f64(&mut self, v: f64);
+        fn visit_f64_nonfinite(&mut self, v: f64);
+        fn visit_number_str(&mut self, v: &str);
+        fn visit_bool(&mut self, v: bool);
+        fn visit_char(&mut self, v: char);
+        fn visit_str(&mut self, v: &str);
+        fn visit_borrowed_str(&mut self, v: &'v str);
+        fn str_begin(&mut self, len: Option<usize>);
+        fn visit_str_fragment(&mut self, v: &str);
+        fn str_end(&mut self);
+        fn visit_bytes(&mut self, v: &[u8]);
+        fn visit_borrowed_bytes(&mut self, v: &'v [u8]);
+        fn visit_bytes_hint(&mut self, v: &[u8], hint: BytesHint);
+        fn visit_none(&mut self);
+        fn visit_some(&mut self);
+        fn visit_unit(&mut self);
+        fn map_begin(&mut self, len: Option<usize>);
+        fn map_key(&mut self);
+        fn map_value(&mut self);
+        fn map_end(&mut self);
+        fn seq_begin(&mut self, len: Option<usize>);
+        fn seq_elem(&mut self);
+        fn seq_end(&mut self);
+        fn record_begin(&mut self, name: &str, len: Option<usize>);
+        fn record_field(&mut self, name: &str);
+        fn record_end(&mut self);
+        fn visit_variant(&mut self, name: &str, variant: &str, index: u32);
+        fn variant_begin(&mut self, name: &str, variant: &str, index: u32);
+        fn variant_end(&mut self);
+        fn visit_tag(&mut self, tag: &str);
+        fn visit_duration(&mut self, v: time::Duration);
+        fn visit_timestamp(&mut self, secs: i64, nanos: u32);
+        fn visit_display(&mut self, v: &dyn fmt::Display);
+        fn visit_debug(&mut self, v: &dyn fmt::Debug);
+        #[cfg(feature = "std")]
+        fn visit_error(&mut self, v: &(dyn std::error::Error + 'static));
+        fn visit_fallback(&mut self, v: &dyn fmt::Debug);
+        fn visit_fmt(&mut self, args: &fmt::Arguments);
+    }
+
+    fn context(&self) -> Option<&(dyn Any + 'static)> {
+        self.stream.context()
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        self.stream.supports(capability)
+    }
+
+    fn is_human_readable(&self) -> bool {
+        self.stream.is_human_readable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq)]
+    enum StreamError {
+        Full,
+        Visit,
+    }
+
+    impl From<Error> for StreamError {
+        fn from(_: Error) -> Self {
+            StreamError::Visit
+        }
+    }
+
+    struct Sum {
+        sum: u64,
+        max: u64,
+    }
+
+    impl<'v> Stream<'v> for Sum {
+        type Ok = u64;
+        type Error = StreamError;
+
+        fn visit_u64(&mut self, v: u64) -> Result<(), Self::Error> {
+            self.sum += v;
+
+            if self.sum > self.max {
+                return Err(StreamError::Full);
+            }
+
+            Ok(())
+        }
+
+        // Flattened fields are visited as map keys through `serde_interop`
+        fn visit_str(&mut self, _: &str) -> Result<(), Self::Error> {
+            Ok(())
+        }
+
+        fn visit_fmt(&mut self, _: &fmt::Arguments) -> Result<(), Self::Error> {
+            Err(StreamError::Visit)
+        }
+
+        fn end(self) -> Result<Self::Ok, Self::Error> {
+            Ok(self.sum)
+        }
+    }
+
+    #[test]
+    fn stream_generic() {
+        assert_eq!(Ok(3), stream(&Some(3u8), Sum { sum: 0, max: 5 }));
+        assert_eq!(
+            Err(StreamError::Full),
+            stream(&Some(6u8), Sum { sum: 0, max: 5 })
+        );
+        assert_eq!(
+            Err(StreamError::Visit),
+            stream(&true, Sum { sum: 0, max: 5 })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "std")]
+    fn stream_collections() {
+        let mut map = crate::std::collections::BTreeMap::new();
+        map.insert(1u16, 2u16);
+
+        assert_eq!(Ok(6), stream(&vec![1u16, 2, 3], Sum { sum: 0, max: 10 }));
+        assert_eq!(Ok(3), stream(&map, Sum { sum: 0, max: 10 }));
+        assert_eq!(
+            Err(StreamError::Full),
+            stream(&vec![5u16, 6], Sum { sum: 0, max: 10 })
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "derive")]
+    fn stream_derive() {
+        #[derive(Debug, crate::Visit)]
+        struct Inner {
+            a: u16,
+        }
+
+        #[derive(Debug, crate::Visit)]
+        struct Outer {
+            #[visit(flatten)]
+            inner: Inner,
+            #[visit(with = "double")]
+            b: u16,
+            c: Vec<u16>,
+        }
+
+        fn double<'v>(v: &'v u16, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+            visitor.visit_u16(*v * 2)
+        }
+
+        let v = Outer {
+            inner: Inner { a: 1 },
+            b: 2,
+            c: vec![3],
+        };
+
+        assert_eq!(Ok(8), stream(&v, Sum { sum: 0, max: 10 }));
+        assert_eq!(Err(StreamError::Full), stream(&v, Sum { sum: 0, max: 5 }));
+    }
+
+    #[test]
+    fn stream_visitor() {
+        let mut sum = Sum { sum: 0, max: 5 };
+
+        Streamer::new(&mut sum).visit(&3u8 as &dyn Visit).unwrap();
+        assert_eq!(
+            Err(StreamError::Full),
+            Streamer::new(&mut sum).visit(&3u8 as &dyn Visit)
+        );
+    }
+}
@@ -0,0 +1,273 @@
+//! A plain text serializer for visited values.
+//!
+//! Single values are written as-is, and flat records and maps are written
+//! as `key=value` lines, like a properties file. Nothing is quoted or
+//! escaped, so the output is for humans to read rather than for parsing.
+//! Optional and unit values are written as empty strings.
+
+use crate::std::{fmt, mem};
+
+use crate::{Error, TextWriter, Visitor};
+
+/// A visitor that writes single values and flat maps as plain text.
+///
+/// ```
+/// let mut text = ser::text::Writer::new(String::new());
+///
+/// let value = ser::value!({ "host": "localhost", "port": 8080 });
+/// ser::Visit::visit(&value, &mut text).unwrap();
+///
+/// assert_eq!("host=localhost\nport=8080", text.into_inner());
+/// ```
+///
+/// Use `Writer::separators` to change what keys, values, and entries are separated by.
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    first: bool,
+    key: bool,
+    nested: bool,
+    separators: Separators,
+}
+
+/// The separators between keys, values, and entries in plain text.
+///
+/// ```
+/// let separators = ser::text::Separators::new().key(": ").entry(", ");
+///
+/// let mut text = ser::text::Writer::separators(String::new(), separators);
+/// ser::Visit::visit(&ser::value!({ "a": 1, "b": 2 }), &mut text).unwrap();
+///
+/// assert_eq!("a: 1, b: 2", text.into_inner());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Separators {
+    key: &'static str,
+    entry: &'static str,
+}
+
+impl Separators {
+    /// Separate keys from values with `=`, and entries with newlines.
+    pub fn new() -> Self {
+        Separators {
+            key: "=",
+            entry: "\n",
+        }
+    }
+
+    /// Set the string between a key and its value.
+    pub fn key(self, key: &'static str) -> Self {
+        Separators { key, ..self }
+    }
+
+    /// Set the string between entries.
+    pub fn entry(self, entry: &'static str) -> Self {
+        Separators { entry, ..self }
+    }
+}
+
+impl Default for Separators {
+    fn default() -> Self {
+        Separators::new()
+    }
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer::separators(out, Separators::new())
+    }
+
+    /// Create a writer over the given output with the given separators.
+    pub fn separators(out: W, separators: Separators) -> Self {
+        Writer {
+            out,
+            first: true,
+            key: false,
+            nested: false,
+            separators,
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write a key or value.
+    fn value(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        self.key = false;
+        self.out.write_fmt(v)?;
+
+        Ok(())
+    }
+
+    /// Write a separator before an entry.
+    fn entry(&mut self) -> Result<(), Error> {
+        if !mem::replace(&mut self.first, false) {
+            self.out.write_str(self.separators.entry)?;
+        }
+
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        if self.nested || self.key {
+            return self.unsupported();
+        }
+
+        self.nested = true;
+        self.first = true;
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        self.nested = false;
+
+        Ok(())
+    }
+
+    fn unsupported(&mut self) -> Result<(), Error> {
+        Err(Error::msg(
+            "only single values and flat maps can be written as text",
+        ))
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.value(format_args!("{}", v))
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.value(format_args!(""))
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.entry()?;
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.out.write_str(self.separators.key)?;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.unsupported()
+    }
+
+    fn record_begin(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.entry()?;
+        self.out.write_str(name)?;
+        self.out.write_str(self.separators.key)?;
+
+        Ok(())
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn variant_begin(&mut self, _: &str, _: &str, _: u32) -> Result<(), Error> {
+        self.unsupported()
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.value(*args)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_text(v: &dyn Visit, separators: Separators, expected: &str) {
+        let mut text = Writer::separators(VisitArgs::new(), separators);
+        v.visit(&mut text).unwrap();
+
+        assert_eq!(Some(expected), text.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_value() {
+        assert_text(&1, Separators::new(), "1");
+        assert_text(&"a b", Separators::new(), "a b");
+        assert_text(&None::<u8>, Separators::new(), "");
+    }
+
+    #[test]
+    fn write_map() {
+        let value = crate::value!({ "a": 1, "b": "c d", "e": null });
+
+        assert_text(&value, Separators::new(), "a=1\nb=c d\ne=");
+        assert_text(
+            &value,
+            Separators::new().key(" = ").entry("; "),
+            "a = 1; b = c d; e = ",
+        );
+        assert_text(&crate::value!({}), Separators::new(), "");
+
+        let mut text = Writer::new(VisitArgs::new());
+        text.record_begin("Record", Some(2)).unwrap();
+        text.record_field("a").unwrap();
+        text.visit_variant("Enum", "A", 0).unwrap();
+        text.record_field("b").unwrap();
+        text.visit_f64(1.5).unwrap();
+        text.record_end().unwrap();
+
+        assert_eq!(Some("a=A\nb=1.5"), text.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_nested() {
+        assert!(crate::value!({ "a": { "b": 1 } })
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!(crate::value!({ "a": [1] })
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!([1][..].visit(&mut Writer::new(VisitArgs::new())).is_err());
+    }
+}
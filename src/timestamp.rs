@@ -0,0 +1,75 @@
+use crate::std::fmt;
+
+/// Format a timestamp as an RFC3339 string in UTC.
+pub(crate) struct Rfc3339 {
+    pub(crate) secs: i64,
+    pub(crate) nanos: u32,
+}
+
+impl fmt::Display for Rfc3339 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let days = self.secs.div_euclid(86_400);
+        let secs_of_day = self.secs.rem_euclid(86_400);
+
+        let (year, month, day) = civil_from_days(days);
+
+        write!(
+            f,
+            "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}",
+            year,
+            month,
+            day,
+            secs_of_day / 3_600,
+            secs_of_day % 3_600 / 60,
+            secs_of_day % 60,
+        )?;
+
+        if self.nanos != 0 {
+            write!(f, ".{:09}", self.nanos)?;
+        }
+
+        f.write_str("Z")
+    }
+}
+
+/// Convert a number of days since the unix epoch into a civil date.
+///
+/// See: http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = if z >= 0 { z } else { z - 146_096 } / 146_097;
+    let doe = (z - era * 146_097) as u64;
+    let yoe = (doe - doe / 1_460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe as i64 + era * 400;
+
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_rfc3339() {
+        for (secs, nanos, expected) in &[
+            (0, 0, "1970-01-01T00:00:00Z"),
+            (951_782_400, 0, "2000-02-29T00:00:00Z"),
+            (1_700_000_000, 500_000_000, "2023-11-14T22:13:20.500000000Z"),
+            (-1, 0, "1969-12-31T23:59:59Z"),
+        ] {
+            assert_eq!(
+                *expected,
+                Rfc3339 {
+                    secs: *secs,
+                    nanos: *nanos
+                }
+                .to_string()
+            );
+        }
+    }
+}
@@ -0,0 +1,458 @@
+//! A TOML serializer for visited values.
+//!
+//! A record or map is written as a TOML document of `key = value` lines.
+//! Nested records and maps are written as inline tables, sequences as
+//! arrays, and enum variants are externally tagged. Timestamps are written
+//! as offset date-times.
+//!
+//! TOML can't represent everything that can be visited. Null values,
+//! integers outside the range of `i64`, and documents that aren't tables
+//! are errors.
+
+use crate::std::{fmt, mem};
+
+use crate::{
+    json::{self, is_number},
+    timestamp::Rfc3339,
+    Capability, Error, TextWriter, Visitor,
+};
+
+/// A visitor that writes records and maps as TOML documents.
+///
+/// ```
+/// let mut toml = ser::toml::Writer::new(String::new());
+///
+/// let value = ser::value!({ "name": "app", "ports": [80, 443], "db": { "pool": 4 } });
+/// ser::Visit::visit(&value, &mut toml).unwrap();
+///
+/// assert_eq!("name = \"app\"\nports = [80, 443]\ndb = { pool = 4 }\n", toml.into_inner());
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    first: bool,
+    key: bool,
+    depth: usize,
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            first: true,
+            key: false,
+            depth: 0,
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Check that a value is inside the document's table.
+    fn value(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            Err(Error::msg(
+                "only records and maps can be written as TOML documents",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Write a value that doesn't need quoting, or a key.
+    fn raw(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        if self.key {
+            return self.write_key(v);
+        }
+
+        self.value()?;
+        self.out.write_fmt(v)?;
+
+        self.done()
+    }
+
+    /// Write a quoted and escaped string, or a key.
+    fn string(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        if self.key {
+            return self.write_key(v);
+        }
+
+        self.value()?;
+        self.out.write_char('"')?;
+        Escape(&mut self.out).write_fmt(v)?;
+        self.out.write_char('"')?;
+
+        self.done()
+    }
+
+    /// Write a key, quoting it unless it's a valid bare key.
+    fn write_key(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        self.key = false;
+
+        let mut bare = Bare {
+            empty: true,
+            bare: true,
+        };
+        fmt::write(&mut bare, v)?;
+
+        if bare.bare && !bare.empty {
+            self.out.write_fmt(v)?;
+        } else {
+            self.out.write_char('"')?;
+            Escape(&mut self.out).write_fmt(v)?;
+            self.out.write_char('"')?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a newline after a complete entry in the document's table.
+    fn done(&mut self) -> Result<(), Error> {
+        if self.depth == 1 {
+            self.out.write_char('\n')?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a separator before an element or key in an inline array or table.
+    fn elem(&mut self, padded: bool) -> Result<(), Error> {
+        let first = mem::replace(&mut self.first, false);
+
+        if self.depth > 1 {
+            if !first {
+                self.out.write_str(", ")?;
+            } else if padded {
+                self.out.write_char(' ')?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn begin(&mut self, open: char, table: bool) -> Result<(), Error> {
+        if self.key {
+            return Err(Error::msg("map keys must be strings, numbers, or booleans"));
+        }
+
+        if !table {
+            self.value()?;
+        }
+
+        self.first = true;
+        self.depth += 1;
+
+        if self.depth > 1 {
+            self.out.write_char(open)?;
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self, close: char, padded: bool) -> Result<(), Error> {
+        self.depth -= 1;
+
+        let empty = mem::replace(&mut self.first, false);
+
+        if self.depth > 0 {
+            if padded && !empty {
+                self.out.write_char(' ')?;
+            }
+
+            self.out.write_char(close)?;
+        }
+
+        self.done()
+    }
+
+    fn integer_out_of_range(&mut self) -> Result<(), Error> {
+        Err(Error::msg("TOML integers must fit in an `i64`"))
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.raw(format_args!("{}", v))
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        if v > i64::MAX as u64 {
+            return self.integer_out_of_range();
+        }
+
+        self.raw(format_args!("{}", v))
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        if v > i64::MAX as i128 || v < i64::MIN as i128 {
+            return self.integer_out_of_range();
+        }
+
+        self.raw(format_args!("{}", v))
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        if v > i64::MAX as u128 {
+            return self.integer_out_of_range();
+        }
+
+        self.raw(format_args!("{}", v))
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        if v.is_nan() {
+            self.raw(format_args!("nan"))
+        } else if v.is_infinite() {
+            self.raw(format_args!("{}inf", if v < 0.0 { "-" } else { "" }))
+        } else {
+            // `Debug` always includes a fractional part or exponent
+            self.raw(format_args!("{:?}", v))
+        }
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        // Integers need to fit in an `i64`, but floats can be any size
+        if is_number(v) && (v.contains(['.', 'e', 'E']) || v.parse::<i64>().is_ok()) {
+            self.raw(format_args!("{}", v))
+        } else {
+            self.string(format_args!("{}", v))
+        }
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.raw(format_args!("{}", v))
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.string(format_args!("{}", v))
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.begin('[', false)?;
+
+        for b in v {
+            self.elem(false)?;
+            self.raw(format_args!("{}", b))?;
+        }
+
+        self.end(']', false)
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        Err(Error::msg("TOML can't represent null values"))
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin('{', true)
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.elem(true)?;
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.out.write_str(" = ")?;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end('}', true)
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin('[', false)
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.elem(false)
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end(']', false)
+    }
+
+    fn record_begin(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin('{', true)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.map_key()?;
+        self.write_key(format_args!("{}", name))?;
+        self.map_value()
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end('}', true)
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.string(format_args!("{}", variant))
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.begin('{', false)?;
+        self.record_field(variant)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.end('}', true)
+    }
+
+    fn visit_timestamp(&mut self, secs: i64, nanos: u32) -> Result<(), Error> {
+        self.raw(format_args!("{}", Rfc3339 { secs, nanos }))
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(capability, Capability::NumberStr | Capability::NonFinite)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.string(*args)
+    }
+}
+
+/// Escape a string written into a basic string or quoted key.
+///
+/// TOML uses the same escapes as JSON, but also doesn't allow `DEL`.
+struct Escape<W>(W);
+
+impl<W> fmt::Write for Escape<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for (i, part) in s.split('\u{7f}').enumerate() {
+            if i > 0 {
+                self.0.write_str("\\u007F")?;
+            }
+
+            json::Escape(&mut self.0).write_str(part)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Check whether a key can be written without quotes.
+struct Bare {
+    empty: bool,
+    bare: bool,
+}
+
+impl fmt::Write for Bare {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.empty &= s.is_empty();
+        self.bare &= s
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-');
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_toml(v: &dyn Visit, expected: &str) {
+        let mut toml = Writer::new(VisitArgs::new());
+        v.visit(&mut toml).unwrap();
+
+        assert_eq!(Some(expected), toml.into_inner().to_str());
+    }
+
+    fn assert_toml_err(v: &dyn Visit) {
+        assert!(v.visit(&mut Writer::new(VisitArgs::new())).is_err());
+    }
+
+    #[test]
+    fn write_document() {
+        assert_toml(
+            &crate::value!({ "a": 1, "b c": "d\"", "e": [1.5, f64::NEG_INFINITY], "f": {}, "": true }),
+            "a = 1\n\"b c\" = \"d\\\"\"\ne = [1.5, -inf]\nf = {}\n\"\" = true\n",
+        );
+        assert_toml(&crate::value!({}), "");
+    }
+
+    #[test]
+    fn write_escaped() {
+        assert_toml(
+            &crate::value!({ "a": "b\u{7f}c\u{1}", "d\u{7f}": 1 }),
+            "a = \"b\\u007Fc\\u0001\"\n\"d\\u007F\" = 1\n",
+        );
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_toml(
+            &crate::value!({ "a": { "b": [{ "c": 1 }, []], "d": 2 } }),
+            "a = { b = [{ c = 1 }, []], d = 2 }\n",
+        );
+
+        let mut toml = Writer::new(VisitArgs::new());
+        toml.record_begin("Record", Some(3)).unwrap();
+        toml.record_field("shape").unwrap();
+        toml.variant_begin("Shape", "Circle", 0).unwrap();
+        toml.visit_f64(1.0).unwrap();
+        toml.variant_end().unwrap();
+        toml.record_field("kind").unwrap();
+        toml.visit_variant("Kind", "A", 0).unwrap();
+        toml.record_field("at").unwrap();
+        toml.visit_timestamp(0, 0).unwrap();
+        toml.record_end().unwrap();
+
+        assert_eq!(
+            Some("shape = { Circle = 1.0 }\nkind = \"A\"\nat = 1970-01-01T00:00:00Z\n"),
+            toml.into_inner().to_str()
+        );
+    }
+
+    #[test]
+    fn write_invalid() {
+        assert_toml_err(&1);
+        assert_toml_err(&&[1][..]);
+        assert_toml_err(&crate::value!({ "a": null }));
+        assert_toml_err(&crate::value!({ "a": u64::MAX }));
+
+        let mut toml = Writer::new(VisitArgs::new());
+        toml.map_begin(None).unwrap();
+        toml.map_key().unwrap();
+        assert!(toml.seq_begin(None).is_err());
+    }
+}
@@ -0,0 +1,240 @@
+//! Ad-hoc structured values for visiting.
+//!
+//! A `Value` is built inline with the `value!` macro, using JSON-like
+//! syntax, and borrows everything it's built from. Arrays are visited as
+//! sequences, objects as maps with string keys, and `null` as unit. Any
+//! other expression is visited through its own `Visit` implementation.
+
+#[allow(unused_imports)]
+use crate::{imp, EnsureVisit, Error, Visit, Visitor};
+
+/// An ad-hoc structured value.
+///
+/// Values are usually built with the `value!` macro, which borrows
+/// everything it's given, so no allocation is needed.
+#[derive(Debug, Clone, Copy)]
+pub enum Value<'a> {
+    /// A null value, visited as unit.
+    Null,
+    /// Any other value.
+    Visit { value: &'a dyn Visit },
+    /// A sequence of values.
+    Seq { elems: &'a [Value<'a>] },
+    /// A map of string keys to values.
+    Map { entries: &'a [(&'a str, Value<'a>)] },
+}
+
+/// Build a `Value` inline using JSON-like syntax.
+///
+/// Arrays and objects are nested values, `null` is a null value, and any
+/// other expression is borrowed as a `&dyn Visit`. Object keys must be
+/// string literals.
+///
+/// ```
+/// let tags = ["a", "b"];
+///
+/// let value = ser::value!({
+///     "id": 123,
+///     "tags": [tags[0], tags[1]],
+///     "parent": null,
+/// });
+/// ```
+#[macro_export]
+macro_rules! value {
+    (null) => {
+        $crate::Value::Null
+    };
+    ([$($elems:tt)*]) => {
+        $crate::Value::Seq { elems: &$crate::value!(@seq [] $($elems)*) }
+    };
+    ({$($entries:tt)*}) => {
+        $crate::Value::Map { entries: &$crate::value!(@map [] $($entries)*) }
+    };
+    (@seq [$($out:expr,)*]) => {
+        [$($out,)*]
+    };
+    (@seq [$($out:expr,)*] null $(, $($rest:tt)*)?) => {
+        $crate::value!(@seq [$($out,)* $crate::Value::Null,] $($($rest)*)?)
+    };
+    (@seq [$($out:expr,)*] [$($elems:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::value!(@seq [$($out,)* $crate::value!([$($elems)*]),] $($($rest)*)?)
+    };
+    (@seq [$($out:expr,)*] {$($entries:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::value!(@seq [$($out,)* $crate::value!({$($entries)*}),] $($($rest)*)?)
+    };
+    (@seq [$($out:expr,)*] $value:expr $(, $($rest:tt)*)?) => {
+        $crate::value!(@seq [$($out,)* $crate::value!($value),] $($($rest)*)?)
+    };
+    (@map [$($out:expr,)*]) => {
+        [$($out,)*]
+    };
+    (@map [$($out:expr,)*] $key:literal : null $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out,)* ($key, $crate::Value::Null),] $($($rest)*)?)
+    };
+    (@map [$($out:expr,)*] $key:literal : [$($elems:tt)*] $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out,)* ($key, $crate::value!([$($elems)*])),] $($($rest)*)?)
+    };
+    (@map [$($out:expr,)*] $key:literal : {$($entries:tt)*} $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out,)* ($key, $crate::value!({$($entries)*})),] $($($rest)*)?)
+    };
+    (@map [$($out:expr,)*] $key:literal : $value:expr $(, $($rest:tt)*)?) => {
+        $crate::value!(@map [$($out,)* ($key, $crate::value!($value)),] $($($rest)*)?)
+    };
+    ($value:expr) => {
+        $crate::Value::Visit { value: &$value }
+    };
+}
+
+/// Build a list of key-value pairs.
+///
+/// Keys are identifiers or string literals. Values are borrowed as
+/// `&dyn Visit`, so the list is a `&[(&str, &dyn Visit)]`.
+///
+/// ```
+/// let name = "alice";
+///
+/// let pairs = ser::kv!(user_id = 42, name = name, "user.admin" = false);
+///
+/// assert_eq!(3, pairs.len());
+/// assert_eq!("user.admin", pairs[2].0);
+/// ```
+#[macro_export]
+macro_rules! kv {
+    (@key $key:ident) => {
+        stringify!($key)
+    };
+    (@key $key:literal) => {
+        $key
+    };
+    ($($key:tt = $value:expr),* $(,)?) => {
+        &[$(($crate::kv!(@key $key), &$value as &dyn $crate::Visit)),*] as &[(&str, &dyn $crate::Visit)]
+    };
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<'a> Visit for Value<'a> {
+    fn visit<'v>(&'v self, visitor: &mut dyn Visitor<'v>) -> Result<(), Error> {
+        match *self {
+            Value::Null => visitor.visit_unit(),
+            Value::Visit { value } => value.visit(visitor),
+            Value::Seq { elems } => crate::visit_seq(elems.len(), elems.iter(), visitor),
+            Value::Map { entries } => {
+                visitor.map_begin(Some(entries.len()))?;
+
+                for (k, v) in entries {
+                    visitor.map_key()?;
+                    visitor.visit_borrowed_str(k)?;
+
+                    visitor.map_value()?;
+                    v.visit(visitor)?;
+                }
+
+                visitor.map_end()
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "serde_interop"))]
+impl<'a> imp::VisitPrivate for Value<'a> {}
+
+#[cfg(feature = "serde_interop")]
+impl<'a> serde::Serialize for Value<'a> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match *self {
+            Value::Null => serializer.serialize_unit(),
+            Value::Visit { value } => value.serialize(serializer),
+            Value::Seq { elems } => serializer.collect_seq(elems),
+            Value::Map { entries } => serializer.collect_map(entries.iter().map(|(k, v)| (k, v))),
+        }
+    }
+}
+
+ensure_visit!(Value<'static>);
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_value() {
+        use crate::tests::{assert_visit, Token};
+
+        let tags = ["a", "b"];
+
+        assert_visit(
+            &value!({
+                "id": 123,
+                "tags": [tags[0], tags[1]],
+                "parent": null,
+                "nested": { "empty": [] },
+            }),
+            &[
+                Token::MapBegin(Some(4)),
+                Token::MapKey,
+                Token::Str("id"),
+                Token::MapValue,
+                Token::I64(123),
+                Token::MapKey,
+                Token::Str("tags"),
+                Token::MapValue,
+                Token::SeqBegin(Some(2)),
+                Token::SeqElem,
+                Token::Str("a"),
+                Token::SeqElem,
+                Token::Str("b"),
+                Token::SeqEnd,
+                Token::MapKey,
+                Token::Str("parent"),
+                Token::MapValue,
+                Token::Unit,
+                Token::MapKey,
+                Token::Str("nested"),
+                Token::MapValue,
+                Token::MapBegin(Some(1)),
+                Token::MapKey,
+                Token::Str("empty"),
+                Token::MapValue,
+                Token::SeqBegin(Some(0)),
+                Token::SeqEnd,
+                Token::MapEnd,
+                Token::MapEnd,
+            ],
+        );
+
+        assert_visit(&value!(1 + 1), &[Token::I64(2)]);
+        assert_visit(&value!(null), &[Token::Unit]);
+    }
+
+    #[test]
+    #[cfg(feature = "serde_interop")]
+    fn serialize_value() {
+        let value = value!({ "id": 123, "tags": ["a", "b"], "parent": null });
+
+        assert_eq!(
+            r#"{"id":123,"tags":["a","b"],"parent":null}"#,
+            serde_json::to_string(&value).unwrap(),
+        );
+    }
+
+    #[test]
+    #[cfg(not(feature = "serde_interop"))]
+    fn visit_kv() {
+        use crate::tests::{assert_visit, Token};
+
+        let name = "alice";
+        let pairs = kv!(user_id = 42, name = name, "user.admin" = false,);
+
+        assert_eq!("user_id", pairs[0].0);
+        assert_eq!("name", pairs[1].0);
+        assert_eq!("user.admin", pairs[2].0);
+
+        assert_visit(pairs[0].1, &[Token::I64(42)]);
+        assert_visit(pairs[1].1, &[Token::Str("alice")]);
+        assert_visit(pairs[2].1, &[Token::Bool(false)]);
+
+        assert!(kv!().is_empty());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::Error;
+
+#[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+use crate::std::fmt;
+
+#[cfg(feature = "std")]
+use crate::std::vec::Vec;
+
+/// An output for binary formats.
+///
+/// This is the binary counterpart to `fmt::Write`. It's implemented for
+/// `Vec<u8>` with the `std` feature, and for `&mut [u8]`, which is
+/// advanced past the bytes written to it, without.
+pub trait WriteBytes {
+    /// Write all of the given bytes.
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error>;
+}
+
+#[cfg(feature = "std")]
+impl WriteBytes for Vec<u8> {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.extend_from_slice(bytes);
+
+        Ok(())
+    }
+}
+
+impl WriteBytes for &mut [u8] {
+    fn write_bytes(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        if bytes.len() > self.len() {
+            return Err(Error::msg("the buffer is full"));
+        }
+
+        let (head, tail) = crate::std::mem::take(self).split_at_mut(bytes.len());
+        head.copy_from_slice(bytes);
+        *self = tail;
+
+        Ok(())
+    }
+}
+
+/// A visitor that writes text to an output.
+///
+/// This is implemented by the writers for text formats, like `json::Writer`,
+/// so they can be used generically, such as by `io::Writer`.
+pub trait TextWriter<W> {
+    /// Get a mutable reference to the output.
+    fn get_mut(&mut self) -> &mut W;
+
+    /// Get the output.
+    fn into_inner(self) -> W;
+}
+
+/// Get the length of formatted text in bytes.
+///
+/// Binary formats that prefix strings with their length can use this to
+/// write formatted text without buffering it, by formatting it twice.
+#[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+pub(crate) fn fmt_len(args: fmt::Arguments) -> Result<usize, Error> {
+    struct Len(usize);
+
+    impl fmt::Write for Len {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            self.0 += s.len();
+            Ok(())
+        }
+    }
+
+    let mut len = Len(0);
+    fmt::write(&mut len, args)?;
+
+    Ok(len.0)
+}
+
+/// Write formatted text to an output for bytes.
+///
+/// The text must be exactly `len` bytes, as returned by `fmt_len`.
+/// Formatting isn't guaranteed to produce the same text each time, so any
+/// text past `len` isn't written, and a mismatch in either direction is an
+/// error instead of leaving a length prefix that doesn't match its string.
+#[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+pub(crate) fn write_fmt(
+    out: &mut impl WriteBytes,
+    args: fmt::Arguments,
+    len: usize,
+) -> Result<(), Error> {
+    struct Bytes<'a, W> {
+        out: &'a mut W,
+        remaining: usize,
+        error: Option<Error>,
+    }
+
+    impl<'a, W> fmt::Write for Bytes<'a, W>
+    where
+        W: WriteBytes,
+    {
+        fn write_str(&mut self, s: &str) -> fmt::Result {
+            if s.len() > self.remaining {
+                self.error = Some(len_mismatch());
+                return Err(fmt::Error);
+            }
+
+            self.remaining -= s.len();
+
+            self.out.write_bytes(s.as_bytes()).map_err(|err| {
+                self.error = Some(err);
+                fmt::Error
+            })
+        }
+    }
+
+    let mut bytes = Bytes {
+        out,
+        remaining: len,
+        error: None,
+    };
+
+    if fmt::write(&mut bytes, args).is_err() {
+        return Err(bytes
+            .error
+            .unwrap_or_else(|| Error::msg("an error occurred while formatting a value")));
+    }
+
+    if bytes.remaining != 0 {
+        return Err(len_mismatch());
+    }
+
+    Ok(())
+}
+
+#[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+fn len_mismatch() -> Error {
+    Error::msg("a value formatted to a different length than it was measured at")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_slice() {
+        let mut buf = [0; 4];
+        let mut out = &mut buf[..];
+
+        out.write_bytes(&[1, 2]).unwrap();
+        out.write_bytes(&[3]).unwrap();
+        assert!(out.write_bytes(&[4, 5]).is_err());
+        assert_eq!(1, out.len());
+
+        assert_eq!([1, 2, 3, 0], buf);
+    }
+
+    #[test]
+    #[cfg(any(feature = "msgpack", feature = "postcard", feature = "digest"))]
+    fn write_fmt_len_mismatch() {
+        use crate::{std::cell::Cell, tests::Growing};
+
+        let mut buf = [0; 8];
+
+        let v = Growing(Cell::new(0));
+        let len = fmt_len(format_args!("{}", v)).unwrap();
+        assert_eq!(1, len);
+        assert!(write_fmt(&mut &mut buf[..], format_args!("{}", v), len).is_err());
+
+        let v = Growing(Cell::new(1));
+        assert!(write_fmt(&mut &mut buf[..], format_args!("{}", v), 3).is_err());
+
+        let v = Growing(Cell::new(0));
+        write_fmt(&mut &mut buf[..], format_args!("{}", v), 1).unwrap();
+        assert_eq!(b'a', buf[0]);
+    }
+}
@@ -0,0 +1,543 @@
+//! An XML serializer for visited values.
+//!
+//! Records and maps are written as elements with a child element for each
+//! field or entry, sequences as elements with an `<item>` child for each
+//! element, and primitives as text nodes. The root element is named after
+//! the visited record, or `<value>` for anything else. Enum variants are
+//! written as a child element named after the variant, or as text for
+//! variants without data. Optional values that are `None` and unit values
+//! are written as empty elements.
+//!
+//! With attributes enabled, fields and entries with primitive values are
+//! written as attributes of their parent element instead of as children,
+//! up to the first one that isn't primitive. `None` fields are left out.
+
+use crate::std::{fmt, str};
+
+use crate::{Error, TextWriter, Visitor};
+
+const MAX_NAMES: usize = 256;
+const MAX_DEPTH: usize = 32;
+
+/// A visitor that writes values as XML.
+///
+/// ```
+/// let mut xml = ser::xml::Writer::new(String::new());
+///
+/// let value = ser::value!({ "id": 1, "tags": ["a", "b"] });
+/// ser::Visit::visit(&value, &mut xml).unwrap();
+///
+/// assert_eq!(
+///     "<value><id>1</id><tags><item>a</item><item>b</item></tags></value>",
+///     xml.into_inner(),
+/// );
+/// ```
+///
+/// Use `Writer::attributes` to write primitive fields as attributes.
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    attributes: bool,
+    key: bool,
+    names: Names,
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output that writes fields as elements.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            attributes: false,
+            key: false,
+            names: Names::new(),
+        }
+    }
+
+    /// Create a writer over the given output that writes primitive fields as attributes.
+    ///
+    /// ```
+    /// let mut xml = ser::xml::Writer::attributes(String::new());
+    ///
+    /// let value = ser::value!({ "id": 1, "name": "a & b", "tags": ["c"] });
+    /// ser::Visit::visit(&value, &mut xml).unwrap();
+    ///
+    /// assert_eq!(
+    ///     "<value id=\"1\" name=\"a &amp; b\"><tags><item>c</item></tags></value>",
+    ///     xml.into_inner(),
+    /// );
+    /// ```
+    pub fn attributes(out: W) -> Self {
+        Writer {
+            attributes: true,
+            ..Writer::new(out)
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Make sure there's an element for the root value.
+    fn root(&mut self, name: &str) -> Result<(), Error> {
+        if self.names.depth == 0 {
+            self.names.push(format_args!("{}", name), false)?;
+        }
+
+        Ok(())
+    }
+
+    /// Whether the current value can be written as an attribute of its parent.
+    fn is_attribute(&self) -> bool {
+        let depth = self.names.depth;
+
+        self.attributes
+            && depth > 1
+            && self.names.frames[depth - 1].attribute
+            && self.names.frames[depth - 2].open
+    }
+
+    /// Write the start tag of the current element, leaving it open for attributes.
+    fn start(&mut self) -> Result<(), Error> {
+        let depth = self.names.depth;
+
+        if depth > 1 && self.names.frames[depth - 2].open {
+            self.names.frames[depth - 2].open = false;
+            self.out.write_char('>')?;
+        }
+
+        self.names.frames[depth - 1].open = true;
+
+        self.out.write_char('<')?;
+        self.out.write_str(self.names.name(depth - 1))?;
+
+        Ok(())
+    }
+
+    /// Write the end tag of the current element.
+    fn end(&mut self) -> Result<(), Error> {
+        let depth = self.names.depth;
+
+        if depth == 0 {
+            return Err(Error::msg("an element was ended without being started"));
+        }
+
+        if self.names.frames[depth - 1].open {
+            self.out.write_str("/>")?;
+        } else {
+            self.out.write_str("</")?;
+            self.out.write_str(self.names.name(depth - 1))?;
+            self.out.write_char('>')?;
+        }
+
+        self.names.pop();
+
+        Ok(())
+    }
+
+    /// Write a primitive as text or an attribute, or use it as the name of a map entry.
+    fn text(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        if self.key {
+            self.key = false;
+            return self.names.push(v, true);
+        }
+
+        self.root("value")?;
+
+        if self.is_attribute() {
+            let depth = self.names.depth;
+
+            self.out.write_char(' ')?;
+            self.out.write_str(self.names.name(depth - 1))?;
+            self.out.write_str("=\"")?;
+            self.escape(v, true)?;
+            self.out.write_char('"')?;
+
+            self.names.pop();
+
+            return Ok(());
+        }
+
+        self.start()?;
+        self.names.frames[self.names.depth - 1].open = false;
+        self.out.write_char('>')?;
+        self.escape(v, false)?;
+
+        self.end()
+    }
+
+    /// Write an escaped text node or attribute value, failing on characters XML can't represent.
+    fn escape(&mut self, v: fmt::Arguments, attribute: bool) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        let mut escape = Escape {
+            out: &mut self.out,
+            attribute,
+            invalid: false,
+        };
+
+        match escape.write_fmt(v) {
+            Ok(()) => Ok(()),
+            Err(_) if escape.invalid => Err(Error::msg("XML can't represent control characters")),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    fn begin(&mut self, root: &str) -> Result<(), Error> {
+        if self.key {
+            return Err(Error::msg("map keys must be strings, numbers, or booleans"));
+        }
+
+        self.root(root)?;
+        self.start()
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.text(format_args!("{}", v))
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        if self.key {
+            return Err(Error::msg("map keys must be strings, numbers, or booleans"));
+        }
+
+        self.root("value")?;
+
+        if self.is_attribute() {
+            self.names.pop();
+
+            Ok(())
+        } else {
+            self.start()?;
+            self.end()
+        }
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin("value")
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin("value")
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.names.push(format_args!("item"), false)
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn record_begin(&mut self, name: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin(name)
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.names.push(format_args!("{}", name), true)
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.text(format_args!("{}", variant))
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.begin("value")?;
+        self.names.push(format_args!("{}", variant), false)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.end()
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.text(*args)
+    }
+}
+
+/// The names of the elements being written.
+#[derive(Debug)]
+struct Names {
+    buf: [u8; MAX_NAMES],
+    len: usize,
+    frames: [Frame; MAX_DEPTH],
+    depth: usize,
+}
+
+/// An element that's being written.
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    start: usize,
+    // Whether the element could be written as an attribute of its parent
+    attribute: bool,
+    // Whether the start tag of the element is still open for attributes
+    open: bool,
+}
+
+impl Names {
+    fn new() -> Self {
+        Names {
+            buf: [0; MAX_NAMES],
+            len: 0,
+            frames: [Frame {
+                start: 0,
+                attribute: false,
+                open: false,
+            }; MAX_DEPTH],
+            depth: 0,
+        }
+    }
+
+    fn name(&self, i: usize) -> &str {
+        let end = if i + 1 < self.depth {
+            self.frames[i + 1].start
+        } else {
+            self.len
+        };
+
+        // Names are checked when they're pushed
+        str::from_utf8(&self.buf[self.frames[i].start..end]).expect("invalid name")
+    }
+
+    fn push(&mut self, name: fmt::Arguments, attribute: bool) -> Result<(), Error> {
+        if self.depth == MAX_DEPTH {
+            return Err(Error::msg("values are nested too deeply"));
+        }
+
+        let start = self.len;
+        if fmt::write(self, name).is_err() {
+            self.len = start;
+            return Err(Error::msg("element names are too long"));
+        }
+
+        self.frames[self.depth] = Frame {
+            start,
+            attribute,
+            open: false,
+        };
+        self.depth += 1;
+
+        if !is_name(self.name(self.depth - 1)) {
+            self.pop();
+            return Err(Error::msg("names must be valid XML names"));
+        }
+
+        Ok(())
+    }
+
+    fn pop(&mut self) {
+        self.depth -= 1;
+        self.len = self.frames[self.depth].start;
+    }
+}
+
+impl fmt::Write for Names {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        if self.len + s.len() > MAX_NAMES {
+            return Err(fmt::Error);
+        }
+
+        self.buf[self.len..self.len + s.len()].copy_from_slice(s.as_bytes());
+        self.len += s.len();
+
+        Ok(())
+    }
+}
+
+/// Whether a string is a valid XML name.
+///
+/// This is stricter than XML itself for characters outside of ASCII.
+fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+
+    match chars.next() {
+        Some(c) if c.is_alphabetic() || c == '_' => (),
+        _ => return false,
+    }
+
+    chars.all(|c| c.is_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+/// Escape text or an attribute value.
+///
+/// Characters that aren't allowed anywhere in an XML document, like most
+/// control characters, can't be escaped, so they fail instead. Parsers
+/// normalize whitespace in attribute values to spaces, so tabs and line
+/// breaks in attributes are written as character references.
+struct Escape<W> {
+    out: W,
+    attribute: bool,
+    invalid: bool,
+}
+
+impl<W> fmt::Write for Escape<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+
+        for (i, c) in s.char_indices() {
+            let escape = match c {
+                '&' => "&amp;",
+                '<' => "&lt;",
+                '>' => "&gt;",
+                '"' => "&quot;",
+                '\t' if self.attribute => "&#9;",
+                '\n' if self.attribute => "&#10;",
+                '\r' if self.attribute => "&#13;",
+                '\t' | '\n' | '\r' | ' '..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'.. => {
+                    continue
+                }
+                _ => {
+                    self.invalid = true;
+                    return Err(fmt::Error);
+                }
+            };
+
+            self.out.write_str(&s[start..i])?;
+            self.out.write_str(escape)?;
+
+            start = i + 1;
+        }
+
+        self.out.write_str(&s[start..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_xml_with(writer: fn(VisitArgs) -> Writer<VisitArgs>, v: &dyn Visit, expected: &str) {
+        let mut xml = writer(VisitArgs::new());
+        v.visit(&mut xml).unwrap();
+
+        assert_eq!(Some(expected), xml.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_elements() {
+        assert_xml_with(Writer::new, &1, "<value>1</value>");
+        assert_xml_with(Writer::new, &"<a & b>", "<value>&lt;a &amp; b&gt;</value>");
+        assert_xml_with(Writer::new, &None::<u8>, "<value/>");
+        assert_xml_with(
+            Writer::new,
+            &crate::value!({ "a": { "b": null }, "c": [], "d": [[1]] }),
+            "<value><a><b/></a><c/><d><item><item>1</item></item></d></value>",
+        );
+    }
+
+    #[test]
+    fn write_attributes() {
+        assert_xml_with(
+            Writer::attributes,
+            &crate::value!({ "a": 1, "b": null, "c": "\"", "d": { "e": 2 }, "f": 3 }),
+            "<value a=\"1\" c=\"&quot;\"><d e=\"2\"/><f>3</f></value>",
+        );
+        assert_xml_with(
+            Writer::attributes,
+            &crate::value!({ "a": "b\tc\r\nd" }),
+            "<value a=\"b&#9;c&#13;&#10;d\"/>",
+        );
+        assert_xml_with(
+            Writer::attributes,
+            &crate::value!([1]),
+            "<value><item>1</item></value>",
+        );
+    }
+
+    #[test]
+    fn write_records() {
+        let mut xml = Writer::attributes(VisitArgs::new());
+        xml.record_begin("Shape", Some(2)).unwrap();
+        xml.record_field("kind").unwrap();
+        xml.visit_variant("Kind", "A", 0).unwrap();
+        xml.record_field("size").unwrap();
+        xml.variant_begin("Size", "Px", 0).unwrap();
+        xml.visit_u64(1).unwrap();
+        xml.variant_end().unwrap();
+        xml.record_end().unwrap();
+
+        assert_eq!(
+            Some("<Shape kind=\"A\"><size><Px>1</Px></size></Shape>"),
+            xml.into_inner().to_str()
+        );
+    }
+
+    #[test]
+    fn write_invalid_names() {
+        assert!(crate::value!({ "a b": 1 })
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!(crate::value!({ "1": 1 })
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!(crate::value!({ "": 1 })
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+    }
+
+    #[test]
+    fn write_invalid_chars() {
+        assert!("a\u{1}".visit(&mut Writer::new(VisitArgs::new())).is_err());
+        assert!("\u{1b}[0m"
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!("\u{ffff}"
+            .visit(&mut Writer::new(VisitArgs::new()))
+            .is_err());
+        assert!(crate::value!({ "a": "\u{0}" })
+            .visit(&mut Writer::attributes(VisitArgs::new()))
+            .is_err());
+
+        assert_xml_with(Writer::new, &"a\tb\n\u{e9}", "<value>a\tb\n\u{e9}</value>");
+    }
+
+    #[test]
+    fn write_unbalanced() {
+        assert!(Writer::new(VisitArgs::new()).map_end().is_err());
+        assert!(Writer::new(VisitArgs::new()).record_end().is_err());
+    }
+}
@@ -0,0 +1,515 @@
+//! A YAML serializer for visited values.
+//!
+//! Records and maps are written as block mappings, sequences as block
+//! sequences, and enum variants are externally tagged. Strings are written
+//! as plain scalars when they can't be mistaken for anything else, and
+//! double-quoted otherwise. Bytes are written as `!!binary` scalars.
+//! Optional and unit values are written as `null`.
+
+use crate::std::{fmt, mem};
+
+use crate::{
+    json::{is_number, Base64},
+    Capability, Error, TextWriter, Visitor,
+};
+
+/// A visitor that writes values as block-style YAML.
+///
+/// The output doesn't include a document marker or a trailing newline.
+///
+/// ```
+/// let mut yaml = ser::yaml::Writer::new(String::new());
+///
+/// let value = ser::value!({ "name": "app", "ports": [80, 443], "env": { "DEBUG": "true" } });
+/// ser::Visit::visit(&value, &mut yaml).unwrap();
+///
+/// assert_eq!(
+///     "name: app\nports:\n  - 80\n  - 443\nenv:\n  DEBUG: \"true\"",
+///     yaml.into_inner(),
+/// );
+/// ```
+#[derive(Debug)]
+pub struct Writer<W> {
+    out: W,
+    first: bool,
+    key: bool,
+    pending: Pending,
+    written: bool,
+    depth: usize,
+}
+
+/// An indicator that's been written and is waiting for its value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Pending {
+    None,
+    Key,
+    Dash,
+}
+
+impl<W> Writer<W>
+where
+    W: fmt::Write,
+{
+    /// Create a writer over the given output.
+    pub fn new(out: W) -> Self {
+        Writer {
+            out,
+            first: true,
+            key: false,
+            pending: Pending::None,
+            written: false,
+            depth: 0,
+        }
+    }
+
+    /// Get the inner output.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+
+    /// Write a space between an indicator and the scalar that follows it.
+    fn prefix(&mut self) -> Result<(), Error> {
+        self.key = false;
+        self.written = true;
+
+        if mem::replace(&mut self.pending, Pending::None) != Pending::None {
+            self.out.write_char(' ')?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a scalar that doesn't need quoting.
+    fn plain(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        self.prefix()?;
+        self.out.write_fmt(v)?;
+
+        Ok(())
+    }
+
+    /// Write a string, quoting it if it could be mistaken for something else.
+    fn string(&mut self, v: fmt::Arguments) -> Result<(), Error> {
+        use self::fmt::Write;
+
+        self.prefix()?;
+
+        let mut plain = Plain::new();
+        fmt::write(&mut plain, v)?;
+
+        if plain.is_plain() {
+            self.out.write_fmt(v)?;
+        } else {
+            self.out.write_char('"')?;
+            Escape(&mut self.out).write_fmt(v)?;
+            self.out.write_char('"')?;
+        }
+
+        Ok(())
+    }
+
+    /// Write a newline and indentation before a key or element.
+    ///
+    /// The first entry after a `-` is written on the same line as it.
+    fn entry(&mut self) -> Result<(), Error> {
+        self.first = false;
+
+        if mem::replace(&mut self.pending, Pending::None) == Pending::Dash {
+            self.out.write_char(' ')?;
+        } else if self.written {
+            self.out.write_char('\n')?;
+
+            for _ in 1..self.depth {
+                self.out.write_str("  ")?;
+            }
+        }
+
+        self.written = true;
+
+        Ok(())
+    }
+
+    fn begin(&mut self) -> Result<(), Error> {
+        if self.key {
+            return Err(Error::msg("map keys must be strings, numbers, or booleans"));
+        }
+
+        self.first = true;
+        self.depth += 1;
+
+        Ok(())
+    }
+
+    /// Complete a block, writing it in flow style if it's empty.
+    fn end(&mut self, empty: &str) -> Result<(), Error> {
+        self.depth -= 1;
+
+        if mem::replace(&mut self.first, false) {
+            self.plain(format_args!("{}", empty))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W> TextWriter<W> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn get_mut(&mut self) -> &mut W {
+        &mut self.out
+    }
+
+    fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+impl<'v, W> Visitor<'v> for Writer<W>
+where
+    W: fmt::Write,
+{
+    fn visit_i64(&mut self, v: i64) -> Result<(), Error> {
+        self.plain(format_args!("{}", v))
+    }
+
+    fn visit_u64(&mut self, v: u64) -> Result<(), Error> {
+        self.plain(format_args!("{}", v))
+    }
+
+    fn visit_i128(&mut self, v: i128) -> Result<(), Error> {
+        self.plain(format_args!("{}", v))
+    }
+
+    fn visit_u128(&mut self, v: u128) -> Result<(), Error> {
+        self.plain(format_args!("{}", v))
+    }
+
+    fn visit_f64(&mut self, v: f64) -> Result<(), Error> {
+        if v.is_nan() {
+            self.plain(format_args!(".nan"))
+        } else if v.is_infinite() {
+            self.plain(format_args!("{}.inf", if v < 0.0 { "-" } else { "" }))
+        } else {
+            // `Debug` always includes a fractional part or exponent
+            self.plain(format_args!("{:?}", v))
+        }
+    }
+
+    fn visit_number_str(&mut self, v: &str) -> Result<(), Error> {
+        if is_number(v) {
+            self.plain(format_args!("{}", v))
+        } else {
+            self.string(format_args!("{}", v))
+        }
+    }
+
+    fn visit_bool(&mut self, v: bool) -> Result<(), Error> {
+        self.plain(format_args!("{}", v))
+    }
+
+    fn visit_str(&mut self, v: &str) -> Result<(), Error> {
+        self.string(format_args!("{}", v))
+    }
+
+    fn visit_bytes(&mut self, v: &[u8]) -> Result<(), Error> {
+        self.plain(format_args!("!!binary {}", Base64(v)))
+    }
+
+    fn visit_none(&mut self) -> Result<(), Error> {
+        self.plain(format_args!("null"))
+    }
+
+    fn visit_unit(&mut self) -> Result<(), Error> {
+        self.visit_none()
+    }
+
+    fn map_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn map_key(&mut self) -> Result<(), Error> {
+        self.entry()?;
+        self.key = true;
+
+        Ok(())
+    }
+
+    fn map_value(&mut self) -> Result<(), Error> {
+        self.out.write_char(':')?;
+        self.pending = Pending::Key;
+
+        Ok(())
+    }
+
+    fn map_end(&mut self) -> Result<(), Error> {
+        self.end("{}")
+    }
+
+    fn seq_begin(&mut self, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn seq_elem(&mut self) -> Result<(), Error> {
+        self.entry()?;
+        self.out.write_char('-')?;
+        self.pending = Pending::Dash;
+
+        Ok(())
+    }
+
+    fn seq_end(&mut self) -> Result<(), Error> {
+        self.end("[]")
+    }
+
+    fn record_begin(&mut self, _: &str, _: Option<usize>) -> Result<(), Error> {
+        self.begin()
+    }
+
+    fn record_field(&mut self, name: &str) -> Result<(), Error> {
+        self.map_key()?;
+        self.string(format_args!("{}", name))?;
+        self.map_value()
+    }
+
+    fn record_end(&mut self) -> Result<(), Error> {
+        self.end("{}")
+    }
+
+    fn visit_variant(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.string(format_args!("{}", variant))
+    }
+
+    fn variant_begin(&mut self, _: &str, variant: &str, _: u32) -> Result<(), Error> {
+        self.begin()?;
+        self.record_field(variant)
+    }
+
+    fn variant_end(&mut self) -> Result<(), Error> {
+        self.end("{}")
+    }
+
+    fn supports(&self, capability: Capability) -> bool {
+        matches!(capability, Capability::NumberStr | Capability::NonFinite)
+    }
+
+    fn visit_fmt(&mut self, args: &fmt::Arguments) -> Result<(), Error> {
+        self.string(*args)
+    }
+}
+
+/// Check whether a string can be written as a plain scalar.
+///
+/// This is conservative, so some strings that could be plain are quoted.
+struct Plain {
+    len: usize,
+    start: [u8; 5],
+    prev: char,
+    plain: bool,
+}
+
+impl Plain {
+    fn new() -> Self {
+        Plain {
+            len: 0,
+            start: [0; 5],
+            prev: ' ',
+            plain: true,
+        }
+    }
+
+    fn is_plain(&self) -> bool {
+        if !self.plain || self.len == 0 || self.prev == ' ' || self.prev == ':' {
+            return false;
+        }
+
+        // Strings that would be read back as booleans or null
+        let start = &self.start[..self.len.min(5)];
+        !(self.len <= 5
+            && matches!(
+                start,
+                b"true" | b"false" | b"null" | b"yes" | b"no" | b"on" | b"off" | b"y" | b"n" | b"~"
+            ))
+    }
+}
+
+impl fmt::Write for Plain {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            if self.len == 0 {
+                // Indicators, and anything that could be read back as a number
+                self.plain &= !matches!(
+                    c,
+                    '-' | '?'
+                        | ':'
+                        | ','
+                        | '['
+                        | ']'
+                        | '{'
+                        | '}'
+                        | '#'
+                        | '&'
+                        | '*'
+                        | '!'
+                        | '|'
+                        | '>'
+                        | '\''
+                        | '"'
+                        | '%'
+                        | '@'
+                        | '`'
+                        | ' '
+                        | '.'
+                        | '+'
+                        | '0'..='9'
+                );
+            }
+
+            self.plain &= !(c.is_control()
+                || !is_printable(c)
+                || (c == ' ' && self.prev == ':')
+                || (c == '#' && self.prev == ' '));
+
+            if self.len < 5 {
+                self.start[self.len] = if c.is_ascii() {
+                    c.to_ascii_lowercase() as u8
+                } else {
+                    0
+                };
+            }
+
+            self.prev = c;
+            self.len += c.len_utf8();
+        }
+
+        Ok(())
+    }
+}
+
+/// Escape a string written into a double-quoted scalar.
+///
+/// Characters that YAML doesn't allow in documents, like `DEL` and the C1
+/// control characters, are escaped along with the C0 control characters.
+struct Escape<W>(W);
+
+impl<W> fmt::Write for Escape<W>
+where
+    W: fmt::Write,
+{
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut start = 0;
+
+        for (i, c) in s.char_indices() {
+            let escape = match c {
+                '"' => "\\\"",
+                '\\' => "\\\\",
+                '\n' => "\\n",
+                '\r' => "\\r",
+                '\t' => "\\t",
+                c if is_printable(c) => continue,
+                _ => "",
+            };
+
+            self.0.write_str(&s[start..i])?;
+            if !escape.is_empty() {
+                self.0.write_str(escape)?;
+            } else if (c as u32) <= 0xff {
+                write!(self.0, "\\x{:02x}", c as u32)?;
+            } else {
+                write!(self.0, "\\u{:04x}", c as u32)?;
+            }
+
+            start = i + c.len_utf8();
+        }
+
+        self.0.write_str(&s[start..])
+    }
+}
+
+/// Whether a character can be written into a YAML document as-is.
+///
+/// `NEL` is allowed by YAML, but is escaped so it isn't mistaken for a line break.
+fn is_printable(c: char) -> bool {
+    matches!(
+        c,
+        '\t' | '\n' | '\r' | ' '..='~' | '\u{a0}'..='\u{d7ff}' | '\u{e000}'..='\u{fffd}' | '\u{10000}'..
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{tests::VisitArgs, Visit};
+
+    fn assert_yaml(v: &dyn Visit, expected: &str) {
+        let mut yaml = Writer::new(VisitArgs::new());
+        v.visit(&mut yaml).unwrap();
+
+        assert_eq!(Some(expected), yaml.into_inner().to_str());
+    }
+
+    #[test]
+    fn write_scalars() {
+        assert_yaml(&1, "1");
+        assert_yaml(&1.5, "1.5");
+        assert_yaml(&f64::NEG_INFINITY, "-.inf");
+        assert_yaml(&true, "true");
+        assert_yaml(&None::<u8>, "null");
+        assert_yaml(&"a b", "a b");
+    }
+
+    #[test]
+    fn write_quoted() {
+        assert_yaml(&"", r#""""#);
+        assert_yaml(&"true", r#""true""#);
+        assert_yaml(&"No", r#""No""#);
+        assert_yaml(&"1.0", r#""1.0""#);
+        assert_yaml(&"- a", r#""- a""#);
+        assert_yaml(&"a: b", r#""a: b""#);
+        assert_yaml(&"a #b", r#""a #b""#);
+        assert_yaml(&"a ", r#""a ""#);
+        assert_yaml(&"a\nb", r#""a\nb""#);
+        assert_yaml(&"\u{1}", r#""\x01""#);
+        assert_yaml(&"a\u{7f}", r#""a\x7f""#);
+        assert_yaml(&"\u{85}b", r#""\x85b""#);
+        assert_yaml(&"\u{9f}", r#""\x9f""#);
+        assert_yaml(&"\u{ffff}", r#""\uffff""#);
+
+        assert_yaml(&"a:b", "a:b");
+        assert_yaml(&"nothing", "nothing");
+    }
+
+    #[test]
+    fn write_nested() {
+        assert_yaml(
+            &crate::value!({ "a": { "b": 1 }, "c": [1, { "d": 2, "e": [] }, [3, 4]], "f": {} }),
+            "a:\n  b: 1\nc:\n  - 1\n  - d: 2\n    e: []\n  - - 3\n    - 4\nf: {}",
+        );
+
+        let mut yaml = Writer::new(VisitArgs::new());
+        yaml.seq_begin(Some(2)).unwrap();
+        yaml.seq_elem().unwrap();
+        yaml.variant_begin("Shape", "Circle", 0).unwrap();
+        yaml.record_begin("Circle", Some(1)).unwrap();
+        yaml.record_field("radius").unwrap();
+        yaml.visit_u64(1).unwrap();
+        yaml.record_end().unwrap();
+        yaml.variant_end().unwrap();
+        yaml.seq_elem().unwrap();
+        yaml.visit_variant("Shape", "Point", 1).unwrap();
+        yaml.seq_end().unwrap();
+
+        assert_eq!(
+            Some("- Circle:\n    radius: 1\n- Point"),
+            yaml.into_inner().to_str()
+        );
+    }
+
+    #[test]
+    fn write_invalid_key() {
+        let mut yaml = Writer::new(VisitArgs::new());
+        yaml.map_begin(None).unwrap();
+        yaml.map_key().unwrap();
+        assert!(yaml.seq_begin(None).is_err());
+    }
+}